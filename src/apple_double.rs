@@ -0,0 +1,297 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Pairing AppleDouble `._name` files with the `name` they describe, see
+//! [`crate::ExtractOptions::pair_apple_double`].
+//!
+//! An AppleDouble file is a big-endian header (magic `0x00051607`, version,
+//! filler, entry count) followed by `(id, offset, length)` entries. Entry 9
+//! is the 32-byte Finder info; macOS appends the file's extended attributes
+//! to it behind an `ATTR` header, as `(offset, length, flags, name)`
+//! records. Entry 2 is the resource fork.
+
+use crate::report::ManifestEntry;
+use log::debug;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: u32 = 0x0005_1607;
+const RESOURCE_FORK: u32 = 2;
+const FINDER_INFO: u32 = 9;
+const FINDER_INFO_LEN: usize = 32;
+/// The `ATTR` header follows the Finder info and two bytes of padding.
+const ATTR_HEADER_OFFSET: usize = FINDER_INFO_LEN + 2;
+const ATTR_HEADER_LEN: usize = 36;
+
+/// What a `._name` file holds for `name`, as recorded on `name`'s manifest
+/// line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct AppleDouble {
+    /// The `._name` file, relative to the output directory.
+    pub path: String,
+    /// Names of the extended attributes it carries, in stored order.
+    pub xattrs: Vec<String>,
+    /// Whether the Finder info (type, creator, flags) is set.
+    pub finder_info: bool,
+    /// Size of the resource fork, 0 when there is none.
+    pub resource_fork: u64,
+}
+
+/// Attach every extracted `._name` file to the manifest entry of its
+/// sibling `name`, when there is one. Returns a message for each `._name`
+/// file that could not be read or parsed, and was left unpaired.
+pub(crate) fn pair(entries: &mut [ManifestEntry], output_dir: &Path) -> Vec<String> {
+    let mut unpaired = Vec::new();
+    let by_path: HashMap<_, _> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.path.clone(), i))
+        .collect();
+    for i in 0..entries.len() {
+        let double = &entries[i];
+        let Some(name) = double.path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(sibling) = name.strip_prefix("._").filter(|name| !name.is_empty()) else {
+            continue;
+        };
+        if double.kind != "file" {
+            continue;
+        }
+        let Some(&target) = by_path.get(&double.path.with_file_name(sibling)) else {
+            debug!("{} describes no extracted file", double.path.display());
+            continue;
+        };
+        let rel = double.path.strip_prefix(output_dir).unwrap_or(&double.path);
+        let rel = rel.to_string_lossy().replace('\\', "/");
+        let parsed = match fs::read(&double.path) {
+            Ok(data) => parse(&data),
+            // Written elsewhere (a writer factory, ...): pair it regardless.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(AppleDouble::default()),
+            Err(e) => Err(e.into()),
+        };
+        match parsed {
+            Ok(parsed) => {
+                entries[target].apple_double = Some(AppleDouble {
+                    path: rel,
+                    ..parsed
+                })
+            }
+            Err(e) => unpaired.push(format!("Not pairing {rel}: {e}")),
+        }
+    }
+    unpaired
+}
+
+/// Decode an AppleDouble file, leaving `path` empty.
+pub(crate) fn parse(data: &[u8]) -> Result<AppleDouble, Box<dyn Error>> {
+    if be32(data, 0)? != MAGIC {
+        return Err("not an AppleDouble file".into());
+    }
+    let mut double = AppleDouble::default();
+    let count = be16(data, 24)? as usize;
+    for i in 0..count {
+        let at = 26 + i * 12;
+        let (id, offset, len) = (
+            be32(data, at)?,
+            be32(data, at + 4)? as usize,
+            be32(data, at + 8)? as usize,
+        );
+        let entry = data
+            .get(offset..offset + len)
+            .ok_or("AppleDouble entry past end of file")?;
+        match id {
+            RESOURCE_FORK => double.resource_fork = len as u64,
+            FINDER_INFO => {
+                let info = entry.get(..FINDER_INFO_LEN).unwrap_or(entry);
+                double.finder_info = info.iter().any(|&b| b != 0);
+                let attrs = entry.get(ATTR_HEADER_OFFSET..).unwrap_or_default();
+                if attrs.starts_with(b"ATTR") {
+                    double.xattrs = xattr_names(data, offset + ATTR_HEADER_OFFSET)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(double)
+}
+
+/// The names in the `ATTR` block starting at `header`.
+fn xattr_names(data: &[u8], header: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let count = be16(data, header + 34)?;
+    let mut at = header + ATTR_HEADER_LEN;
+    let mut names = Vec::new();
+    for _ in 0..count {
+        let len = *data.get(at + 10).ok_or("truncated AppleDouble attribute")? as usize;
+        let name = data
+            .get(at + 11..at + 11 + len)
+            .ok_or("truncated AppleDouble attribute")?;
+        let name = name.split(|&b| b == 0).next().unwrap_or_default();
+        names.push(String::from_utf8_lossy(name).into_owned());
+        // Records are 4-byte aligned.
+        at = (at + 11 + len + 3) & !3;
+    }
+    Ok(names)
+}
+
+fn be16(data: &[u8], at: usize) -> Result<u16, Box<dyn Error>> {
+    let bytes = data.get(at..at + 2).ok_or("truncated AppleDouble")?;
+    Ok(u16::from_be_bytes(bytes.try_into()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = data.get(at..at + 4).ok_or("truncated AppleDouble")?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cpio;
+    use crate::{ExtractOptions, PkgExtractor, WarningKind};
+    use std::io::Cursor;
+
+    /// An AppleDouble file as macOS writes it: Finder info carrying the
+    /// extended attributes `xattrs`, then a resource fork.
+    fn apple_double(xattrs: &[(&str, &[u8])], resource_fork: &[u8]) -> Vec<u8> {
+        let mut records = Vec::new();
+        let mut values = Vec::new();
+        let records_len: usize = xattrs
+            .iter()
+            .map(|(name, _)| (11 + name.len() + 1 + 3) & !3)
+            .sum();
+        let data_start = 50 + ATTR_HEADER_OFFSET + ATTR_HEADER_LEN + records_len;
+        for (name, value) in xattrs {
+            let offset = (data_start + values.len()) as u32;
+            records.extend_from_slice(&offset.to_be_bytes());
+            records.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            records.extend_from_slice(&0u16.to_be_bytes());
+            records.push(name.len() as u8 + 1);
+            records.extend_from_slice(name.as_bytes());
+            records.push(0);
+            records.resize((records.len() + 3) & !3, 0);
+            values.extend_from_slice(value);
+        }
+        let finder_len = ATTR_HEADER_OFFSET + ATTR_HEADER_LEN + records.len() + values.len();
+
+        let mut data = Vec::new();
+        for field in [MAGIC, 0x0002_0000, 0, 0, 0, 0] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        data.extend_from_slice(&2u16.to_be_bytes());
+        for field in [FINDER_INFO, 50, finder_len as u32] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        let fork_offset = 50 + finder_len;
+        for field in [
+            RESOURCE_FORK,
+            fork_offset as u32,
+            resource_fork.len() as u32,
+        ] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        data.extend_from_slice(b"TEXTttxt");
+        data.resize(50 + ATTR_HEADER_OFFSET, 0);
+        data.extend_from_slice(b"ATTR");
+        let total = (fork_offset + resource_fork.len()) as u32;
+        for field in [0, total, data_start as u32, values.len() as u32, 0, 0, 0] {
+            data.extend_from_slice(&field.to_be_bytes());
+        }
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&(xattrs.len() as u16).to_be_bytes());
+        data.extend_from_slice(&records);
+        data.extend_from_slice(&values);
+        data.extend_from_slice(resource_fork);
+        data
+    }
+
+    #[test]
+    fn apple_double_files_are_paired_with_their_siblings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = tmp.path().join("manifest.json");
+        let out = tmp.path().join("out");
+        let double = apple_double(
+            &[
+                ("com.apple.quarantine", b"0083;00000000;Safari;"),
+                ("com.apple.FinderInfo", b""),
+            ],
+            b"resource fork",
+        );
+        let cpio = cpio(&[
+            ("./dir", 0o040755, b""),
+            ("./dir/._foo", 0o100644, &double),
+            ("./dir/foo", 0o100644, b"foo"),
+            ("./dir/._orphan", 0o100644, &double),
+        ]);
+
+        let mut extractor = PkgExtractor::new(Cursor::new(Vec::new()), Some(out.clone()))
+            .with_options(ExtractOptions {
+                manifest: Some(manifest.clone()),
+                pair_apple_double: true,
+                ..Default::default()
+            });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.write_reports().unwrap();
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(manifest)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[2]["path"], "dir/foo");
+        assert_eq!(
+            lines[2]["apple_double"],
+            serde_json::json!({
+                "path": "dir/._foo",
+                "xattrs": ["com.apple.quarantine", "com.apple.FinderInfo"],
+                "finder_info": true,
+                "resource_fork": 13,
+            })
+        );
+        assert!(lines
+            .iter()
+            .filter(|line| line["path"] != "dir/foo")
+            .all(|line| line.get("apple_double").is_none()));
+        // The sidecar itself is still written.
+        assert_eq!(fs::read(out.join("dir/._foo")).unwrap(), double);
+    }
+
+    #[test]
+    fn unparsable_apple_double_files_are_left_unpaired_with_a_warning() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let cpio = cpio(&[
+            ("./._bad", 0o100644, b"not an AppleDouble file"),
+            ("./bad", 0o100644, b"bad"),
+        ]);
+        let options = ExtractOptions {
+            manifest: Some(tmp.path().join("manifest.json")),
+            pair_apple_double: true,
+            ..Default::default()
+        };
+
+        let mut extractor = PkgExtractor::new(Cursor::new(Vec::new()), Some(out.clone()))
+            .with_options(options.clone());
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.write_reports().unwrap();
+        let warnings = &extractor.stats().warnings;
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert_eq!(warnings[0].kind, WarningKind::UnparsableFile);
+        assert!(warnings[0].message.contains("._bad"));
+
+        let mut strict = PkgExtractor::new(Cursor::new(Vec::new()), Some(out.clone()))
+            .with_options(ExtractOptions {
+                warnings_as_errors: true,
+                ..options
+            });
+        strict.extract_cpio(&cpio, &out).unwrap();
+        assert!(strict.write_reports().is_err());
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Extracting a series of packages with shared options, each into its own
+//! directory.
+
+use crate::{ExtractOptions, PkgExtractor};
+use log::info;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// How [`BatchExtractor`] materialises a package identical to one it
+/// already extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Make the output directory a symlink to the earlier output.
+    Symlink,
+    /// Copy the earlier output, so both trees stay independent.
+    Copy,
+}
+
+/// What [`BatchExtractor::extract`] did with a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutcome {
+    /// The package was extracted.
+    Extracted,
+    /// The package duplicated one extracted earlier into this directory
+    /// (the one actually written, e.g. with `avoid_clobber_dir`), whose
+    /// output was reused.
+    Reused(PathBuf),
+}
+
+/// Extracts many packages with the same [`ExtractOptions`], optionally
+/// reusing earlier output for exact duplicates (same table-of-contents
+/// digest) instead of extracting them again.
+#[derive(Debug, Default)]
+pub struct BatchExtractor {
+    options: ExtractOptions,
+    duplicates: Option<DuplicateAction>,
+    seen: HashMap<String, PathBuf>,
+}
+
+impl BatchExtractor {
+    pub fn new(options: ExtractOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Reuse the output of an earlier identical package by `action`
+    /// rather than extracting it again.
+    pub fn skip_duplicates(mut self, action: DuplicateAction) -> Self {
+        self.duplicates = Some(action);
+        self
+    }
+
+    /// Extract the package read from `reader` into `output_dir`.
+    ///
+    /// A duplicate reused by [`DuplicateAction::Symlink`] replaces an
+    /// empty `output_dir`, and fails if it holds anything else.
+    pub fn extract<R: Read + Seek + Debug>(
+        &mut self,
+        reader: R,
+        output_dir: impl Into<PathBuf>,
+    ) -> Result<BatchOutcome, Box<dyn Error>> {
+        let output_dir = output_dir.into();
+        let mut extractor =
+            PkgExtractor::new(reader, Some(output_dir.clone())).with_options(self.options.clone());
+
+        let Some(action) = self.duplicates else {
+            extractor.extract()?;
+            return Ok(BatchOutcome::Extracted);
+        };
+
+        let digest = extractor.toc_digest()?;
+        if let Some(previous) = self.seen.get(&digest) {
+            info!(
+                "{} duplicates {}, reusing its output",
+                output_dir.display(),
+                previous.display()
+            );
+            match action {
+                DuplicateAction::Symlink => {
+                    if output_dir.exists() {
+                        if fs::read_dir(&output_dir)?.next().is_some() {
+                            return Err(format!(
+                                "Cannot link {} to {}: it already exists and is not empty",
+                                output_dir.display(),
+                                previous.display()
+                            )
+                            .into());
+                        }
+                        fs::remove_dir(&output_dir)?;
+                    }
+                    symlink_dir(&std::path::absolute(previous)?, &output_dir)?
+                }
+                DuplicateAction::Copy => copy_tree(previous, &output_dir)?,
+            }
+            return Ok(BatchOutcome::Reused(previous.clone()));
+        }
+
+        let stats = extractor.extract()?;
+        self.seen.insert(digest, stats.output_dir);
+        Ok(BatchOutcome::Extracted)
+    }
+}
+
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_dir(target, link)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (target, link);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "symlinks not supported on this platform",
+        ))
+    }
+}
+
+/// Recursively copy `src` to `dst`, recreating symlinks rather than
+/// following them.
+fn copy_tree(src: &Path, dst: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_tree(&from, &to)?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(&from)?;
+            crate::create_symlink(&target, &to)?;
+        } else {
+            fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use std::io::Cursor;
+
+    fn pkg() -> Vec<u8> {
+        XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.dup"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[("./etc/conf", 0o100644, b"setting=1\n")])),
+            )
+            .build()
+    }
+
+    #[test]
+    fn batch_reuses_output_of_identical_package() {
+        let tmp = tempfile::tempdir().unwrap();
+        let other = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.other"))
+            .file("Payload", gzip(&cpio(&[("./other", 0o100644, b"x")])))
+            .build();
+
+        for action in [DuplicateAction::Copy, DuplicateAction::Symlink] {
+            if cfg!(not(unix)) && action == DuplicateAction::Symlink {
+                continue;
+            }
+            let root = tmp.path().join(format!("{action:?}"));
+            let mut batch = BatchExtractor::default().skip_duplicates(action);
+
+            let first = root.join("first");
+            assert_eq!(
+                batch.extract(Cursor::new(pkg()), &first).unwrap(),
+                BatchOutcome::Extracted
+            );
+            assert_eq!(
+                batch
+                    .extract(Cursor::new(other.clone()), root.join("other"))
+                    .unwrap(),
+                BatchOutcome::Extracted
+            );
+            let second = root.join("second");
+            assert_eq!(
+                batch.extract(Cursor::new(pkg()), &second).unwrap(),
+                BatchOutcome::Reused(first.clone())
+            );
+
+            assert_eq!(fs::read(second.join("etc/conf")).unwrap(), b"setting=1\n");
+            let is_link = fs::symlink_metadata(&second)
+                .unwrap()
+                .file_type()
+                .is_symlink();
+            assert_eq!(is_link, action == DuplicateAction::Symlink);
+        }
+    }
+
+    #[test]
+    fn batch_reuses_the_directory_actually_extracted_into() {
+        let tmp = tempfile::tempdir().unwrap();
+        let first = tmp.path().join("first");
+        fs::create_dir(&first).unwrap();
+        fs::write(first.join("mine"), b"mine").unwrap();
+        let mut batch = BatchExtractor::new(ExtractOptions {
+            avoid_clobber_dir: true,
+            ..Default::default()
+        })
+        .skip_duplicates(DuplicateAction::Copy);
+
+        batch.extract(Cursor::new(pkg()), &first).unwrap();
+        let second = tmp.path().join("second");
+        assert_eq!(
+            batch.extract(Cursor::new(pkg()), &second).unwrap(),
+            BatchOutcome::Reused(tmp.path().join("first-2"))
+        );
+        assert_eq!(fs::read(second.join("etc/conf")).unwrap(), b"setting=1\n");
+        assert!(!second.join("mine").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn batch_symlink_replaces_only_an_empty_destination() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut batch = BatchExtractor::default().skip_duplicates(DuplicateAction::Symlink);
+        let first = tmp.path().join("first");
+        batch.extract(Cursor::new(pkg()), &first).unwrap();
+
+        let empty = tmp.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        assert_eq!(
+            batch.extract(Cursor::new(pkg()), &empty).unwrap(),
+            BatchOutcome::Reused(first)
+        );
+        assert!(fs::symlink_metadata(&empty)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        let full = tmp.path().join("full");
+        fs::create_dir(&full).unwrap();
+        fs::write(full.join("mine"), b"mine").unwrap();
+        let err = batch.extract(Cursor::new(pkg()), &full).unwrap_err();
+        assert!(err.to_string().contains("not empty"), "{err}");
+        assert_eq!(fs::read(full.join("mine")).unwrap(), b"mine");
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reading the `Paths` tree of an Apple bill of materials (`Bom`), the
+//! listing `lsbom` prints: every path a package installs, with its type,
+//! mode, ownership, size and CRC.
+//!
+//! A BOM is a big-endian block store: a `BOMStore` header, a table of
+//! `(offset, length)` block pointers, and named variables pointing at
+//! blocks. `Paths` is a B+ tree whose leaves pair a path-info block (id,
+//! attributes) with a file block (parent id, name); full paths are rebuilt
+//! by following parent ids up to the root `.`.
+
+use crate::FileType;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// One path of a BOM, as `lsbom` lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BomEntry {
+    /// `.` for the root, `./usr/bin/foo` below it.
+    pub path: String,
+    pub kind: FileType,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+    /// POSIX `cksum` CRC, for regular files and symlinks.
+    pub checksum: u32,
+    pub link_target: Option<String>,
+}
+
+impl BomEntry {
+    /// The path relative to the root, without its `./` (`""` for the root),
+    /// the form [`bom_key`] gives cpio entry names.
+    pub fn key(&self) -> &str {
+        bom_key(&self.path)
+    }
+}
+
+/// `name` (a cpio entry name or a BOM path) relative to the payload root:
+/// `./usr/bin/foo` and `/usr/bin/foo` both become `usr/bin/foo`.
+pub(crate) fn bom_key(name: &str) -> &str {
+    let name = name.strip_prefix('.').unwrap_or(name);
+    name.trim_start_matches('/')
+}
+
+struct Store<'a> {
+    data: &'a [u8],
+    blocks: Vec<(u32, u32)>,
+}
+
+impl<'a> Store<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, Box<dyn Error>> {
+        if data.get(..8) != Some(b"BOMStore") {
+            return Err("not a BOM: missing BOMStore magic".into());
+        }
+        let index_offset = be32(data, 16)? as usize;
+        let count = be32(data, index_offset)? as usize;
+        let blocks = (0..count)
+            .map(|i| {
+                let at = index_offset + 4 + i * 8;
+                Ok((be32(data, at)?, be32(data, at + 4)?))
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+        Ok(Self { data, blocks })
+    }
+
+    fn block(&self, id: u32) -> Result<&'a [u8], Box<dyn Error>> {
+        let &(offset, len) = self
+            .blocks
+            .get(id as usize)
+            .ok_or_else(|| format!("BOM block {id} out of range"))?;
+        self.data
+            .get(offset as usize..offset as usize + len as usize)
+            .ok_or_else(|| format!("BOM block {id} extends past the end of the file").into())
+    }
+
+    fn var(&self, wanted: &str) -> Result<u32, Box<dyn Error>> {
+        let vars_offset = be32(self.data, 24)? as usize;
+        let count = be32(self.data, vars_offset)?;
+        let mut at = vars_offset + 4;
+        for _ in 0..count {
+            let block = be32(self.data, at)?;
+            let len = *self.data.get(at + 4).ok_or("truncated BOM variable")? as usize;
+            let name = self
+                .data
+                .get(at + 5..at + 5 + len)
+                .ok_or("truncated BOM variable")?;
+            if name == wanted.as_bytes() {
+                return Ok(block);
+            }
+            at += 5 + len;
+        }
+        Err(format!("BOM has no {wanted} variable").into())
+    }
+}
+
+fn be16(data: &[u8], at: usize) -> Result<u16, Box<dyn Error>> {
+    let bytes = data.get(at..at + 2).ok_or("truncated BOM")?;
+    Ok(u16::from_be_bytes(bytes.try_into()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = data.get(at..at + 4).ok_or("truncated BOM")?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+/// Every entry of the `Paths` tree of the BOM in `data`, in tree order.
+pub(crate) fn read_paths(data: &[u8]) -> Result<Vec<BomEntry>, Box<dyn Error>> {
+    let store = Store::parse(data)?;
+    let tree = store.block(store.var("Paths")?)?;
+    if tree.get(..4) != Some(b"tree") {
+        return Err("BOM Paths variable is not a tree".into());
+    }
+
+    // Descend to the leftmost leaf, then follow the leaves' forward links.
+    let mut node = be32(tree, 8)?;
+    let mut hops = 0;
+    loop {
+        let paths = store.block(node)?;
+        if be16(paths, 0)? == 1 {
+            break;
+        }
+        node = be32(paths, 12)?;
+        hops += 1;
+        if hops > store.blocks.len() {
+            return Err("BOM Paths tree has a cycle".into());
+        }
+    }
+
+    let mut raw = Vec::new();
+    let mut visited = 0;
+    while node != 0 {
+        let paths = store.block(node)?;
+        let count = be16(paths, 2)? as usize;
+        for i in 0..count {
+            let info1 = store.block(be32(paths, 12 + i * 8)?)?;
+            let file = store.block(be32(paths, 16 + i * 8)?)?;
+            let id = be32(info1, 0)?;
+            let info2 = store.block(be32(info1, 4)?)?;
+            let parent = be32(file, 0)?;
+            let name = file.get(4..).unwrap_or_default();
+            let name = name.split(|&b| b == 0).next().unwrap_or_default();
+            raw.push((
+                id,
+                parent,
+                String::from_utf8_lossy(name).into_owned(),
+                info2,
+            ));
+        }
+        node = be32(paths, 4)?;
+        visited += 1;
+        if visited > store.blocks.len() {
+            return Err("BOM Paths leaves form a cycle".into());
+        }
+    }
+
+    let parents: HashMap<u32, (u32, &str)> = raw
+        .iter()
+        .map(|(id, parent, name, _)| (*id, (*parent, name.as_str())))
+        .collect();
+    raw.iter()
+        .map(|(_, parent, name, info)| {
+            let mut parts = vec![name.as_str()];
+            let mut up = *parent;
+            while up != 0 {
+                let &(next, part) = parents
+                    .get(&up)
+                    .ok_or_else(|| format!("BOM path {name:?} has an unknown parent {up}"))?;
+                parts.push(part);
+                up = next;
+                if parts.len() > parents.len() {
+                    return Err("BOM parent chain has a cycle".into());
+                }
+            }
+            parts.reverse();
+            entry(parts.join("/"), info)
+        })
+        .collect()
+}
+
+/// Decode a path-info block: type, architecture, mode, uid, gid, mtime,
+/// size, a flag byte, checksum, then the symlink target.
+fn entry(path: String, info: &[u8]) -> Result<BomEntry, Box<dyn Error>> {
+    let kind = match info.first().ok_or("truncated BOM path info")? {
+        1 => FileType::Regular,
+        2 => FileType::Directory,
+        3 => FileType::Symlink,
+        _ => FileType::Other,
+    };
+    let link_target = if kind == FileType::Symlink {
+        let len = be32(info, 27)? as usize;
+        let target = info.get(31..31 + len).ok_or("truncated BOM link name")?;
+        let target = target.split(|&b| b == 0).next().unwrap_or_default();
+        Some(String::from_utf8_lossy(target).into_owned())
+    } else {
+        None
+    };
+    Ok(BomEntry {
+        path,
+        kind,
+        mode: be16(info, 4)?,
+        uid: be32(info, 6)?,
+        gid: be32(info, 10)?,
+        size: be32(info, 18)?,
+        checksum: be32(info, 23)?,
+        link_target,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::bom;
+
+    #[test]
+    fn read_paths_rebuilds_full_paths() {
+        let data = bom(&[
+            (".", 0o040755, 0, 0, None),
+            ("./usr", 0o040755, 0, 0, None),
+            ("./usr/bin", 0o040755, 0, 0, None),
+            ("./usr/bin/tool", 0o100755, 5, 0xdead_beef, None),
+            ("./usr/bin/alias", 0o120755, 4, 7, Some("tool")),
+        ]);
+
+        let entries = read_paths(&data).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                ".",
+                "./usr",
+                "./usr/bin",
+                "./usr/bin/tool",
+                "./usr/bin/alias"
+            ]
+        );
+        assert_eq!(entries[3].kind, FileType::Regular);
+        assert_eq!(entries[3].mode, 0o100755);
+        assert_eq!((entries[3].size, entries[3].checksum), (5, 0xdead_beef));
+        assert_eq!(entries[3].key(), "usr/bin/tool");
+        assert_eq!(entries[4].link_target.as_deref(), Some("tool"));
+        assert!(read_paths(b"not a bom").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Content-addressed extraction, see
+//! [`crate::ExtractOptions::content_addressed`]: regular files are stored
+//! once per distinct content under `ab/cd/abcd...` (their SHA-256), and
+//! [`crate::CAS_MANIFEST`] maps every payload path to what it was.
+
+use crate::report::Cksum;
+use crate::{throttle, FileType, PathStyle, PkgExtractor, Written};
+use log::debug;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// What a payload path was, as recorded in [`crate::CAS_MANIFEST`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum CasEntry {
+    File {
+        sha256: String,
+        size: u64,
+        mode: u32,
+    },
+    Directory {
+        mode: u32,
+    },
+    Symlink {
+        target: String,
+    },
+}
+
+/// Where the object with this hex digest lives, relative to the store root.
+pub(crate) fn object_path(sha256: &str) -> PathBuf {
+    Path::new(&sha256[..2]).join(&sha256[2..4]).join(sha256)
+}
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Record `target_path` in the content-addressed manifest, storing its
+    /// contents first if it is a regular file whose content is not in the
+    /// store yet.
+    pub(crate) fn store_content(
+        &mut self,
+        file_type: FileType,
+        target_path: &Path,
+        mode: u32,
+        file_size: u64,
+        body: &mut dyn Read,
+        rate_limiter: &mut Option<throttle::RateLimiter>,
+    ) -> Result<Written, Box<dyn Error>> {
+        let mut written = Written::default();
+        let entry = match file_type {
+            FileType::Directory => CasEntry::Directory { mode },
+            FileType::Symlink => {
+                let mut raw = Vec::new();
+                body.take(file_size).read_to_end(&mut raw)?;
+                let target = self.options.display_bytes(&raw).into_owned();
+                written.link_target = Some(target.clone());
+                CasEntry::Symlink { target }
+            }
+            FileType::Regular => {
+                // Hashed while staged, since the object's name is only known
+                // once every byte has been seen.
+                fs::create_dir_all(&self.output_dir)?;
+                let staging = self.temp_dir.as_deref().unwrap_or(&self.output_dir);
+                let mut temp = tempfile::Builder::new()
+                    .prefix(".pkg-extractor-")
+                    .tempfile_in(staging)?;
+                let mut hasher = Sha256::new();
+                let mut cksum = Cksum::new();
+                let mut buf = vec![0u8; 8192];
+                let mut remaining = file_size;
+                while remaining > 0 {
+                    let to_read = remaining.min(buf.len() as u64) as usize;
+                    let n = body.read(&mut buf[..to_read])?;
+                    if n == 0 {
+                        break;
+                    }
+                    temp.write_all(&buf[..n])?;
+                    hasher.update(&buf[..n]);
+                    cksum.update(&buf[..n]);
+                    remaining -= n as u64;
+                    written.bytes += n as u64;
+                    if let Some(limiter) = rate_limiter {
+                        limiter.consume(n);
+                    }
+                }
+                written.checksum = Some(cksum.finish());
+                let sha256: String = hasher
+                    .finalize()
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect();
+                let object = self.output_dir.join(object_path(&sha256));
+                if object.exists() {
+                    debug!("{} already stored as {sha256}", target_path.display());
+                } else {
+                    fs::create_dir_all(object.parent().unwrap_or(&self.output_dir))?;
+                    if let Err(e) = temp.persist(&object) {
+                        // Renaming fails across filesystems.
+                        fs::copy(e.file.path(), &object)?;
+                    }
+                    crate::set_mode(&object, 0o644)?;
+                }
+                CasEntry::File {
+                    sha256,
+                    size: written.bytes,
+                    mode,
+                }
+            }
+            FileType::Other => return Ok(written),
+        };
+        let path = PathStyle::Relative.render(&self.output_dir, target_path);
+        self.cas_entries.insert(path.replace('\\', "/"), entry);
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cpio;
+    use crate::{ExtractOptions, CAS_MANIFEST};
+    use std::io::Cursor;
+
+    #[test]
+    fn identical_files_share_one_object() {
+        let tmp = tempfile::tempdir().unwrap();
+        let payload = cpio(&[
+            ("./dir", 0o040755, b""),
+            ("./dir/a.txt", 0o100644, b"same\n"),
+            ("./dir/b.txt", 0o100755, b"same\n"),
+            ("./other.txt", 0o100644, b"other\n"),
+            ("./link", 0o120777, b"dir/a.txt"),
+        ]);
+
+        let mut extractor =
+            PkgExtractor::new(Cursor::new(Vec::new()), Some(tmp.path().to_path_buf()))
+                .with_options(ExtractOptions {
+                    content_addressed: true,
+                    ..Default::default()
+                });
+        extractor.extract_cpio(&payload, tmp.path()).unwrap();
+        extractor.write_reports().unwrap();
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(tmp.path().join(CAS_MANIFEST)).unwrap()).unwrap();
+        let a = manifest["dir/a.txt"]["sha256"].as_str().unwrap();
+        assert_eq!(
+            a,
+            "a6328afc76e9db71da297ebff4b0d3e7a7eb3b01d917c05a6573fef121b6ecb6"
+        );
+        assert_eq!(manifest["dir/b.txt"]["sha256"], a);
+        assert_eq!(manifest["dir/b.txt"]["mode"], 0o100755);
+        assert_ne!(manifest["other.txt"]["sha256"], a);
+        assert_eq!(
+            manifest["dir"],
+            serde_json::json!({"type": "directory", "mode": 0o040755})
+        );
+        assert_eq!(
+            manifest["link"],
+            serde_json::json!({"type": "symlink", "target": "dir/a.txt"})
+        );
+
+        assert_eq!(
+            fs::read(tmp.path().join(object_path(a))).unwrap(),
+            b"same\n"
+        );
+        assert!(!tmp.path().join("dir").exists());
+        assert!(!tmp.path().join("link").exists());
+        let objects = walk_files(tmp.path());
+        assert_eq!(objects.len(), 3, "{objects:?}"); // two objects + manifest
+    }
+
+    fn walk_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(walk_files(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reporting the code signatures embedded in extracted Mach-O binaries, see
+//! [`crate::ExtractOptions::code_signatures`].
+//!
+//! A signed Mach-O has an `LC_CODE_SIGNATURE` load command pointing at a
+//! big-endian `SuperBlob` in its `__LINKEDIT` data. Its `CodeDirectory`
+//! blob names the signing identifier and team, carries the signing flags
+//! (ad-hoc or not) and lists the hash of every page of code before the
+//! signature, which is what validation checks.
+
+use crate::macho::{fat_slices, FAT_MAGIC, FAT_MAGIC_64};
+use crate::report::ManifestEntry;
+use log::debug;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+const MH_MAGIC: u32 = 0xfeed_face;
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CS_ADHOC: u32 = 0x2;
+const CS_HASHTYPE_SHA256: u8 = 2;
+/// First CodeDirectory version with a team identifier.
+const CS_SUPPORTSTEAMID: u32 = 0x20200;
+
+/// The code signature of one Mach-O (slice), as listed in the
+/// [`crate::ExtractOptions::code_signatures`] report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeSignature {
+    /// The binary, relative to the output directory.
+    pub path: String,
+    /// `x86_64`, `arm64`, or the raw CPU type of other architectures.
+    pub arch: String,
+    /// The signing identifier, `None` for an unsigned binary.
+    pub identifier: Option<String>,
+    pub team_id: Option<String>,
+    /// Signed without a certificate (`codesign -s -`).
+    pub ad_hoc: bool,
+    /// Whether the code hashes to what the signature records; `None` when
+    /// unsigned or hashed with something other than SHA-256.
+    pub valid: Option<bool>,
+}
+
+/// Write to `dest` a JSON array of the signatures of every extracted
+/// regular file that is a Mach-O, one element per architecture slice.
+/// Binaries whose signature cannot be parsed are left out, and returned
+/// with the reason for the caller to warn about.
+pub(crate) fn write_report(
+    dest: &Path,
+    entries: &[ManifestEntry],
+    output_dir: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut signatures = Vec::new();
+    let mut unreadable = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.kind == "file") {
+        let mut magic = [0u8; 4];
+        let mut file = match File::open(&entry.path) {
+            Ok(file) => file,
+            // Written elsewhere (a writer factory, ...) or moved since.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if file.read_exact(&mut magic).is_err() || !is_macho(u32::from_be_bytes(magic)) {
+            continue;
+        }
+        let path = entry.path.strip_prefix(output_dir).unwrap_or(&entry.path);
+        let path = path.to_string_lossy().replace('\\', "/");
+        match read(&fs::read(&entry.path)?) {
+            Ok(found) => signatures.extend(found.into_iter().map(|signature| CodeSignature {
+                path: path.clone(),
+                ..signature
+            })),
+            Err(e) => unreadable.push(format!("Could not read the code signature of {path}: {e}")),
+        }
+    }
+    debug!(
+        "Found {} Mach-O slices, writing their signatures to {}",
+        signatures.len(),
+        dest.display()
+    );
+    let mut out = BufWriter::new(File::create(dest)?);
+    serde_json::to_writer_pretty(&mut out, &signatures)?;
+    out.flush()?;
+    Ok(unreadable)
+}
+
+/// Whether a file starting with these (big-endian) bytes may be a Mach-O.
+fn is_macho(magic: u32) -> bool {
+    [FAT_MAGIC, FAT_MAGIC_64, MH_MAGIC, MH_MAGIC_64].contains(&magic)
+        || [MH_MAGIC, MH_MAGIC_64].contains(&magic.swap_bytes())
+}
+
+/// The signatures of every slice of the Mach-O in `data`, with an empty
+/// `path`; empty when `data` is not a Mach-O.
+pub(crate) fn read(data: &[u8]) -> Result<Vec<CodeSignature>, Box<dyn Error>> {
+    if let Some(slices) = fat_slices(data)? {
+        let mut signatures = Vec::new();
+        for slice in slices {
+            signatures.extend(read_thin(&data[slice.range])?);
+        }
+        return Ok(signatures);
+    }
+    Ok(read_thin(data)?.into_iter().collect())
+}
+
+/// The signature of a single-architecture Mach-O.
+fn read_thin(data: &[u8]) -> Result<Option<CodeSignature>, Box<dyn Error>> {
+    let header_len = match le32(data, 0)? {
+        MH_MAGIC => 28,
+        MH_MAGIC_64 => 32,
+        _ => return Ok(None),
+    };
+    let mut signature = CodeSignature {
+        path: String::new(),
+        arch: match le32(data, 4)? {
+            0x0100_0007 => "x86_64".to_string(),
+            0x0100_000c => "arm64".to_string(),
+            cpu => format!("{cpu:#x}"),
+        },
+        identifier: None,
+        team_id: None,
+        ad_hoc: false,
+        valid: None,
+    };
+
+    let ncmds = le32(data, 16)?;
+    let mut at = header_len;
+    let mut blob = None;
+    for _ in 0..ncmds {
+        let (cmd, size) = (le32(data, at)?, le32(data, at + 4)? as usize);
+        if cmd == LC_CODE_SIGNATURE {
+            let (offset, len) = (le32(data, at + 8)? as usize, le32(data, at + 12)? as usize);
+            blob = Some(
+                offset
+                    .checked_add(len)
+                    .and_then(|end| data.get(offset..end))
+                    .ok_or("code signature past end of file")?,
+            );
+            break;
+        }
+        if size < 8 {
+            return Err("malformed Mach-O load command".into());
+        }
+        at = at
+            .checked_add(size)
+            .ok_or("malformed Mach-O load command")?;
+    }
+    let Some(blob) = blob else {
+        return Ok(Some(signature));
+    };
+
+    if be32(blob, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+        return Err("code signature is not an embedded signature SuperBlob".into());
+    }
+    let mut directory = None;
+    for i in 0..be32(blob, 8)? as usize {
+        if be32(blob, 12 + i * 8)? == CSSLOT_CODEDIRECTORY {
+            let offset = be32(blob, 16 + i * 8)? as usize;
+            directory = Some(blob.get(offset..).ok_or("CodeDirectory past end of blob")?);
+        }
+    }
+    let cd = directory.ok_or("code signature has no CodeDirectory")?;
+    if be32(cd, 0)? != CSMAGIC_CODEDIRECTORY {
+        return Err("malformed CodeDirectory".into());
+    }
+
+    let version = be32(cd, 8)?;
+    signature.ad_hoc = be32(cd, 12)? & CS_ADHOC != 0;
+    signature.identifier = Some(c_string(cd, be32(cd, 20)? as usize)?);
+    if version >= CS_SUPPORTSTEAMID {
+        let team = be32(cd, 48)? as usize;
+        if team != 0 {
+            signature.team_id = Some(c_string(cd, team)?);
+        }
+    }
+
+    let hash_offset = be32(cd, 16)? as usize;
+    let code_slots = be32(cd, 28)? as usize;
+    let code_limit = be32(cd, 32)? as usize;
+    let (hash_size, hash_type, page_shift) = (byte(cd, 36)?, byte(cd, 37)?, byte(cd, 39)?);
+    if hash_type == CS_HASHTYPE_SHA256 && page_shift < 32 {
+        let page = 1usize << page_shift;
+        let code = data
+            .get(..code_limit)
+            .ok_or("code limit past end of file")?;
+        // Only hash once the directory's shape is consistent: SHA-256
+        // slots are 32 bytes, and there is one per page of the signed code.
+        let valid = hash_size == 32
+            && code_slots == code.len().div_ceil(page)
+            && (0..code_slots).all(|i| {
+                let start = i * page;
+                let end = (start + page).min(code.len());
+                let expected = hash_offset
+                    .checked_add(i * 32)
+                    .and_then(|at| cd.get(at..at.checked_add(32)?));
+                expected == Some(Sha256::digest(&code[start..end]).as_slice())
+            });
+        signature.valid = Some(valid);
+    }
+    Ok(Some(signature))
+}
+
+fn c_string(data: &[u8], at: usize) -> Result<String, Box<dyn Error>> {
+    let bytes = data.get(at..).ok_or("string past end of CodeDirectory")?;
+    let bytes = bytes.split(|&b| b == 0).next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn byte(data: &[u8], at: usize) -> Result<u8, Box<dyn Error>> {
+    data.get(at)
+        .copied()
+        .ok_or_else(|| "truncated Mach-O".into())
+}
+
+fn le32(data: &[u8], at: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = at
+        .checked_add(4)
+        .and_then(|end| data.get(at..end))
+        .ok_or("truncated Mach-O")?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = at
+        .checked_add(4)
+        .and_then(|end| data.get(at..end))
+        .ok_or("truncated Mach-O")?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cpio;
+    use crate::{ExtractOptions, PkgExtractor, WarningKind};
+    use std::fs;
+    use std::io::Cursor;
+
+    /// A 64-bit arm64 Mach-O whose only load command is its code
+    /// signature: a CodeDirectory for `identifier` (and `team`) hashing the
+    /// header as its single page.
+    fn signed_macho(identifier: &str, team: Option<&str>) -> Vec<u8> {
+        let code_limit = 48u32;
+        let mut identity = identifier.as_bytes().to_vec();
+        identity.push(0);
+        let team_offset = team.map_or(0, |_| 52 + identity.len() as u32);
+        if let Some(team) = team {
+            identity.extend_from_slice(team.as_bytes());
+            identity.push(0);
+        }
+        let hash_offset = 52 + identity.len() as u32;
+        let cd_len = hash_offset + 32;
+        let blob_len = 20 + cd_len;
+
+        let mut macho = Vec::new();
+        for field in [MH_MAGIC_64, 0x0100_000c, 0, 2, 1, 16, 0, 0] {
+            macho.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [LC_CODE_SIGNATURE, 16, code_limit, blob_len] {
+            macho.extend_from_slice(&field.to_le_bytes());
+        }
+        let page_hash = Sha256::digest(&macho);
+
+        for field in [
+            CSMAGIC_EMBEDDED_SIGNATURE,
+            blob_len,
+            1,
+            CSSLOT_CODEDIRECTORY,
+            20,
+        ] {
+            macho.extend_from_slice(&field.to_be_bytes());
+        }
+        let flags = if team.is_some() { 0 } else { CS_ADHOC };
+        for field in [
+            CSMAGIC_CODEDIRECTORY,
+            cd_len,
+            CS_SUPPORTSTEAMID,
+            flags,
+            hash_offset,
+            52,
+            0,
+            1,
+            code_limit,
+        ] {
+            macho.extend_from_slice(&field.to_be_bytes());
+        }
+        macho.extend_from_slice(&[32, CS_HASHTYPE_SHA256, 0, 12]);
+        for field in [0, 0, team_offset] {
+            macho.extend_from_slice(&field.to_be_bytes());
+        }
+        macho.extend_from_slice(&identity);
+        macho.extend_from_slice(&page_hash);
+        macho
+    }
+
+    #[test]
+    fn reports_signing_identifier_team_and_validity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = tmp.path().join("signatures.json");
+        let mut tampered = signed_macho("com.example.helper", None);
+        tampered[28] ^= 1;
+        let cpio = cpio(&[
+            (
+                "./bin/tool",
+                0o100755,
+                &signed_macho("com.example.tool", Some("ABCDE12345")),
+            ),
+            ("./bin/helper", 0o100755, &tampered),
+            ("./README", 0o100644, b"not a binary"),
+        ]);
+
+        let mut extractor =
+            PkgExtractor::new(Cursor::new(Vec::new()), Some(tmp.path().to_path_buf()))
+                .with_options(ExtractOptions {
+                    code_signatures: Some(report.clone()),
+                    ..Default::default()
+                });
+        extractor.extract_cpio(&cpio, tmp.path()).unwrap();
+        extractor.write_reports().unwrap();
+
+        let signatures: Vec<serde_json::Value> =
+            serde_json::from_slice(&fs::read(report).unwrap()).unwrap();
+        assert_eq!(
+            signatures,
+            [
+                serde_json::json!({
+                    "path": "bin/tool",
+                    "arch": "arm64",
+                    "identifier": "com.example.tool",
+                    "team_id": "ABCDE12345",
+                    "ad_hoc": false,
+                    "valid": true,
+                }),
+                serde_json::json!({
+                    "path": "bin/helper",
+                    "arch": "arm64",
+                    "identifier": "com.example.helper",
+                    "team_id": null,
+                    "ad_hoc": true,
+                    "valid": false,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn inconsistent_code_directories_are_invalid() {
+        let signed = signed_macho("com.example.tool", None);
+        assert_eq!(read(&signed).unwrap()[0].valid, Some(true));
+
+        // A SHA-256 directory claiming 20-byte hashes.
+        let mut short_hashes = signed.clone();
+        short_hashes[104] = 20;
+        assert_eq!(read(&short_hashes).unwrap()[0].valid, Some(false));
+
+        // More code slots than pages of signed code.
+        let mut extra_slots = signed.clone();
+        extra_slots[99] = 2;
+        assert_eq!(read(&extra_slots).unwrap()[0].valid, Some(false));
+        // Or a huge count, rejected before any hashing.
+        let mut many_slots = signed;
+        many_slots[96..100].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(read(&many_slots).unwrap()[0].valid, Some(false));
+    }
+
+    #[test]
+    fn malformed_binaries_are_left_out_with_a_warning() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = tmp.path().join("signatures.json");
+        let mut truncated = signed_macho("com.example.truncated", None);
+        truncated.truncate(60);
+        // A 64-bit fat header whose slice offset + size overflows.
+        let mut overflowing = Vec::new();
+        overflowing.extend_from_slice(&FAT_MAGIC_64.to_be_bytes());
+        overflowing.extend_from_slice(&1u32.to_be_bytes());
+        overflowing.extend_from_slice(&0x0100_000cu32.to_be_bytes());
+        overflowing.extend_from_slice(&0u32.to_be_bytes());
+        overflowing.extend_from_slice(&u64::MAX.to_be_bytes());
+        overflowing.extend_from_slice(&2u64.to_be_bytes());
+        overflowing.extend_from_slice(&[0; 8]);
+        assert!(read(&truncated).is_err());
+        assert!(read(&overflowing).is_err());
+
+        let cpio = cpio(&[
+            ("./bin/truncated", 0o100755, &truncated),
+            ("./bin/overflowing", 0o100755, &overflowing),
+            (
+                "./bin/tool",
+                0o100755,
+                &signed_macho("com.example.tool", None),
+            ),
+        ]);
+        let mut extractor =
+            PkgExtractor::new(Cursor::new(Vec::new()), Some(tmp.path().to_path_buf()))
+                .with_options(ExtractOptions {
+                    code_signatures: Some(report.clone()),
+                    ..Default::default()
+                });
+        extractor.extract_cpio(&cpio, tmp.path()).unwrap();
+        extractor.write_reports().unwrap();
+
+        let signatures: Vec<serde_json::Value> =
+            serde_json::from_slice(&fs::read(report).unwrap()).unwrap();
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0]["path"], "bin/tool");
+        let warnings: Vec<_> = extractor
+            .stats()
+            .warnings
+            .iter()
+            .filter(|w| w.kind == WarningKind::UnparsableFile)
+            .map(|w| w.message.as_str())
+            .collect();
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("bin/truncated"));
+        assert!(warnings[1].contains("bin/overflowing"));
+    }
+}
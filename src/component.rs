@@ -0,0 +1,293 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Component discovery: which installable units a flat package holds and
+//! what each one's `PackageInfo` says about itself.
+
+use crate::{decode_payload, ExtractOptions, ExtractionStats, OutputDirs, PkgExtractor};
+use apple_flat_package::reader::PkgFlavor;
+use apple_flat_package::PackageInfo;
+use apple_xar::reader::XarReader;
+use cpio_archive::{CpioReader as _, OdcReader};
+use log::debug;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{Cursor, Read, Seek};
+use std::path::PathBuf;
+
+/// What a component package declares about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComponentInfo {
+    /// Position in archive order, starting at 0.
+    pub index: usize,
+    /// Directory of the component inside the xar (`foo.pkg`); empty for the
+    /// root component of a component package.
+    pub path: String,
+    /// `identifier` from the PackageInfo, or `path` without its `.pkg`
+    /// suffix when the component has no usable PackageInfo.
+    pub identifier: String,
+    /// Package `version` from the PackageInfo.
+    pub version: Option<String>,
+    /// Default `install-location` from the PackageInfo.
+    pub install_location: Option<String>,
+    /// `installKBytes` from the PackageInfo `<payload>` element.
+    pub install_kbytes: Option<u64>,
+    /// `numberOfFiles` from the PackageInfo `<payload>` element.
+    pub number_of_files: Option<u64>,
+    /// `auth` from the PackageInfo: `root` when installing needs admin
+    /// rights.
+    pub auth: Option<String>,
+    /// `relocatable` from the PackageInfo: whether the installer may put
+    /// bundles wherever a copy of them already lives.
+    pub relocatable: Option<bool>,
+    /// Whether installing runs scripts: the component has a Scripts archive
+    /// or its PackageInfo declares pre/postinstall scripts.
+    pub runs_scripts: bool,
+}
+
+impl ComponentInfo {
+    /// Path of `member` (`Payload`, `Scripts`, `PackageInfo`, ...) inside the
+    /// xar.
+    pub(crate) fn member(&self, member: &str) -> String {
+        if self.path.is_empty() {
+            member.to_string()
+        } else {
+            format!("{}/{member}", self.path)
+        }
+    }
+}
+
+/// Where a component's archives (`Payload`, `Scripts`, `Bom`) are read
+/// from: the package's xar, or the copies a [`ComponentHandle`] holds.
+pub(crate) trait MemberSource {
+    /// The data of the xar member at `path`, `None` when there is none.
+    fn member_data(&mut self, path: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>>;
+}
+
+impl<T: Read + Seek + Sized + Debug> MemberSource for XarReader<T> {
+    fn member_data(&mut self, path: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self.get_file_data_from_path(path)?)
+    }
+}
+
+impl MemberSource for &ComponentHandle {
+    fn member_data(&mut self, path: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let members = [
+            ("Payload", &self.payload),
+            ("Scripts", &self.scripts),
+            ("Bom", &self.bom),
+        ];
+        Ok(members
+            .into_iter()
+            .find(|(member, _)| self.info.member(member) == path)
+            .and_then(|(_, data)| data.clone()))
+    }
+}
+
+/// Iterator returned by [`PkgExtractor::components`]. Gives the package
+/// reader back to the extractor when dropped.
+pub struct Components<'a, R: Read + Seek + Sized + Debug> {
+    pub(crate) extractor: &'a mut PkgExtractor<R>,
+    pub(crate) xar: Option<XarReader<R>>,
+    pub(crate) infos: std::vec::IntoIter<ComponentInfo>,
+}
+
+impl<R: Read + Seek + Sized + Debug> Iterator for Components<'_, R> {
+    type Item = Result<ComponentHandle, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let info = self.infos.next()?;
+        let xar = self.xar.as_mut()?;
+        let options = &self.extractor.options;
+        let mut read = |member: &str| xar.get_file_data_from_path(&info.member(member));
+        let members = (
+            read("Payload"),
+            read("Scripts"),
+            // Only `bom_layout` extraction needs it.
+            options.bom_layout.then(|| read("Bom")).transpose(),
+        );
+        let (payload, scripts, bom) = match members {
+            (Ok(payload), Ok(scripts), Ok(bom)) => (payload, scripts, bom.flatten()),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return Some(Err(e.into())),
+        };
+        Some(Ok(ComponentHandle {
+            info,
+            payload,
+            scripts,
+            bom,
+            options: self.extractor.options.clone(),
+            output_dir: self.extractor.output_dir.clone(),
+            output_dirs: self.extractor.output_dirs.clone(),
+        }))
+    }
+}
+
+impl<R: Read + Seek + Sized + Debug> Drop for Components<'_, R> {
+    fn drop(&mut self) {
+        if let Some(xar) = self.xar.take() {
+            self.extractor.reader = Some(xar.into_inner());
+        }
+    }
+}
+
+/// One component of a package, as yielded by [`PkgExtractor::components`]:
+/// its metadata plus its `Payload` and `Scripts` archives (and `Bom` with
+/// `bom_layout`), ready to be inspected or extracted on their own.
+#[derive(Debug, Clone)]
+pub struct ComponentHandle {
+    info: ComponentInfo,
+    payload: Option<Vec<u8>>,
+    scripts: Option<Vec<u8>>,
+    bom: Option<Vec<u8>>,
+    options: ExtractOptions,
+    output_dir: PathBuf,
+    output_dirs: OutputDirs,
+}
+
+impl ComponentHandle {
+    pub fn info(&self) -> &ComponentInfo {
+        &self.info
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.info.identifier
+    }
+
+    pub fn has_payload(&self) -> bool {
+        self.payload.is_some()
+    }
+
+    /// Names of the entries in the component's Payload, in archive order.
+    pub fn payload_entries(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let Some(payload) = &self.payload else {
+            return Ok(Vec::new());
+        };
+        let cpio = decode_payload(payload)?;
+        let mut reader = OdcReader::new(Cursor::new(cpio.as_ref()));
+        let mut names = Vec::new();
+        while let Some(header) = reader.read_next()? {
+            if header.name() != "." {
+                names.push(header.name().to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    /// Extract this component into the extractor's output directory, with
+    /// the extractor's options. With `avoid_clobber_dir`, that is the
+    /// directory the extractor or its other components already extracted
+    /// into, if any.
+    pub fn extract(&self) -> Result<ExtractionStats, Box<dyn Error>> {
+        self.extract_to(self.output_dir.clone())
+    }
+
+    /// Extract this component into `dir` as extracting the whole package
+    /// there would: `component_dir_map` and `per_component_dirs` routing,
+    /// Scripts under `dir/Scripts[/<identifier>]` with `extract_scripts`,
+    /// reports and stats included.
+    pub fn extract_to(&self, dir: impl Into<PathBuf>) -> Result<ExtractionStats, Box<dyn Error>> {
+        let mut extractor = PkgExtractor::new(Cursor::new(Vec::new()), Some(dir.into()))
+            .with_options(self.options.clone());
+        extractor.output_dirs = self.output_dirs.clone();
+        let mut members = self;
+        extractor.extract_single_component(&mut members, &self.info)?;
+        Ok(extractor.stats)
+    }
+}
+
+/// Same rule as `PkgReader::new`: a top-level `Distribution` file makes it a
+/// product package.
+pub(crate) fn pkg_flavor<T: Read + Seek + Sized + Debug>(
+    xar: &XarReader<T>,
+) -> Result<PkgFlavor, Box<dyn Error>> {
+    Ok(if xar.find_file("Distribution")?.is_some() {
+        PkgFlavor::Product
+    } else {
+        PkgFlavor::Component
+    })
+}
+
+/// List the components of the package in archive order: the root component
+/// for a component package, every top-level `*.pkg` directory for a product
+/// package.
+pub(crate) fn read_components<T: Read + Seek + Sized + Debug>(
+    xar: &mut XarReader<T>,
+    flavor: PkgFlavor,
+) -> Result<Vec<ComponentInfo>, Box<dyn Error>> {
+    component_paths(xar, flavor)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| component_info(xar, index, path))
+        .collect()
+}
+
+/// The xar paths of the package's components, in archive order, read from
+/// the table of contents alone: `""` for a component package's root.
+pub(crate) fn component_paths<T: Read + Seek + Sized + Debug>(
+    xar: &XarReader<T>,
+    flavor: PkgFlavor,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(match flavor {
+        PkgFlavor::Component => vec![String::new()],
+        // Sub-packages live at the top level of the xar as directories whose
+        // name ends in `.pkg`. This matches what
+        // `PkgReader::component_packages` does internally.
+        PkgFlavor::Product => xar
+            .files()?
+            .into_iter()
+            .filter_map(|(name, _)| {
+                if name.ends_with(".pkg") && !name.contains('/') {
+                    Some(name)
+                } else {
+                    None
+                }
+            })
+            .collect(),
+    })
+}
+
+/// Build the [`ComponentInfo`] for the component at `path`, tolerating a
+/// missing or unparsable PackageInfo.
+fn component_info<T: Read + Seek + Sized + Debug>(
+    xar: &mut XarReader<T>,
+    index: usize,
+    path: String,
+) -> Result<ComponentInfo, Box<dyn Error>> {
+    let mut component = ComponentInfo {
+        index,
+        identifier: path.strip_suffix(".pkg").unwrap_or(&path).to_string(),
+        path,
+        version: None,
+        install_location: None,
+        install_kbytes: None,
+        number_of_files: None,
+        auth: None,
+        relocatable: None,
+        runs_scripts: false,
+    };
+    component.runs_scripts = xar.find_file(&component.member("Scripts"))?.is_some();
+
+    let Some(data) = xar.get_file_data_from_path(&component.member("PackageInfo"))? else {
+        return Ok(component);
+    };
+    match PackageInfo::from_reader(Cursor::new(data)) {
+        Ok(info) => {
+            if !info.identifier.is_empty() {
+                component.identifier = info.identifier;
+            }
+            component.version = Some(info.version);
+            component.install_location = info.install_location;
+            component.install_kbytes = info.payload.as_ref().map(|p| p.install_kbytes);
+            component.number_of_files = info.payload.as_ref().map(|p| p.number_of_files);
+            component.auth = Some(info.auth).filter(|auth| !auth.is_empty());
+            component.relocatable = info.relocatable;
+            component.runs_scripts |= !info.scripts.scripts.is_empty();
+        }
+        Err(e) => debug!("Unparsable PackageInfo for {:?}: {e}", component.path),
+    }
+    Ok(component)
+}
@@ -0,0 +1,93 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Recording what extraction decided for every entry, and checking a later
+//! run against such a recording.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// What the extractor did with a cpio entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Extract,
+    /// Regular file outside the size filter.
+    SkipSize,
+    /// Entry below one of `exclude_dirs`.
+    SkipExcluded,
+    /// Device, FIFO, socket, ...
+    SkipType,
+    /// Extension mapped to `EntryAction::Skip`.
+    SkipExtension,
+    /// Same size and CRC as in the `delta_baseline` BOM.
+    SkipUnchanged,
+    /// Absent from the component's BOM, or of another type there, under
+    /// `bom_layout`.
+    SkipNotInBom,
+    /// Name resolving outside the output directory.
+    Refuse,
+    /// Writing failed and `continue_on_file_error` skipped it.
+    Failed,
+}
+
+/// One line of a decision log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Decision {
+    /// Entry name as stored in the cpio.
+    pub entry: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub action: Action,
+    /// Destination relative to the output directory, once one was chosen.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub target: Option<String>,
+}
+
+/// Write `decisions` to `dest` as newline-delimited JSON.
+pub(crate) fn write(dest: &Path, decisions: &[Decision]) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(dest)?);
+    for decision in decisions {
+        serde_json::to_writer(&mut out, decision)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Compare `decisions` against the log recorded at `recorded`, failing on
+/// the first difference.
+pub(crate) fn replay(recorded: &Path, decisions: &[Decision]) -> Result<(), Box<dyn Error>> {
+    let text = fs::read_to_string(recorded)?;
+    let expected = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str::<Decision>)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (index, (was, now)) in expected.iter().zip(decisions).enumerate() {
+        if was != now {
+            return Err(format!(
+                "Decision #{index} differs from {}: recorded {was:?}, now {now:?}",
+                recorded.display()
+            )
+            .into());
+        }
+    }
+    if expected.len() != decisions.len() {
+        return Err(format!(
+            "{} recorded {} decisions, this run made {}",
+            recorded.display(),
+            expected.len(),
+            decisions.len()
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Just enough of a product package's `Distribution` script to know which
+//! components it declares and which installer UI files it shows.
+//!
+//! We don't use `apple_flat_package::Distribution`: its serde model drops the
+//! text of `<pkg-ref>` elements (the `#foo.pkg` archive reference) and
+//! rejects documents missing elements it considers mandatory, while all we
+//! need is a lenient walk over the `<pkg-ref>`s.
+
+use std::error::Error;
+use xml::reader::{EventReader, XmlEvent};
+
+/// Everything the Distribution says about one component, merged across all
+/// `<pkg-ref>` elements sharing its `id` (choices reference components by
+/// id only; the top-level pkg-ref carries the attributes and archive path).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PkgRef {
+    pub id: String,
+    pub version: Option<String>,
+    pub auth: Option<String>,
+    pub install_kbytes: Option<u64>,
+    /// Element text, e.g. `#foo.pkg`.
+    pub archive: Option<String>,
+}
+
+impl PkgRef {
+    /// The xar directory the archive reference points at (`#foo.pkg` ->
+    /// `foo.pkg`), with `%XX` escapes decoded.
+    pub(crate) fn archive_path(&self) -> Option<String> {
+        let reference = self.archive.as_deref()?.trim();
+        let name = reference.strip_prefix('#').unwrap_or(reference);
+        if name.is_empty() {
+            return None;
+        }
+        Some(percent_decode(name))
+    }
+}
+
+/// A file the Installer app shows while installing a product package,
+/// declared by its Distribution (`<license file="License.rtf"/>`, ...) and
+/// stored under `Resources/`, possibly localised in `<lang>.lproj/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstallerResource {
+    /// Background image.
+    Background,
+    /// Text shown on the Introduction pane.
+    Welcome,
+    /// Text shown on the Read Me pane.
+    Readme,
+    /// License the user has to agree to.
+    License,
+    /// Text shown on the Summary pane once installation is done.
+    Conclusion,
+}
+
+impl InstallerResource {
+    const ALL: [Self; 5] = [
+        Self::Background,
+        Self::Welcome,
+        Self::Readme,
+        Self::License,
+        Self::Conclusion,
+    ];
+
+    /// The Distribution element declaring it.
+    fn element(self) -> &'static str {
+        match self {
+            Self::Background => "background",
+            Self::Welcome => "welcome",
+            Self::Readme => "readme",
+            Self::License => "license",
+            Self::Conclusion => "conclusion",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Distribution {
+    /// Declared components, in order of first appearance.
+    pub pkg_refs: Vec<PkgRef>,
+    /// The file name declared for each installer resource, first
+    /// declaration first.
+    pub resources: Vec<(InstallerResource, String)>,
+}
+
+impl Distribution {
+    pub(crate) fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut distribution = Distribution::default();
+        // Index into `pkg_refs` of the `<pkg-ref>` whose text we're reading.
+        let mut current: Option<usize> = None;
+
+        for event in EventReader::new(data) {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } if name.local_name == "pkg-ref" => {
+                    let attr = |key: &str| {
+                        attributes
+                            .iter()
+                            .find(|a| a.name.local_name == key)
+                            .map(|a| a.value.clone())
+                    };
+                    let Some(id) = attr("id") else {
+                        continue;
+                    };
+                    let index = match distribution.pkg_refs.iter().position(|r| r.id == id) {
+                        Some(index) => index,
+                        None => {
+                            distribution.pkg_refs.push(PkgRef {
+                                id,
+                                ..Default::default()
+                            });
+                            distribution.pkg_refs.len() - 1
+                        }
+                    };
+                    let pkg_ref = &mut distribution.pkg_refs[index];
+                    pkg_ref.version = pkg_ref.version.take().or_else(|| attr("version"));
+                    pkg_ref.auth = pkg_ref.auth.take().or_else(|| attr("auth"));
+                    pkg_ref.install_kbytes = pkg_ref
+                        .install_kbytes
+                        .or_else(|| attr("installKBytes").and_then(|v| v.parse().ok()));
+                    current = Some(index);
+                }
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let resource = InstallerResource::ALL
+                        .into_iter()
+                        .find(|r| r.element() == name.local_name);
+                    let file = attributes.iter().find(|a| a.name.local_name == "file");
+                    if let (Some(resource), Some(file)) = (resource, file) {
+                        distribution.resources.push((resource, file.value.clone()));
+                    }
+                }
+                XmlEvent::Characters(text) => {
+                    if let Some(index) = current {
+                        let pkg_ref = &mut distribution.pkg_refs[index];
+                        if pkg_ref.archive.is_none() && !text.trim().is_empty() {
+                            pkg_ref.archive = Some(text.trim().to_string());
+                        }
+                    }
+                }
+                XmlEvent::EndElement { name } if name.local_name == "pkg-ref" => current = None,
+                _ => {}
+            }
+        }
+
+        Ok(distribution)
+    }
+
+    /// The file name the Distribution declares for `resource`.
+    pub(crate) fn resource(&self, resource: InstallerResource) -> Option<&str> {
+        self.resources
+            .iter()
+            .find(|(r, _)| *r == resource)
+            .map(|(_, file)| file.as_str())
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_pkg_refs_by_id() {
+        let xml = br#"<?xml version="1.0" encoding="utf-8"?>
+<installer-gui-script minSpecVersion="1">
+    <choice id="base"><pkg-ref id="com.example.base"/></choice>
+    <pkg-ref id="com.example.base" version="1.2" auth="root" installKBytes="42">#base%20system.pkg</pkg-ref>
+    <pkg-ref id="com.example.extra">#extra.pkg</pkg-ref>
+</installer-gui-script>"#;
+
+        let distribution = Distribution::parse(xml).unwrap();
+
+        assert_eq!(distribution.pkg_refs.len(), 2);
+        let base = &distribution.pkg_refs[0];
+        assert_eq!(base.id, "com.example.base");
+        assert_eq!(base.version.as_deref(), Some("1.2"));
+        assert_eq!(base.auth.as_deref(), Some("root"));
+        assert_eq!(base.install_kbytes, Some(42));
+        assert_eq!(base.archive_path().as_deref(), Some("base system.pkg"));
+        assert_eq!(
+            distribution.pkg_refs[1].archive_path().as_deref(),
+            Some("extra.pkg")
+        );
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Iterating over payload entries together with the contents of the small
+//! ones, for scripts that want to look at a package's files in a loop
+//! without extracting it.
+
+use crate::component::{pkg_flavor, read_components};
+use crate::{decode_payload, ComponentInfo, FileType, PkgExtractor, CPIO_TRAILER};
+use apple_xar::reader::XarReader;
+use cpio_archive::{CpioReader as _, OdcReader};
+use log::debug;
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{Cursor, Read, Seek};
+
+/// A payload entry, as yielded by [`PkgExtractor::entries_with_bodies`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMeta {
+    /// Identifier of the component whose Payload holds the entry.
+    pub component: String,
+    /// Entry name exactly as stored, e.g. `./usr/bin/foo`.
+    pub name: String,
+    pub file_type: FileType,
+    /// Full mode: file type and permission bits.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Modification time, seconds since the Unix epoch.
+    pub mtime: u32,
+    pub size: u64,
+}
+
+/// An entry and, for a small enough regular file, its contents.
+pub type EntryWithBody = (EntryMeta, Option<Vec<u8>>);
+
+/// Iterator returned by [`PkgExtractor::entries_with_bodies`]. Holds one
+/// component's decoded Payload at a time, and gives the package reader back
+/// to the extractor when dropped.
+pub struct EntriesWithBodies<'a, R: Read + Seek + Sized + Debug> {
+    extractor: &'a mut PkgExtractor<R>,
+    xar: Option<XarReader<R>>,
+    infos: std::vec::IntoIter<ComponentInfo>,
+    current: Option<(String, OdcReader<Cursor<Vec<u8>>>)>,
+    max_body_size: u64,
+}
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Iterate over every payload entry of every component, in archive
+    /// order, with the contents of each regular file of at most
+    /// `max_body_size` bytes; bigger files and other entries come with
+    /// `None`, so memory use stays bounded. `exclude_dirs` are left out.
+    pub fn entries_with_bodies(
+        &mut self,
+        max_body_size: u64,
+    ) -> Result<EntriesWithBodies<'_, R>, Box<dyn Error>> {
+        let mut xar = XarReader::new(self.take_reader()?)?;
+        let infos = pkg_flavor(&xar).and_then(|flavor| read_components(&mut xar, flavor));
+        let infos = match infos {
+            Ok(infos) => infos,
+            Err(e) => {
+                self.reader = Some(xar.into_inner());
+                return Err(e);
+            }
+        };
+        Ok(EntriesWithBodies {
+            extractor: self,
+            xar: Some(xar),
+            infos: infos.into_iter(),
+            current: None,
+            max_body_size,
+        })
+    }
+}
+
+impl<R: Read + Seek + Sized + Debug> EntriesWithBodies<'_, R> {
+    fn next_entry(&mut self) -> Result<Option<EntryWithBody>, Box<dyn Error>> {
+        loop {
+            let Some((component, cpio_reader)) = &mut self.current else {
+                let Some(info) = self.infos.next() else {
+                    return Ok(None);
+                };
+                let Some(xar) = self.xar.as_mut() else {
+                    return Ok(None);
+                };
+                let Some(data) = xar.get_file_data_from_path(&info.member("Payload"))? else {
+                    debug!("Component {:?} has no Payload", info.path);
+                    continue;
+                };
+                let cpio = decode_payload(&data)?.into_owned();
+                self.current = Some((info.identifier, OdcReader::new(Cursor::new(cpio))));
+                continue;
+            };
+
+            let Some(header) = cpio_reader.read_next()? else {
+                self.current = None;
+                continue;
+            };
+            let name = header.name();
+            if name == CPIO_TRAILER {
+                self.current = None;
+                continue;
+            }
+            if name.is_empty()
+                || name == "."
+                || self.extractor.options.excludes(name, header.mode())
+            {
+                continue;
+            }
+
+            let file_type = FileType::from_mode(header.mode());
+            let body = if file_type == FileType::Regular && header.file_size() <= self.max_body_size
+            {
+                let mut body = Vec::with_capacity(header.file_size() as usize);
+                Read::take(&mut *cpio_reader, header.file_size()).read_to_end(&mut body)?;
+                Some(body)
+            } else {
+                None
+            };
+            let meta = EntryMeta {
+                component: component.clone(),
+                name: name.to_string(),
+                file_type,
+                mode: header.mode(),
+                uid: header.uid(),
+                gid: header.gid(),
+                mtime: header.mtime(),
+                size: header.file_size(),
+            };
+            return Ok(Some((meta, body)));
+        }
+    }
+}
+
+impl<R: Read + Seek + Sized + Debug> Iterator for EntriesWithBodies<'_, R> {
+    type Item = Result<EntryWithBody, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                // Nothing sensible can follow a corrupt payload.
+                self.current = None;
+                self.infos = Vec::new().into_iter();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek + Sized + Debug> Drop for EntriesWithBodies<'_, R> {
+    fn drop(&mut self) {
+        if let Some(xar) = self.xar.take() {
+            self.extractor.reader = Some(xar.into_inner());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{cpio, gzip, package_info, XarBuilder};
+
+    #[test]
+    fn yields_bodies_of_small_files_only() {
+        let big = vec![b'x'; 4096];
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.small"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    ("./etc", 0o040755, b""),
+                    ("./etc/app.conf", 0o100644, b"verbose = true\n"),
+                    ("./etc/big.bin", 0o100644, &big),
+                    ("./etc/current", 0o120777, b"app.conf"),
+                    ("./README", 0o100644, b"hello\n"),
+                ])),
+            )
+            .build();
+        let mut extractor = PkgExtractor::new(Cursor::new(pkg), None);
+
+        let mut small = Vec::new();
+        let mut names = Vec::new();
+        for entry in extractor.entries_with_bodies(1024).unwrap() {
+            let (meta, body) = entry.unwrap();
+            assert_eq!(meta.component, "com.example.small");
+            names.push(meta.name.clone());
+            if let Some(body) = body {
+                small.push((meta.name, body));
+            }
+        }
+
+        assert_eq!(
+            names,
+            [
+                "./etc",
+                "./etc/app.conf",
+                "./etc/big.bin",
+                "./etc/current",
+                "./README"
+            ]
+        );
+        assert_eq!(
+            small,
+            [
+                ("./etc/app.conf".to_string(), b"verbose = true\n".to_vec()),
+                ("./README".to_string(), b"hello\n".to_vec()),
+            ]
+        );
+        // The reader is handed back once the iterator is dropped.
+        assert_eq!(extractor.list_entries().unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,43 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::error::Error;
+use std::fmt;
+
+/// Error returned by [`crate::PkgExtractor::try_extract`].
+#[derive(Debug)]
+pub enum ExtractError {
+    /// Extraction failed the ordinary way: malformed package, I/O error,
+    /// warning treated as error, ...
+    Failed(Box<dyn Error>),
+    /// The extractor (or one of the parsers it relies on) panicked. Carries
+    /// the panic message. This is always a bug worth reporting.
+    Internal(String),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Failed(e) => write!(f, "{e}"),
+            ExtractError::Internal(message) => write!(f, "internal error: {message}"),
+        }
+    }
+}
+
+impl Error for ExtractError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExtractError::Failed(e) => Some(e.as_ref()),
+            ExtractError::Internal(_) => None,
+        }
+    }
+}
+
+impl From<Box<dyn Error>> for ExtractError {
+    fn from(e: Box<dyn Error>) -> Self {
+        ExtractError::Failed(e)
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! `.pkgignore` files: gitignore-style patterns excluding payload entries,
+//! see [`crate::ExtractOptions::ignore`] and [`IgnoreFile`] for the syntax.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// A parsed ignore file: one pattern per line, matched against entry paths
+/// relative to the payload root.
+///
+/// The syntax is modelled on gitignore's:
+///
+/// - blank lines and lines starting with `#` are skipped; trailing spaces
+///   are dropped unless escaped as `\ `;
+/// - `!` re-includes what an earlier pattern ignored;
+/// - a trailing `/` only matches directories;
+/// - a `/` at the start or in the middle anchors the pattern to the payload
+///   root, otherwise it matches a name at any depth;
+/// - `*` and `?` match within one path component, `[a-z]`, `[!a-z]` and
+///   `[^a-z]` one character of (or not of) a set, and `\` escapes the next
+///   character;
+/// - `**/` matches any number of leading directories, `/**` everything
+///   below a directory, and `/**/` zero or more directories; any other `**`
+///   is a plain `*`.
+///
+/// The last matching pattern wins, and nothing below an ignored directory
+/// can be re-included. Unlike git, there is only this one file: no nested
+/// per-directory ignore files, `core.excludesFile` or `info/exclude`.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreFile {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    tokens: Vec<Token>,
+    /// Matched against the whole path rather than its last component.
+    anchored: bool,
+    negated: bool,
+    dir_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `[...]`: inclusive ranges, negated by a leading `!` or `^`.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    /// `**/`: zero or more leading directories.
+    Dirs,
+    /// A trailing `**`: anything, separators included.
+    Rest,
+}
+
+impl IgnoreFile {
+    /// Read and parse the ignore file at `path`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read ignore file {}: {e}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    /// Parse ignore patterns, one per line. Fails on a `[` set that is
+    /// never closed.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = trim_unescaped_spaces(line);
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (dir_only, pattern) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = pattern.contains('/');
+            let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+            let tokens =
+                tokenize(pattern).map_err(|e| format!("Invalid ignore pattern {line:?}: {e}"))?;
+            rules.push(Rule {
+                tokens,
+                anchored,
+                negated,
+                dir_only,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Whether `path` (relative to the payload root, `/`-separated) is
+    /// ignored, itself or through one of its parent directories.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let mut parents = path.match_indices('/').map(|(at, _)| &path[..at]);
+        parents.any(|parent| self.matches(parent, true)) || self.matches(path, is_dir)
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        let name_start = path.iter().rposition(|&c| c == '/').map_or(0, |at| at + 1);
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                let subject = if rule.anchored {
+                    &path[..]
+                } else {
+                    &path[name_start..]
+                };
+                (is_dir || !rule.dir_only) && matches(&rule.tokens, subject)
+            })
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+/// `line` without its trailing spaces, except one escaped as `\ `.
+fn trim_unescaped_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    let backslashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+    if backslashes % 2 == 1 && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                // A trailing lone backslash matches itself.
+                tokens.push(Token::Char(chars.get(i + 1).copied().unwrap_or('\\')));
+                i += 2;
+            }
+            '?' => {
+                tokens.push(Token::Any);
+                i += 1;
+            }
+            '*' => {
+                let run = chars[i..].iter().take_while(|&&c| c == '*').count();
+                let after = i + run;
+                let bounded_before = i == 0 || chars[i - 1] == '/';
+                if run >= 2 && bounded_before && chars.get(after) == Some(&'/') {
+                    tokens.push(Token::Dirs);
+                    i = after + 1;
+                } else if run >= 2 && bounded_before && after == chars.len() {
+                    tokens.push(Token::Rest);
+                    i = after;
+                } else {
+                    tokens.push(Token::Star);
+                    i = after;
+                }
+            }
+            '[' => {
+                let (class, end) = class(&chars, i + 1).ok_or("unterminated [")?;
+                tokens.push(class);
+                i = end;
+            }
+            c => {
+                tokens.push(Token::Char(c));
+                i += 1;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// The set starting after a `[` at `start`, and the index past its `]`.
+fn class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start;
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let first = i;
+    loop {
+        let mut c = *chars.get(i)?;
+        if c == ']' && i > first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        if c == '\\' {
+            i += 1;
+            c = *chars.get(i)?;
+        }
+        i += 1;
+        if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|&end| end != ']') {
+            let mut end = chars[i + 1];
+            i += 2;
+            if end == '\\' {
+                end = *chars.get(i)?;
+                i += 1;
+            }
+            ranges.push((c, end));
+        } else {
+            ranges.push((c, c));
+        }
+    }
+}
+
+/// Whether `tokens` match all of `text`.
+///
+/// A dynamic programme over (token, position) rather than backtracking, so
+/// patterns like `*a*a*a*b` stay linear in the path length: `next[p]` holds
+/// whether the tokens after the current one match `text[p..]`, and each
+/// row is built from the end of the text backwards.
+fn matches(tokens: &[Token], text: &[char]) -> bool {
+    let len = text.len();
+    let mut next = vec![false; len + 1];
+    next[len] = true;
+    let mut row = vec![false; len + 1];
+    for token in tokens.iter().rev() {
+        // For `**/`: whether `next` holds just after some `/` at or past `p`.
+        let mut after_slash = false;
+        for p in (0..=len).rev() {
+            let here = text.get(p);
+            row[p] = match token {
+                Token::Char(c) => here == Some(c) && next[p + 1],
+                Token::Any => here.is_some_and(|&c| c != '/') && next[p + 1],
+                Token::Class { negated, ranges } => here.is_some_and(|&c| {
+                    c != '/'
+                        && ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c)) != *negated
+                        && next[p + 1]
+                }),
+                Token::Star => next[p] || (here.is_some_and(|&c| c != '/') && row[p + 1]),
+                Token::Dirs => {
+                    if here == Some(&'/') {
+                        after_slash |= next[p + 1];
+                    }
+                    next[p] || after_slash
+                }
+                Token::Rest => next[p] || (here.is_some() && row[p + 1]),
+            };
+        }
+        std::mem::swap(&mut row, &mut next);
+    }
+    next[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::cpio;
+    use crate::PkgExtractor;
+    use std::io::Cursor;
+
+    #[test]
+    fn ignore_file_excludes_a_directory_and_a_glob() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ignore = tmp.path().join(".pkgignore");
+        fs::write(
+            &ignore,
+            "# build leftovers\nbuild/\n*.log\n!keep.log\n/top.txt\n",
+        )
+        .unwrap();
+        let out = tmp.path().join("out");
+        let payload = cpio(&[
+            ("./build", 0o040755, b""),
+            ("./build/obj.o", 0o100644, b"obj"),
+            ("./src/build", 0o100644, b"a file, not a directory"),
+            ("./src/main.c", 0o100644, b"int main;"),
+            ("./src/debug.log", 0o100644, b"noise"),
+            ("./keep.log", 0o100644, b"kept"),
+            ("./top.txt", 0o100644, b"top"),
+            ("./src/top.txt", 0o100644, b"nested"),
+        ]);
+
+        let mut extractor = PkgExtractor::new(Cursor::new(Vec::new()), Some(out.clone()))
+            .with_ignore_file(&ignore)
+            .unwrap();
+        extractor.extract_cpio(&payload, &out).unwrap();
+
+        assert!(!out.join("build").exists());
+        assert!(!out.join("src/debug.log").exists());
+        assert!(!out.join("top.txt").exists());
+        for kept in ["src/build", "src/main.c", "keep.log", "src/top.txt"] {
+            assert!(out.join(kept).is_file(), "{kept}");
+        }
+        assert!(PkgExtractor::new(Cursor::new(Vec::new()), None)
+            .with_ignore_file(&tmp.path().join("missing"))
+            .is_err());
+    }
+
+    #[test]
+    fn patterns_follow_gitignore_wildcards() {
+        let ignored =
+            |pattern: &str, path: &str| IgnoreFile::parse(pattern).unwrap().is_ignored(path, false);
+
+        // `**` next to other characters is a plain `*`.
+        assert!(ignored("foo**", "a/foobar"));
+        assert!(ignored("**foo", "a/barfoo"));
+        assert!(ignored("a/foo**", "a/foobar"));
+        assert!(!ignored("a/foo**", "b/foobar"));
+        // `**/`, `/**` and `/**/`.
+        assert!(ignored("**/cache", "cache"));
+        assert!(ignored("**/cache", "a/b/cache"));
+        assert!(ignored("lib/**", "lib/a/b.dylib"));
+        assert!(!ignored("lib/**", "lib"));
+        assert!(ignored("a/**/b", "a/b"));
+        assert!(ignored("a/**/b", "a/x/y/b"));
+        assert!(!ignored("a/**/b", "x/a/b"));
+        // `*` and `?` stay within a component.
+        assert!(!ignored("a/*.c", "a/b/c.c"));
+        assert!(ignored("a/?.c", "a/b.c"));
+        // Sets, negated with `!` or `^`.
+        assert!(ignored("*.[ch]", "x.h"));
+        assert!(ignored("[^a]*", "bcd"));
+        assert!(!ignored("[^a]*", "abc"));
+        assert!(ignored("[!a-c]x", "dx"));
+        assert!(!ignored("[!a-c]x", "bx"));
+        // Escapes and trailing spaces.
+        assert!(ignored("trailing\\ ", "trailing "));
+        assert!(ignored("trailing  ", "trailing"));
+        assert!(ignored("\\#hash", "#hash"));
+        assert!(ignored("\\!bang", "!bang"));
+        assert!(ignored("\\*", "*"));
+        assert!(!ignored("\\*", "x"));
+        assert!(IgnoreFile::parse("[abc").is_err());
+    }
+
+    #[test]
+    fn pathological_patterns_match_in_linear_time() {
+        let file = IgnoreFile::parse("*a*a*a*a*a*a*a*a*a*a*b\n**/**/**/**/**/x/**/y\n").unwrap();
+        let name = "a".repeat(4000);
+        let deep = vec!["x"; 500].join("/");
+
+        let started = std::time::Instant::now();
+        assert!(!file.is_ignored(&name, false));
+        assert!(file.is_ignored(&format!("{name}b"), false));
+        assert!(!file.is_ignored(&deep, false));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
@@ -4,42 +4,247 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use apple_flat_package::reader::{PkgFlavor, PkgReader};
+use apple_flat_package::reader::PkgFlavor;
 use apple_xar::reader::XarReader;
-use cpio_archive::{CpioReader as _, OdcReader};
+use apple_xar::table_of_contents::ChecksumType;
+use cpio_archive::{CpioReader as _, OdcHeader, OdcReader};
+use filetime::FileTime;
 use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Debug;
 use std::fs::{self, File, OpenOptions};
-use std::io::{Cursor, Read, Seek, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tempfile::SpooledTempFile;
 
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 
+mod apple_double;
+mod batch;
+mod bom;
+mod cas;
+#[cfg(feature = "codesign")]
+mod codesign;
+mod component;
+mod decisions;
+mod distribution;
+mod entries;
+mod error;
+mod ignore_file;
+mod macho;
+mod options;
 mod pbzx;
+mod report;
+mod resources;
+mod script_diff;
+#[cfg(feature = "signature")]
+mod signature;
+#[cfg(feature = "rusqlite")]
+mod sqlite;
+mod stats;
+#[cfg(feature = "tar")]
+mod tarball;
+#[cfg(test)]
+mod test_util;
+mod throttle;
+#[cfg(feature = "transform")]
+mod transform;
+mod tree_digest;
+
+pub use batch::{BatchExtractor, BatchOutcome, DuplicateAction};
+#[cfg(feature = "codesign")]
+pub use codesign::CodeSignature;
+pub use component::{ComponentHandle, ComponentInfo, Components};
+pub use distribution::InstallerResource;
+pub use entries::{EntriesWithBodies, EntryMeta, EntryWithBody};
+pub use error::ExtractError;
+pub use ignore_file::IgnoreFile;
+pub use macho::MachArch;
+pub use options::{EntryAction, ExtractOptions, ForceMode, LongComponentPolicy};
+pub use report::PathStyle;
+pub use script_diff::{ScriptChange, ScriptDiff};
+#[cfg(feature = "signature")]
+pub use signature::DetachedSignature;
+pub use stats::{ExtractionStats, FailedFile, ResumeCursor, Warning, WarningKind};
+#[cfg(feature = "transform")]
+pub use transform::TransformCommand;
+
+use component::{component_paths, pkg_flavor, read_components, MemberSource};
+use decisions::{Action, Decision};
+use distribution::Distribution;
+use report::{Cksum, ManifestEntry};
 
 const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
 
+/// File in the output directory listing the symlinks `symlinks_sidecar`
+/// recorded instead of creating them.
+pub const SYMLINKS_SIDECAR: &str = "symlinks.json";
+
+/// Manifest written to the output directory by
+/// [`ExtractOptions::content_addressed`] extractions: a JSON object mapping
+/// every payload path to its type and, for regular files, the SHA-256 of
+/// the stored object.
+pub const CAS_MANIFEST: &str = "cas-manifest.json";
+
+/// How many bytes [`PkgExtractor::from_stream`] buffers in memory before
+/// spilling the rest of the stream to an anonymous temporary file.
+pub const STREAM_SPILL_THRESHOLD: usize = 64 * 1024 * 1024;
+
+/// Callback registered with [`PkgExtractor::with_header_inspector`].
+type HeaderInspector = Box<dyn Fn(&CpioHeaderView)>;
+
+/// Callback registered with [`PkgExtractor::with_cursor_callback`].
+type CursorCallback = Box<dyn FnMut(&ResumeCursor)>;
+
+/// Callback registered with [`PkgExtractor::with_writer_factory`].
+type WriterFactory = Box<dyn FnMut(&Path, &FileType, u32) -> std::io::Result<Box<dyn Write>>>;
+
+/// Regular files of a baseline BOM, by `bom_key`: `(size, cksum)`.
+type Baseline = HashMap<String, (u64, u32)>;
+
+/// The entries of a component's BOM the payload has not matched yet, by
+/// [`bom::bom_key`].
+type BomLayout = HashMap<String, bom::BomEntry>;
+
+/// Output directories resolved so far, requested directory to the one
+/// extracted into (the same once resolved), shared by an extractor and the
+/// component handles it hands out.
+pub(crate) type OutputDirs = Arc<Mutex<HashMap<PathBuf, PathBuf>>>;
+
 pub struct PkgExtractor<R: Read + Seek + Sized + Debug> {
     reader: Option<R>,
+    // Where the xar starts in `reader`, recorded the first time it is parsed
+    // so later passes can seek back to it.
+    start_offset: Option<u64>,
     output_dir: PathBuf,
+    options: ExtractOptions,
+    manifest: Vec<ManifestEntry>,
+    stats: ExtractionStats,
+    decisions: Vec<Decision>,
+    /// Symlinks recorded instead of created, by `symlinks_sidecar`: link
+    /// (relative to the output directory) to target.
+    sidecar_links: BTreeMap<String, String>,
+    /// Modes and mtimes held back until the content pass is done:
+    /// `(path, mode, mtime)`, with no mtime for a directory whose mode
+    /// alone is held back.
+    deferred_metadata: Vec<(PathBuf, u32, Option<u32>)>,
+    /// `delta_baseline`, once read.
+    baseline: Option<Baseline>,
+    /// Identifier the cpio being extracted is tracked under in
+    /// `stats.cursor`: empty for a bare cpio, `None` for Scripts, which
+    /// are not tracked.
+    cursor_component: Option<String>,
+    payload_image: Option<File>,
+    header_inspector: Option<HeaderInspector>,
+    cursor_callback: Option<CursorCallback>,
+    component_order: Vec<String>,
+    cas_entries: BTreeMap<String, cas::CasEntry>,
+    bom_layout: Option<BomLayout>,
+    temp_dir: Option<PathBuf>,
+    /// Directories already made by the payload being extracted, so
+    /// thousands of files in one directory cost one `create_dir_all`.
+    created_dirs: HashSet<PathBuf>,
+    /// Where this session extracts to, so `avoid_clobber_dir` picks the
+    /// directory once however many components are extracted.
+    output_dirs: OutputDirs,
+    writer_factory: Option<WriterFactory>,
+    /// Whether `detached_signature` was checked, which `take_reader` does
+    /// once, before the package is first parsed.
+    #[cfg(feature = "signature")]
+    signature_verified: bool,
     // Retained for backward-compatible `new_with_file_path` API; no longer
     // used internally now that we no longer shell out to `xar`.
     #[allow(dead_code)]
     pkg_file_path: Option<PathBuf>,
 }
 
-#[derive(Debug, PartialEq)]
-enum FileType {
+/// Every field of a raw cpio (ODC) header, as handed to the callback of
+/// [`PkgExtractor::with_header_inspector`]. ODC headers carry `dev` and
+/// `rdev` as single numbers; there is no separate major/minor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpioHeaderView<'a> {
+    /// Entry name exactly as stored, e.g. `./usr/bin/foo`.
+    pub name: &'a str,
+    pub dev: u32,
+    pub inode: u32,
+    /// Full mode: file type and permission bits.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u32,
+    pub rdev: u32,
+    /// Modification time, seconds since the Unix epoch.
+    pub mtime: u32,
+    pub file_size: u64,
+}
+
+impl<'a> CpioHeaderView<'a> {
+    fn new(header: &'a dyn cpio_archive::CpioHeader) -> Self {
+        Self {
+            name: header.name(),
+            dev: header.device(),
+            inode: header.inode(),
+            mode: header.mode(),
+            uid: header.uid(),
+            gid: header.gid(),
+            nlink: header.nlink(),
+            rdev: header.rdev(),
+            mtime: header.mtime(),
+            file_size: header.file_size(),
+        }
+    }
+}
+
+/// Where one xar member's bytes live in the package, as claimed by the
+/// table of contents. See [`PkgExtractor::heap_map`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeapEntry {
+    /// Path inside the xar, e.g. `foo.pkg/Payload`.
+    pub path: String,
+    /// TOC file id.
+    pub id: u64,
+    /// Absolute offset of the archived bytes from the start of the package.
+    pub offset: u64,
+    /// Archived (possibly encoded) length in bytes.
+    pub length: u64,
+    /// Size in bytes once the xar-level encoding is undone.
+    pub size: u64,
+    /// xar encoding, e.g. `application/octet-stream` or `application/x-gzip`.
+    pub encoding: String,
+    /// `<style>:<hex digest>` of the archived bytes.
+    pub archived_checksum: String,
+    /// `<style>:<hex digest>` of the decoded bytes.
+    pub extracted_checksum: String,
+}
+
+/// Kind of a payload entry, from the file type bits of its cpio mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
     Directory,
     Regular,
     Symlink,
+    /// Devices, FIFOs, sockets: never extracted.
     Other,
 }
 
 impl FileType {
-    fn from_mode(mode: u32) -> Self {
+    /// Name used in reports: `directory`, `file`, `symlink` or `other`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileType::Directory => "directory",
+            FileType::Regular => "file",
+            FileType::Symlink => "symlink",
+            FileType::Other => "other",
+        }
+    }
+
+    /// Classify a full Unix mode (type and permission bits).
+    pub fn from_mode(mode: u32) -> Self {
         match mode & 0o170000 {
             0o040000 => FileType::Directory,
             0o100000 => FileType::Regular,
@@ -49,13 +254,82 @@ impl FileType {
     }
 }
 
+impl PkgExtractor<SpooledTempFile> {
+    /// Build an extractor from a `Read`-only source (a pipe, stdin, an HTTP
+    /// body, ...). The xar format needs random access, so the whole stream is
+    /// copied up front: the first [`STREAM_SPILL_THRESHOLD`] bytes stay in
+    /// memory, anything beyond that spills to an unnamed file in the system
+    /// temp directory, which is deleted when the extractor is dropped.
+    pub fn from_stream<S: Read>(
+        stream: S,
+        output_dir: Option<PathBuf>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::spool(
+            stream,
+            output_dir,
+            SpooledTempFile::new(STREAM_SPILL_THRESHOLD),
+        )
+    }
+
+    /// Like [`Self::from_stream`], but spill to `temp_dir` rather than the
+    /// system temp directory, which is then also used for every other
+    /// temporary file (see [`Self::with_temp_dir`]).
+    pub fn from_stream_in<S: Read>(
+        stream: S,
+        output_dir: Option<PathBuf>,
+        temp_dir: PathBuf,
+    ) -> Result<Self, Box<dyn Error>> {
+        let spool = SpooledTempFile::new_in(STREAM_SPILL_THRESHOLD, &temp_dir);
+        Ok(Self::spool(stream, output_dir, spool)?.with_temp_dir(temp_dir))
+    }
+
+    fn spool<S: Read>(
+        mut stream: S,
+        output_dir: Option<PathBuf>,
+        mut spool: SpooledTempFile,
+    ) -> Result<Self, Box<dyn Error>> {
+        let copied = std::io::copy(&mut stream, &mut spool)?;
+        spool.rewind()?;
+        debug!(
+            "Buffered {copied} bytes from stream ({})",
+            if spool.is_rolled() {
+                "spilled to disk"
+            } else {
+                "in memory"
+            }
+        );
+        Ok(Self::new(spool, output_dir))
+    }
+}
+
 impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
     pub fn new(reader: R, output_dir: Option<PathBuf>) -> Self {
         let output_dir = output_dir.unwrap_or_else(|| PathBuf::from("extracted_pkg"));
 
         Self {
             reader: Some(reader),
+            start_offset: None,
             output_dir,
+            options: ExtractOptions::default(),
+            manifest: Vec::new(),
+            stats: ExtractionStats::default(),
+            decisions: Vec::new(),
+            sidecar_links: BTreeMap::new(),
+            deferred_metadata: Vec::new(),
+            baseline: None,
+            cursor_component: Some(String::new()),
+            payload_image: None,
+            header_inspector: None,
+            cursor_callback: None,
+            component_order: Vec::new(),
+            cas_entries: BTreeMap::new(),
+            bom_layout: None,
+            temp_dir: None,
+            created_dirs: HashSet::new(),
+            output_dirs: OutputDirs::default(),
+            writer_factory: None,
+            #[cfg(feature = "signature")]
+            signature_verified: false,
             pkg_file_path: None,
         }
     }
@@ -69,470 +343,4600 @@ impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
 
         Self {
             reader: Some(reader),
+            start_offset: None,
             output_dir,
+            options: ExtractOptions::default(),
+            manifest: Vec::new(),
+            stats: ExtractionStats::default(),
+            decisions: Vec::new(),
+            sidecar_links: BTreeMap::new(),
+            deferred_metadata: Vec::new(),
+            baseline: None,
+            cursor_component: Some(String::new()),
+            payload_image: None,
+            header_inspector: None,
+            cursor_callback: None,
+            component_order: Vec::new(),
+            cas_entries: BTreeMap::new(),
+            bom_layout: None,
+            temp_dir: None,
+            created_dirs: HashSet::new(),
+            output_dirs: OutputDirs::default(),
+            writer_factory: None,
+            #[cfg(feature = "signature")]
+            signature_verified: false,
             pkg_file_path: Some(pkg_file_path),
         }
     }
 
-    pub fn extract(mut self) -> Result<(), Box<dyn Error>> {
-        fs::create_dir_all(&self.output_dir)?;
+    /// Replace the extraction options. See [`ExtractOptions`].
+    pub fn with_options(mut self, options: ExtractOptions) -> Self {
+        self.options = options;
+        #[cfg(feature = "signature")]
+        {
+            self.signature_verified = false;
+        }
+        self
+    }
+
+    /// Cap how fast file contents are written, in bytes per second. Shorthand
+    /// for setting [`ExtractOptions::rate_limit`].
+    pub fn with_rate_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.options.rate_limit = Some(bytes_per_sec);
+        self
+    }
+
+    /// Skip the entries the ignore file at `path` matches. Shorthand for
+    /// setting [`ExtractOptions::ignore`] to [`IgnoreFile::load`]`(path)`.
+    pub fn with_ignore_file(mut self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        self.options.ignore = Some(IgnoreFile::load(path)?);
+        Ok(self)
+    }
+
+    /// Create temporary files ([`ExtractOptions::atomic_writes`],
+    /// [`ExtractOptions::content_addressed`]) in `dir`,
+    /// e.g. a large scratch volume, rather than next to their destination.
+    /// When `dir` is on another filesystem than the output, files are copied
+    /// into place instead of renamed.
+    pub fn with_temp_dir(mut self, dir: PathBuf) -> Self {
+        self.temp_dir = Some(dir);
+        self
+    }
+
+    /// Call `inspector` with the raw header of every cpio entry (payload and,
+    /// when extracted, scripts) before deciding what to do with it, `.` and
+    /// filtered or refused entries included. Purely observational: it
+    /// exposes fields extraction itself ignores, such as uid/gid and nlink.
+    pub fn with_header_inspector<F: Fn(&CpioHeaderView) + 'static>(mut self, inspector: F) -> Self {
+        self.header_inspector = Some(Box::new(inspector));
+        self
+    }
+
+    /// Call `callback` with the resume cursor every time it moves: before
+    /// each payload entry, with the entries done so far, and once each
+    /// component is done. Saving what it last got lets an extraction that
+    /// failed or was killed carry on from there through
+    /// `ExtractOptions::resume_from`; the entry being written when it
+    /// stopped is written again.
+    pub fn with_cursor_callback<F: FnMut(&ResumeCursor) + 'static>(mut self, callback: F) -> Self {
+        self.cursor_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Extract a product package's components with these identifiers first,
+    /// in this order (e.g. dependencies before what builds on them), then
+    /// the unlisted ones in archive order. When components overwrite each
+    /// other's files, the last one extracted wins.
+    pub fn with_component_order(mut self, identifiers: Vec<String>) -> Self {
+        self.component_order = identifiers;
+        self
+    }
+
+    /// Decide per entry where extracted bytes go: `factory` is called with
+    /// the destination path, type and mode of every regular file and
+    /// symlink, and the file contents (or the symlink target) are written to
+    /// the writer it returns -- a real file, a compressing writer, a sink
+    /// for verification-only runs, ... The writer is flushed once the entry
+    /// is written. The mode is the one the entry would be created with on
+    /// disk: `force_mode` applies to regular files, symlinks keep theirs.
+    /// Without a factory, files are created on disk with that mode.
+    ///
+    /// With a factory installed the extractor itself writes nothing to disk:
+    /// directories are not created and symlinks are not made.
+    pub fn with_writer_factory<F>(mut self, factory: F) -> Self
+    where
+        F: FnMut(&Path, &FileType, u32) -> std::io::Result<Box<dyn Write>> + 'static,
+    {
+        self.writer_factory = Some(Box::new(factory));
+        self
+    }
+
+    /// Describe where every file in the xar table of contents lives: absolute
+    /// offset, archived length, decoded size, encoding and checksums. Purely
+    /// diagnostic, for lining up the TOC's claims with the bytes actually in
+    /// the package when extraction of a malformed pkg goes wrong. Each entry
+    /// is also logged at debug level.
+    pub fn heap_map(&mut self) -> Result<Vec<HeapEntry>, Box<dyn Error>> {
+        self.with_xar(|_, xar| {
+            let heap_start = xar.heap_start_offset();
+            let mut entries = Vec::new();
+            for (path, file) in xar.files()? {
+                let Some(data) = file.data else {
+                    continue;
+                };
+                let entry = HeapEntry {
+                    path,
+                    id: file.id,
+                    offset: heap_start + data.offset,
+                    length: data.length,
+                    size: data.size,
+                    encoding: data.encoding.style,
+                    archived_checksum: format!(
+                        "{}:{}",
+                        data.archived_checksum.style, data.archived_checksum.checksum
+                    ),
+                    extracted_checksum: format!(
+                        "{}:{}",
+                        data.extracted_checksum.style, data.extracted_checksum.checksum
+                    ),
+                };
+                debug!("heap entry: {entry:?}");
+                entries.push(entry);
+            }
+            Ok(entries)
+        })
+    }
 
-        let reader = self.reader.take().unwrap();
+    /// Extract the package into the output directory and report what was
+    /// written.
+    pub fn extract(mut self) -> Result<ExtractionStats, Box<dyn Error>> {
+        // Check the detached signature before writing anything.
+        #[cfg(feature = "signature")]
+        if self.options.detached_signature.is_some() {
+            let reader = self.take_reader()?;
+            self.reader = Some(reader);
+        }
+        self.resolve_output_dir()?;
+        if self.writer_factory.is_none() {
+            fs::create_dir_all(&self.output_dir)?;
+        }
 
-        // `PkgReader` gives us the flavor and wraps the xar; `into_inner`
-        // hands the xar back so we can read `Payload` bytes ourselves. We
-        // avoid `ComponentPackageReader::payload_reader`: that helper feeds
+        // We read `Payload` bytes straight from the xar rather than going
+        // through `ComponentPackageReader::payload_reader`: that helper feeds
         // the Payload through `cpio_archive::reader`, which only sniffs cpio
         // magics (`070701`/`070702`/`070707`). Real Apple pkgs wrap the cpio
         // in `pbzx`, so the helper would return `Err(BadMagic)` and we'd
         // silently produce empty output.
-        let pkg_reader = PkgReader::new(reader)?;
-        let flavor = pkg_reader.flavor();
-        let mut xar = pkg_reader.into_inner();
-
-        match flavor {
-            PkgFlavor::Component => {
-                debug!("Package type: Component");
-                self.extract_root_component(&mut xar)?;
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            for component in &components {
+                if component.auth.as_deref() == Some("root") || component.runs_scripts {
+                    info!(
+                        "Component {} installs with auth={} and {} scripts",
+                        component.identifier,
+                        component.auth.as_deref().unwrap_or("none"),
+                        if component.runs_scripts { "runs" } else { "no" },
+                    );
+                }
             }
-            PkgFlavor::Product => {
-                debug!("Package type: Product");
-                self.extract_product(&mut xar)?;
+            if let Some(dest) = &this.options.component_report {
+                report::write_components(dest, &components)?;
             }
-        }
+            match flavor {
+                PkgFlavor::Component => {
+                    debug!("Package type: Component");
+                    this.extract_root_component(xar, &components[0])
+                }
+                PkgFlavor::Product => {
+                    debug!("Package type: Product");
+                    this.check_declared_components(xar, &components)?;
+                    this.extract_product(xar, &components)
+                }
+            }
+        })?;
+        self.apply_deferred_metadata()?;
+        self.write_reports()?;
 
         info!(
             "Extraction completed. Files in: {}",
             self.output_dir.display()
         );
-        Ok(())
+        if !self.stats.failed_files.is_empty() {
+            warn!(
+                "{} files could not be extracted",
+                self.stats.failed_files.len()
+            );
+        }
+        Ok(self.stats)
+    }
+
+    /// Like [`PkgExtractor::extract`], but never unwinds into the caller:
+    /// a panic anywhere in the extraction is caught and returned as
+    /// [`ExtractError::Internal`], so fuzzing harnesses and services fed
+    /// untrusted packages keep running. The panic message is still printed
+    /// by the panic hook.
+    ///
+    /// Known ways malformed input can panic today are bugs in the parsers
+    /// we build on (`apple-xar`'s TOC handling, `cpio-archive`'s header
+    /// decoding) and a broken internal invariant (a component package
+    /// always yields exactly one root component, which `extract` indexes).
+    /// Allocations sized from header fields (pbzx chunks, symlink targets)
+    /// can still abort the process on allocation failure, which no
+    /// `catch_unwind` can intercept.
+    pub fn try_extract(self) -> Result<ExtractionStats, ExtractError> {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || self.extract())) {
+            Ok(result) => Ok(result?),
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                Err(ExtractError::Internal(message))
+            }
+        }
+    }
+
+    /// Sum the sizes of the payload's regular files grouped by their first
+    /// path component (`usr`, `Library`, `Applications`, ...), across every
+    /// component, without writing anything. A file at the payload root is
+    /// its own group.
+    pub fn size_breakdown(&mut self) -> Result<BTreeMap<String, u64>, Box<dyn Error>> {
+        self.with_xar(|_, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            let mut totals = BTreeMap::new();
+            walk_payloads(xar, &components, |_, header, _| {
+                if FileType::from_mode(header.mode()) != FileType::Regular {
+                    return Ok(());
+                }
+                let Some(top) = safe_join(Path::new(""), header.name().trim_start_matches('/'))
+                    .and_then(|p| p.iter().next().map(|c| c.to_string_lossy().into_owned()))
+                else {
+                    return Ok(());
+                };
+                *totals.entry(top).or_insert(0) += header.file_size();
+                Ok(())
+            })?;
+            Ok(totals)
+        })
+    }
+
+    /// Whether any component's payload has an entry at `path` (relative to
+    /// the payload root; a leading `./` or `/` is ignored), without
+    /// extracting anything. Stops at the first match.
+    pub fn contains(&mut self, path: &str) -> Result<bool, Box<dyn Error>> {
+        fn normalize(path: &str) -> &str {
+            let path = path.strip_prefix("./").unwrap_or(path);
+            path.trim_start_matches('/').trim_end_matches('/')
+        }
+        let wanted = normalize(path);
+        if wanted.is_empty() || wanted == "." {
+            return Ok(true);
+        }
+        self.with_xar(|_, xar| {
+            let flavor = pkg_flavor(xar)?;
+            for component in read_components(xar, flavor)? {
+                let Some(data) = xar.get_file_data_from_path(&component.member("Payload"))? else {
+                    continue;
+                };
+                let cpio = decode_payload(&data)?;
+                let mut cpio_reader = OdcReader::new(Cursor::new(cpio.as_ref()));
+                while let Some(header) = cpio_reader.read_next()? {
+                    if header.name() == CPIO_TRAILER {
+                        break;
+                    }
+                    if normalize(header.name()) == wanted {
+                        debug!("Found {wanted} in {}", component.identifier);
+                        return Ok(true);
+                    }
+                }
+            }
+            Ok(false)
+        })
+    }
+
+    /// How many components the package has -- 1 for a component package --
+    /// from the table of contents alone, without reading any PackageInfo
+    /// or payload: cheap enough to show "component 2 of 5" up front.
+    pub fn component_count(&mut self) -> Result<usize, Box<dyn Error>> {
+        self.with_xar(|_, xar| {
+            let flavor = pkg_flavor(xar)?;
+            Ok(component_paths(xar, flavor)?.len())
+        })
+    }
+
+    /// Every payload entry name of every component, in archive order,
+    /// without extracting anything; control characters are escaped when
+    /// `sanitize_control_chars` is set, and `exclude_dirs` are left out.
+    pub fn list_entries(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            let mut names = Vec::new();
+            walk_payloads(xar, &components, |_, header, _| {
+                if !this.options.excludes(header.name(), header.mode()) {
+                    names.push(this.options.sanitize_name(header.name()).into_owned());
+                }
+                Ok(())
+            })?;
+            Ok(names)
+        })
+    }
+
+    /// Iterate over the package's components in archive order, each as a
+    /// [`ComponentHandle`] that can be inspected or extracted on its own --
+    /// the building blocks of `extract`'s loop, for callers who want to
+    /// drive it themselves. A component package yields its root component.
+    pub fn components(&mut self) -> Result<Components<'_, R>, Box<dyn Error>> {
+        let mut xar = XarReader::new(self.take_reader()?)?;
+        let infos = pkg_flavor(&xar).and_then(|flavor| read_components(&mut xar, flavor));
+        let infos = match infos {
+            Ok(infos) => infos,
+            Err(e) => {
+                self.reader = Some(xar.into_inner());
+                return Err(e);
+            }
+        };
+        Ok(Components {
+            extractor: self,
+            xar: Some(xar),
+            infos: infos.into_iter(),
+        })
+    }
+
+    /// The directory extraction writes to.
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// What extraction so far has written, for the methods that extract
+    /// without consuming the extractor (e.g. `extract_first_where`).
+    pub fn stats(&self) -> &ExtractionStats {
+        &self.stats
+    }
+
+    /// Extract only the first component, in archive order, for which
+    /// `predicate` holds -- e.g. the first whose identifier starts with
+    /// `com.apple.`. For a component package the root component is the only
+    /// candidate. Returns whether any component matched.
+    pub fn extract_first_where<F: Fn(&ComponentInfo) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Result<bool, Box<dyn Error>> {
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let Some(component) = read_components(xar, flavor)?
+                .into_iter()
+                .find(|c| predicate(c))
+            else {
+                debug!("No component matched the predicate");
+                return Ok(false);
+            };
+
+            this.extract_single_component(xar, &component)?;
+            Ok(true)
+        })
+    }
+
+    /// Extract only the component with the largest installed size -- the
+    /// main payload of an installer bundling an app with small helpers --
+    /// and return its identifier. The size is the PackageInfo's
+    /// `installKBytes`, or the archived Payload size when that is not
+    /// declared; ties go to the first component in archive order. `None`
+    /// when no component has a Payload.
+    pub fn extract_largest_component(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            let payload_sizes: HashMap<String, u64> = xar
+                .files()?
+                .into_iter()
+                .filter_map(|(path, file)| Some((path, file.data?.size)))
+                .collect();
+            let Some((kbytes, component)) = components
+                .into_iter()
+                .filter_map(|c| {
+                    let payload = payload_sizes.get(&c.member("Payload"))?;
+                    Some((c.install_kbytes.unwrap_or(payload.div_ceil(1024)), c))
+                })
+                .rev()
+                .max_by_key(|(kbytes, _)| *kbytes)
+            else {
+                debug!("No component has a Payload");
+                return Ok(None);
+            };
+
+            debug!(
+                "{} is the largest component ({kbytes} KiB)",
+                component.identifier
+            );
+            this.extract_single_component(xar, &component)?;
+            Ok(Some(component.identifier))
+        })
+    }
+
+    /// Extract `component` on its own, reports included.
+    pub(crate) fn extract_single_component<S: MemberSource>(
+        &mut self,
+        xar: &mut S,
+        component: &ComponentInfo,
+    ) -> Result<(), Box<dyn Error>> {
+        info!("Extracting component {}", component.identifier);
+        self.resolve_output_dir()?;
+        if self.writer_factory.is_none() {
+            fs::create_dir_all(&self.output_dir)?;
+        }
+        if !self.extract_component(xar, component)? {
+            self.warn(
+                WarningKind::MissingPayload,
+                format!("Component {} has no Payload", component.identifier),
+            )?;
+        }
+        self.apply_deferred_metadata()?;
+        self.write_reports()
+    }
+
+    /// Finish a single payload file whose extraction was interrupted
+    /// mid-write: the bytes already on disk are kept and only the rest of
+    /// the entry body is appended. Meant for huge files, where redoing the
+    /// write is the expensive part. Returns how many bytes were appended;
+    /// an absent file is extracted whole.
+    ///
+    /// `entry` is the payload name with or without its `./` prefix. The
+    /// first component whose Payload holds it is used, and the file is
+    /// looked for where extraction puts it: `strip_leading_slash`,
+    /// `component_dir_map`, `per_component_dirs` and `Route` extension
+    /// actions apply. A symlink in its place is refused rather than
+    /// followed. Payload compression (pbzx, gzip) cannot be seeked into,
+    /// so the Payload is decoded up to the end of the entry, as it is read;
+    /// it is never held in memory decoded.
+    pub fn resume_file(&mut self, entry: &str) -> Result<u64, Box<dyn Error>> {
+        let strip_leading_slash = self.options.strip_leading_slash;
+        let payload_path = |name: &str| {
+            let name = if strip_leading_slash {
+                name.trim_start_matches('/')
+            } else {
+                name
+            };
+            safe_join(Path::new(""), name).filter(|p| !p.as_os_str().is_empty())
+        };
+        let wanted = payload_path(entry)
+            .ok_or_else(|| format!("{entry:?} is not a valid payload entry name"))?;
+
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            for component in read_components(xar, flavor)? {
+                let Some(data) = xar.get_file_data_from_path(&component.member("Payload"))? else {
+                    continue;
+                };
+                let mut cpio_reader = OdcReader::new(payload_reader(&data)?);
+                let header = loop {
+                    match cpio_reader.read_next()? {
+                        Some(header) if payload_path(header.name()).as_ref() == Some(&wanted) => {
+                            break Some(header)
+                        }
+                        Some(_) => {}
+                        None => break None,
+                    }
+                };
+                let Some(header) = header else {
+                    continue;
+                };
+                if FileType::from_mode(header.mode()) != FileType::Regular {
+                    return Err(format!("{entry:?} is not a regular file").into());
+                }
+
+                let root = match this.options.extension_action(&wanted.to_string_lossy()) {
+                    Some(EntryAction::Skip) => {
+                        return Err(format!(
+                            "{entry:?} is not extracted: its extension is mapped to Skip"
+                        )
+                        .into())
+                    }
+                    Some(EntryAction::Route(dir)) => safe_join(&this.output_dir, dir),
+                    Some(EntryAction::Extract) | None => this.payload_root(&component)?,
+                };
+                let root = root
+                    .ok_or_else(|| format!("{entry:?} is routed outside the output directory"))?;
+                let target = root.join(&wanted);
+                let file_size = header.file_size();
+                let done = match fs::symlink_metadata(&target) {
+                    Ok(metadata) if metadata.file_type().is_symlink() => {
+                        return Err(format!(
+                            "{} is a symlink, not resuming through it",
+                            target.display()
+                        )
+                        .into())
+                    }
+                    Ok(metadata) => metadata.len(),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+                    Err(e) => return Err(e.into()),
+                };
+                if done > file_size {
+                    return Err(format!(
+                        "{} is {done} bytes, larger than the {file_size}-byte entry",
+                        target.display(),
+                    )
+                    .into());
+                }
+
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut outfile = if done == 0 {
+                    create_file_with_mode(&target, this.options.entry_mode(header.mode()))?
+                } else {
+                    OpenOptions::new().append(true).open(&target)?
+                };
+                std::io::copy(
+                    &mut Read::take(&mut cpio_reader, done),
+                    &mut std::io::sink(),
+                )?;
+                let appended = std::io::copy(&mut cpio_reader, &mut outfile)?;
+                if appended != file_size - done {
+                    return Err(format!("{entry:?} is truncated in its Payload").into());
+                }
+                info!(
+                    "Resumed {} at byte {done}, appended {appended} bytes",
+                    target.display(),
+                );
+                return Ok(appended);
+            }
+            Err(format!("No payload entry {entry:?}").into())
+        })
+    }
+
+    /// Hex SHA-256 of the compressed table of contents. The TOC records
+    /// every member's checksum, so two packages with the same digest have
+    /// the same contents.
+    pub(crate) fn toc_digest(&mut self) -> Result<String, Box<dyn Error>> {
+        self.with_xar(|_, xar| {
+            let digest = xar.digest_table_of_contents_with(ChecksumType::Sha256)?;
+            Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+        })
+    }
+
+    /// Hand out the package reader positioned at the start of the xar. The
+    /// first call records where that is, so inspection methods and the final
+    /// `extract` can each parse the archive from the same place, and checks
+    /// `detached_signature`, so nothing parses a package that does not match
+    /// it.
+    fn take_reader(&mut self) -> Result<R, Box<dyn Error>> {
+        let mut reader = self
+            .reader
+            .take()
+            .ok_or("package reader is no longer available")?;
+        match self.start_offset {
+            Some(offset) => {
+                reader.seek(SeekFrom::Start(offset))?;
+            }
+            None => self.start_offset = Some(reader.stream_position()?),
+        }
+        #[cfg(feature = "signature")]
+        if let Some(expected) = self
+            .options
+            .detached_signature
+            .as_ref()
+            .filter(|_| !self.signature_verified)
+        {
+            if let Err(e) = signature::verify(&mut reader, expected) {
+                self.reader = Some(reader);
+                return Err(e);
+            }
+            self.signature_verified = true;
+        }
+        Ok(reader)
+    }
+
+    /// Parse the xar and run `f` on it, then give the reader back so further
+    /// `&mut self` calls (and `extract`) still work.
+    fn with_xar<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self, &mut XarReader<R>) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut xar = XarReader::new(self.take_reader()?)?;
+        let result = f(self, &mut xar);
+        self.reader = Some(xar.into_inner());
+        result
     }
 
     fn extract_root_component<T: Read + Seek + Sized + Debug>(
-        &self,
+        &mut self,
         xar: &mut XarReader<T>,
+        component: &ComponentInfo,
     ) -> Result<(), Box<dyn Error>> {
-        match xar.get_file_data_from_path("Payload")? {
-            Some(data) => self.extract_payload_bytes(&data),
-            None => {
-                warn!("Component package has no Payload");
-                Ok(())
-            }
+        if !self.extract_component(xar, component)? {
+            self.warn(
+                WarningKind::MissingPayload,
+                "Component package has no Payload".into(),
+            )?;
         }
+        Ok(())
     }
 
     fn extract_product<T: Read + Seek + Sized + Debug>(
-        &self,
+        &mut self,
         xar: &mut XarReader<T>,
+        components: &[ComponentInfo],
     ) -> Result<(), Box<dyn Error>> {
-        // Sub-packages live at the top level of the xar as directories whose
-        // name ends in `.pkg`. This matches what `PkgReader::component_packages`
-        // does internally; we just read the Payload file ourselves.
-        let sub_pkgs: Vec<String> = xar
-            .files()?
-            .into_iter()
-            .filter_map(|(name, _)| {
-                if name.ends_with(".pkg") && !name.contains('/') {
-                    Some(name)
-                } else {
-                    None
-                }
-            })
-            .collect();
+        info!("Found {} component packages", components.len());
 
-        info!("Found {} component packages", sub_pkgs.len());
+        let mut ordered: Vec<&ComponentInfo> = components.iter().collect();
+        ordered.sort_by_key(|c| {
+            self.component_order
+                .iter()
+                .position(|id| *id == c.identifier)
+                .unwrap_or(usize::MAX)
+        });
 
         let mut extracted_any = false;
-        for (i, sub_pkg) in sub_pkgs.iter().enumerate() {
+        for (position, component) in ordered.into_iter().enumerate() {
             debug!(
                 "Extracting component package {}/{}: {}",
-                i + 1,
-                sub_pkgs.len(),
-                sub_pkg
+                position + 1,
+                components.len(),
+                component.path
             );
-            let payload_path = format!("{sub_pkg}/Payload");
-            match xar.get_file_data_from_path(&payload_path)? {
-                Some(data) => match self.extract_payload_bytes(&data) {
-                    Ok(()) => extracted_any = true,
-                    Err(e) => warn!("Payload extraction failed for {sub_pkg}: {e}"),
-                },
-                None => debug!("Sub-package {sub_pkg} has no Payload, skipping"),
+            match self.extract_component(xar, component) {
+                Ok(true) => extracted_any = true,
+                Ok(false) => debug!("Sub-package {} has no Payload, skipping", component.path),
+                Err(e) => self.warn(
+                    WarningKind::ComponentFailed,
+                    format!("Payload extraction failed for {}: {e}", component.path),
+                )?,
             }
         }
 
-        if !extracted_any && !sub_pkgs.is_empty() {
+        if !extracted_any && !components.is_empty() {
             return Err("No component payload could be extracted".into());
         }
         Ok(())
     }
 
-    /// Dispatch on the magic bytes of a `Payload` file: `pbzx`-wrapped xz
-    /// (modern pkgs), gzip-compressed cpio (pre-Mavericks legacy), or raw
-    /// cpio (rare but permitted).
-    fn extract_payload_bytes(&self, data: &[u8]) -> Result<(), Box<dyn Error>> {
-        if data.len() >= 4 && &data[0..4] == b"pbzx" {
-            let mut reader = pbzx::PbzxReader::new(Cursor::new(data))?;
-            let mut decompressed = Vec::new();
-            reader.decompress_to(&mut decompressed)?;
-            debug!("pbzx decompressed {} bytes", decompressed.len());
-            self.extract_cpio(&decompressed)
-        } else if data.len() >= 3 && data[0..3] == GZIP_MAGIC {
-            let mut decoder = libflate::gzip::Decoder::new(Cursor::new(data))?;
-            let mut decompressed = Vec::new();
-            decoder.read_to_end(&mut decompressed)?;
-            debug!("gunzipped {} bytes", decompressed.len());
-            self.extract_cpio(&decompressed)
-        } else {
-            debug!("assuming raw cpio ({} bytes)", data.len());
-            self.extract_cpio(data)
+    /// Warn about every component the Distribution declares but the archive
+    /// lacks -- a sign of a malformed or partially assembled package. A
+    /// declared component is present when a component's identifier matches
+    /// the pkg-ref id, or its directory matches the `#foo.pkg` reference.
+    fn check_declared_components<T: Read + Seek + Sized + Debug>(
+        &mut self,
+        xar: &mut XarReader<T>,
+        components: &[ComponentInfo],
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(data) = xar.get_file_data_from_path("Distribution")? else {
+            return Ok(());
+        };
+        let distribution = match Distribution::parse(&data) {
+            Ok(distribution) => distribution,
+            Err(e) => {
+                return self.warn(
+                    WarningKind::UnparsableDistribution,
+                    format!("Unparsable Distribution: {e}"),
+                )
+            }
+        };
+
+        for pkg_ref in &distribution.pkg_refs {
+            let archive_path = pkg_ref.archive_path();
+            let present = components
+                .iter()
+                .any(|c| c.identifier == pkg_ref.id || archive_path.as_deref() == Some(&c.path));
+            if !present {
+                self.warn(
+                    WarningKind::MissingComponent,
+                    format!(
+                    "Distribution declares component {} ({}) but the archive does not contain it",
+                    pkg_ref.id,
+                    archive_path.as_deref().unwrap_or("no archive reference"),
+                ),
+                )?;
+            }
         }
+        Ok(())
     }
 
-    /// Extract a decompressed cpio (ODC / portable-ASCII) byte stream into
-    /// `self.output_dir`.
-    fn extract_cpio(&self, data: &[u8]) -> Result<(), Box<dyn Error>> {
-        let cursor = Cursor::new(data);
-        let mut cpio_reader = OdcReader::new(cursor);
-
-        let mut file_count: u64 = 0;
-        let mut total_bytes: u64 = 0;
+    /// Extract one component, starting over up to `component_retries` times
+    /// when an attempt fails with a transient I/O error. Every attempt reads
+    /// the component's members afresh from their heap offsets, so a reader
+    /// that desynced mid-stream gets a clean start.
+    fn extract_component<S: MemberSource>(
+        &mut self,
+        xar: &mut S,
+        component: &ComponentInfo,
+    ) -> Result<bool, Box<dyn Error>> {
+        let manifest_len = self.manifest.len();
+        let decisions_len = self.decisions.len();
+        let deferred_len = self.deferred_metadata.len();
+        let stats = self.stats.clone();
+        let mut attempt = 0;
+        loop {
+            match self.extract_component_once(xar, component) {
+                Err(e) if attempt < self.options.component_retries && is_transient(e.as_ref()) => {
+                    attempt += 1;
+                    self.note_warning(
+                        WarningKind::Retry,
+                        format!(
+                            "Retrying component {} ({attempt}/{}) after: {e}",
+                            component.path, self.options.component_retries
+                        ),
+                    );
+                    self.manifest.truncate(manifest_len);
+                    self.decisions.truncate(decisions_len);
+                    self.deferred_metadata.truncate(deferred_len);
+                    self.stats = stats.clone();
+                }
+                result => return result,
+            }
+        }
+    }
 
-        while let Some(header) = cpio_reader.read_next()? {
-            let name = header.name().to_string();
-            let file_size = header.file_size();
-            let mode = header.mode();
+    /// Extract one component's Payload into the output directory, plus its
+    /// Scripts when `extract_scripts` is set. Returns whether the component
+    /// had a Payload at all.
+    fn extract_component_once<S: MemberSource>(
+        &mut self,
+        xar: &mut S,
+        component: &ComponentInfo,
+    ) -> Result<bool, Box<dyn Error>> {
+        let done = |cursor: &ResumeCursor| cursor.done.contains(&component.identifier);
+        if self.options.resume_from.as_ref().is_some_and(done) {
+            debug!("Resuming: skipping component {}", component.identifier);
+            self.component_done(component);
+            return Ok(true);
+        }
+        let payload = xar.member_data(&component.member("Payload"))?;
+        let root = match payload {
+            Some(_) => self.payload_root(component)?,
+            None => None,
+        };
+        if let (Some(data), Some(root)) = (&payload, root) {
+            if self.writer_factory.is_none() {
+                fs::create_dir_all(&root)?;
+            }
+            if self.options.bom_layout {
+                self.load_bom_layout(xar, component)?;
+            }
+            let cpio = decode_payload(data)?;
+            self.cursor_component = Some(component.identifier.clone());
+            self.extract_cpio(&cpio, &root)?;
+            self.finish_bom_layout(&root)?;
+            self.append_payload_image(&cpio)?;
+        }
+        // Scripts are redone when resuming inside a component: untracked.
+        self.cursor_component = None;
 
-            // Apple Payload cpios prefix every name with `./`; the `.` root
-            // entry is the only one we need to skip explicitly.
-            if name.is_empty() || name == "." {
-                continue;
+        if self.options.extract_scripts {
+            let scripts_root = self.output_dir.join("Scripts");
+            let dest = if component.path.is_empty() {
+                Some(scripts_root)
+            } else {
+                safe_join(&scripts_root, &component.identifier)
+            };
+            match dest {
+                Some(dest) => self.extract_scripts(xar, &component.member("Scripts"), &dest)?,
+                None => self.warn(
+                    WarningKind::UnsafeName,
+                    format!(
+                        "Refusing to extract scripts for unsafe identifier {:?}",
+                        component.identifier
+                    ),
+                )?,
             }
+        }
 
-            let target_path = match safe_join(&self.output_dir, &name) {
-                Some(p) => p,
+        self.component_done(component);
+        Ok(payload.is_some())
+    }
+
+    /// The directory `component`'s payload extracts into: its
+    /// `component_dir_map` entry, its `per_component_dirs` directory or the
+    /// output directory. `None`, after a warning, when the mapped directory
+    /// is outside the output directory.
+    fn payload_root(
+        &mut self,
+        component: &ComponentInfo,
+    ) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        match self.options.component_dir_map.get(&component.identifier) {
+            Some(dir) => match safe_join(&self.output_dir, dir) {
+                Some(root) => {
+                    debug!("Routing {} to {}", component.identifier, dir.display());
+                    Ok(Some(root))
+                }
                 None => {
-                    warn!(
-                        "Refusing to extract entry {name:?}: resolves outside {}",
-                        self.output_dir.display()
+                    let message = format!(
+                        "Refusing to extract component {}: {} is outside the output directory",
+                        component.identifier,
+                        dir.display()
                     );
-                    continue;
+                    self.warn(WarningKind::UnsafeName, message)?;
+                    Ok(None)
                 }
-            };
-            if let Some(parent) = target_path.parent() {
-                fs::create_dir_all(parent)?;
+            },
+            None if self.options.per_component_dirs => {
+                let dir = self.component_dir(&component.identifier);
+                Ok(Some(self.output_dir.join(dir)))
             }
+            None => Ok(Some(self.output_dir.clone())),
+        }
+    }
 
-            match FileType::from_mode(mode) {
-                FileType::Directory => {
-                    fs::create_dir_all(&target_path)?;
-                }
-                FileType::Regular => {
-                    drop(header);
-                    let mut outfile = create_file_with_mode(&target_path, mode)?;
-                    let mut buf = vec![0u8; 8192];
-                    let mut remaining = file_size;
-                    while remaining > 0 {
-                        let to_read = remaining.min(buf.len() as u64) as usize;
-                        match cpio_reader.read(&mut buf[..to_read]) {
-                            Ok(0) => break,
-                            Ok(n) => {
-                                outfile.write_all(&buf[..n])?;
-                                remaining -= n as u64;
-                                total_bytes += n as u64;
-                            }
-                            Err(e) => {
-                                error!("Error reading cpio entry {name}: {e}");
-                                break;
-                            }
-                        }
-                    }
-                    file_count += 1;
-                }
-                FileType::Symlink => {
-                    // The link target is stored as the entry body.
-                    drop(header);
-                    let mut target = vec![0u8; file_size as usize];
-                    cpio_reader.read_exact(&mut target)?;
-                    let target_str = String::from_utf8(target)
-                        .map_err(|e| format!("invalid utf-8 symlink target for {name}: {e}"))?;
-                    create_symlink(&target_str, &target_path)?;
-                    file_count += 1;
+    /// Record `component` as done in the resume cursor.
+    fn component_done(&mut self, component: &ComponentInfo) {
+        let cursor = self.stats.cursor.get_or_insert_with(Default::default);
+        cursor.done.push(component.identifier.clone());
+        if let Some(callback) = &mut self.cursor_callback {
+            callback(cursor);
+        }
+    }
+
+    /// Record the first `entry` entries of the cpio being extracted as done
+    /// in the resume cursor.
+    fn advance_cursor(&mut self, entry: u64) {
+        if let Some(cursor) = &mut self.stats.cursor {
+            cursor.entry = entry;
+            if let Some(callback) = &mut self.cursor_callback {
+                callback(cursor);
+            }
+        }
+    }
+
+    /// Pick the directory to extract into and check `require_empty_output`
+    /// against it, the first time this session extracts to the output
+    /// directory; later extractions, by this extractor or the component
+    /// handles it handed out, reuse it and write next to what the earlier
+    /// ones did.
+    fn resolve_output_dir(&mut self) -> Result<(), Box<dyn Error>> {
+        let output_dirs = Arc::clone(&self.output_dirs);
+        let mut output_dirs = output_dirs.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(resolved) = output_dirs.get(&self.output_dir) {
+            self.output_dir = resolved.clone();
+            self.stats.output_dir = resolved.clone();
+            return Ok(());
+        }
+        let requested = self.output_dir.clone();
+        self.pick_output_dir()?;
+        self.check_empty_output()?;
+        output_dirs.insert(requested, self.output_dir.clone());
+        output_dirs.insert(self.output_dir.clone(), self.output_dir.clone());
+        Ok(())
+    }
+
+    /// With `avoid_clobber_dir`, move the output directory to the first
+    /// free `<dir>-2`, `<dir>-3`, ... when it already exists. Each candidate
+    /// is claimed by creating it, so concurrent runs never pick the same
+    /// one. The directory extracted into is recorded in the stats either
+    /// way.
+    fn pick_output_dir(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.options.avoid_clobber_dir && self.writer_factory.is_none() {
+            if let Some(parent) = self.output_dir.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // `components` drops any trailing slash, which would otherwise
+            // turn `out/` into `out/-2`.
+            let base = self
+                .output_dir
+                .components()
+                .as_path()
+                .as_os_str()
+                .to_owned();
+            let mut suffix = 1;
+            let dir = loop {
+                let mut candidate = base.clone();
+                if suffix > 1 {
+                    candidate.push(format!("-{suffix}"));
                 }
-                _ => {
-                    debug!("Skipping {:?} entry: {}", FileType::from_mode(mode), name);
+                let candidate = PathBuf::from(candidate);
+                match fs::create_dir(&candidate) {
+                    Ok(()) => break candidate,
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => suffix += 1,
+                    Err(e) => return Err(e.into()),
                 }
+            };
+            if suffix > 1 {
+                info!(
+                    "{} already exists, extracting into {} instead",
+                    self.output_dir.display(),
+                    dir.display()
+                );
+                self.output_dir = dir;
             }
         }
+        self.stats.output_dir = self.output_dir.clone();
+        Ok(())
+    }
 
-        debug!("Extracted {file_count} files, {total_bytes} bytes from cpio");
+    /// Refuse to extract into a populated output directory when
+    /// `require_empty_output` is set. A temp directory placed inside it
+    /// does not count.
+    fn check_empty_output(&self) -> Result<(), Box<dyn Error>> {
+        if !self.options.require_empty_output || self.writer_factory.is_some() {
+            return Ok(());
+        }
+        let entries = match fs::read_dir(&self.output_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if self.temp_dir.as_deref() != Some(path.as_path()) {
+                return Err(format!(
+                    "Output directory {} is not empty (it contains {})",
+                    self.output_dir.display(),
+                    path.display()
+                )
+                .into());
+            }
+        }
         Ok(())
     }
-}
 
-/// Create (or truncate) a regular file carrying the permission bits from a
-/// cpio header. On Unix, only the low 12 bits (`& 0o7777`) are used; the
-/// type-of-file nibble is applied via the create call itself. On non-Unix
-/// hosts we fall back to the default create mode and drop the permission
-/// bits on the floor -- there is no meaningful cross-platform mapping.
-fn create_file_with_mode(path: &Path, mode: u32) -> std::io::Result<File> {
-    let mut options = OpenOptions::new();
-    options.write(true).create(true).truncate(true);
-    #[cfg(unix)]
-    {
-        options.mode(mode & 0o7777);
+    /// Read `component`'s BOM as the layout its payload is checked against.
+    fn load_bom_layout<S: MemberSource>(
+        &mut self,
+        xar: &mut S,
+        component: &ComponentInfo,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(data) = xar.member_data(&component.member("Bom"))? else {
+            return self.warn(
+                WarningKind::BomMismatch,
+                format!(
+                    "Component {} has no Bom; extracting its payload as is",
+                    component.identifier
+                ),
+            );
+        };
+        let entries = bom::read_paths(&data)
+            .map_err(|e| format!("Reading the Bom of {}: {e}", component.identifier))?;
+        self.bom_layout = Some(
+            entries
+                .into_iter()
+                .filter(|entry| !entry.key().is_empty())
+                .map(|entry| (entry.key().to_string(), entry))
+                .collect(),
+        );
+        Ok(())
     }
-    #[cfg(not(unix))]
-    {
-        let _ = mode;
+
+    /// Create the directories only the BOM declared and report its other
+    /// entries the payload lacked.
+    fn finish_bom_layout(&mut self, root: &Path) -> Result<(), Box<dyn Error>> {
+        let Some(layout) = self.bom_layout.take() else {
+            return Ok(());
+        };
+        let mut missing: Vec<_> = layout.into_values().collect();
+        missing.sort_by(|a, b| a.path.cmp(&b.path));
+        for entry in missing {
+            match safe_join(root, entry.key()) {
+                Some(dir) if entry.kind == FileType::Directory => {
+                    debug!("Creating {} from the Bom", entry.path);
+                    if self.writer_factory.is_none() {
+                        fs::create_dir_all(&dir)?;
+                        self.deferred_metadata.push((dir, entry.mode as u32, None));
+                    }
+                }
+                _ => self.warn(
+                    WarningKind::BomMismatch,
+                    format!("{} is in the Bom but not in the payload", entry.path),
+                )?,
+            }
+        }
+        Ok(())
     }
-    options.open(path)
-}
 
-/// Join a cpio entry name onto the output directory, refusing any path
-/// component that would escape the root (`..`, or an absolute path, or a
-/// Windows drive prefix). Returns `None` when the entry is unsafe. Normal
-/// Apple Payload entries are `./`-rooted and always resolve inside.
-fn safe_join(root: &Path, entry: &str) -> Option<PathBuf> {
-    let candidate = Path::new(entry);
-    if candidate.is_absolute() {
-        return None;
+    /// The `per_component_dirs` directory name of `identifier`, allocated
+    /// (and recorded in the stats) on first use.
+    fn component_dir(&mut self, identifier: &str) -> String {
+        if let Some(dir) = self.stats.component_dirs.get(identifier) {
+            return dir.clone();
+        }
+        let base = sanitize_identifier(identifier);
+        let mut dir = base.clone();
+        let mut n = 1;
+        while self.stats.component_dirs.values().any(|used| *used == dir) {
+            n += 1;
+            dir = format!("{base}-{n}");
+        }
+        if dir != identifier {
+            info!("Extracting component {identifier:?} into {dir:?}");
+        }
+        self.stats
+            .component_dirs
+            .insert(identifier.to_string(), dir.clone());
+        dir
     }
 
-    let mut out = root.to_path_buf();
-    for component in candidate.components() {
-        use std::path::Component;
-        match component {
-            Component::Normal(part) => out.push(part),
-            Component::CurDir => {}
-            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+    /// Apply the modes and mtimes held back during the content pass,
+    /// deepest paths first, so a directory is only locked down once
+    /// everything below it is written (and its mtime no longer disturbed).
+    fn apply_deferred_metadata(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut deferred = std::mem::take(&mut self.deferred_metadata);
+        deferred.sort_by_key(|(path, _, _)| std::cmp::Reverse(path.components().count()));
+        for (path, mode, mtime) in &deferred {
+            if let Some(mtime) = mtime {
+                // Unlike `File::set_modified`, this works on directories on
+                // Windows too.
+                filetime::set_file_mtime(path, FileTime::from_unix_time(*mtime as i64, 0))?;
+            }
+            set_mode(path, *mode)?;
         }
+        debug!("Applied deferred metadata to {} paths", deferred.len());
+        Ok(())
+    }
+
+    /// Write the reports requested in the options, spelling paths as
+    /// `report_paths` says.
+    fn write_reports(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.options.pair_apple_double {
+            for message in apple_double::pair(&mut self.manifest, &self.output_dir) {
+                self.warn(WarningKind::UnparsableFile, message)?;
+            }
+        }
+        if let Some(dest) = &self.options.manifest {
+            report::write_manifest(
+                dest,
+                &self.manifest,
+                &self.output_dir,
+                self.options.report_paths,
+            )?;
+            debug!(
+                "Wrote {} manifest entries to {}",
+                self.manifest.len(),
+                dest.display()
+            );
+        }
+        if let Some(dest) = &self.options.record_decisions {
+            decisions::write(dest, &self.decisions)?;
+        }
+        if let Some(recorded) = &self.options.replay_decisions {
+            decisions::replay(recorded, &self.decisions)?;
+            debug!(
+                "All {} decisions match {}",
+                self.decisions.len(),
+                recorded.display()
+            );
+        }
+        if let Some(dest) = &self.options.lsbom {
+            report::write_lsbom(dest, &self.manifest, &self.output_dir)?;
+            debug!("Wrote lsbom listing to {}", dest.display());
+        }
+        if self.options.symlinks_sidecar && !self.sidecar_links.is_empty() {
+            let dest = self.output_dir.join(SYMLINKS_SIDECAR);
+            fs::write(&dest, serde_json::to_vec_pretty(&self.sidecar_links)?)?;
+            debug!(
+                "Recorded {} symlinks in {}",
+                self.sidecar_links.len(),
+                dest.display()
+            );
+        }
+        if self.options.content_addressed {
+            let dest = self.output_dir.join(CAS_MANIFEST);
+            fs::write(&dest, serde_json::to_vec_pretty(&self.cas_entries)?)?;
+            debug!(
+                "Recorded {} paths in {}",
+                self.cas_entries.len(),
+                dest.display()
+            );
+        }
+        if let Some(dest) = &self.options.size_index {
+            report::write_size_index(dest, &self.manifest, &self.output_dir)?;
+            debug!("Wrote directory sizes to {}", dest.display());
+        }
+        #[cfg(feature = "codesign")]
+        if let Some(dest) = &self.options.code_signatures {
+            for message in codesign::write_report(dest, &self.manifest, &self.output_dir)? {
+                self.warn(WarningKind::UnparsableFile, message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Report a suspicious but recoverable condition: logged with `warn!`
+    /// and collected in the stats, or returned as an error carrying the
+    /// same message when `warnings_as_errors` is set.
+    fn warn(&mut self, kind: WarningKind, message: String) -> Result<(), Box<dyn Error>> {
+        if self.options.warnings_as_errors {
+            return Err(format!("warning treated as error: {message}").into());
+        }
+        self.note_warning(kind, message);
+        Ok(())
+    }
+
+    /// Log and collect a warning that `warnings_as_errors` does not turn
+    /// into an error, because what it reports is already handled.
+    fn note_warning(&mut self, kind: WarningKind, message: String) {
+        warn!("{message}");
+        self.stats.warnings.push(Warning { kind, message });
+    }
+
+    /// Unpack the `Scripts` archive at `xar_path` (if the component has one)
+    /// into `dest`. The archive uses the same gzip-or-raw cpio framing as a
+    /// legacy Payload.
+    fn extract_scripts<S: MemberSource>(
+        &mut self,
+        xar: &mut S,
+        xar_path: &str,
+        dest: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        match xar.member_data(xar_path)? {
+            Some(data) => {
+                debug!("Extracting {xar_path} into {}", dest.display());
+                if self.writer_factory.is_none() {
+                    fs::create_dir_all(dest)?;
+                }
+                self.extract_payload_bytes(&data, dest)
+            }
+            None => {
+                debug!("No {xar_path} archive, skipping");
+                Ok(())
+            }
+        }
+    }
+
+    /// Decode a `Payload` (or `Scripts`) archive and extract the cpio inside
+    /// it into `root`.
+    fn extract_payload_bytes(&mut self, data: &[u8], root: &Path) -> Result<(), Box<dyn Error>> {
+        let cpio = decode_payload(data)?;
+        self.extract_cpio(&cpio, root)
+    }
+
+    /// The root-relative path under which an archive-style output (tarball,
+    /// database) records a payload entry, or `None` when the entry is
+    /// filtered out by size, unsafe (after a warning) or the root itself.
+    #[cfg(any(feature = "tar", feature = "rusqlite"))]
+    fn archive_path(
+        &mut self,
+        header: &dyn cpio_archive::CpioHeader,
+    ) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        let name = header.name();
+        let file_size = header.file_size();
+        if self.options.excludes(name, header.mode()) {
+            debug!("Skipping {name}: excluded");
+            return Ok(None);
+        }
+        if FileType::from_mode(header.mode()) == FileType::Regular
+            && !self.options.accepts_size(file_size)
+        {
+            debug!("Skipping {name}: {file_size} bytes is outside the size filter");
+            return Ok(None);
+        }
+
+        let join_name = if self.options.strip_leading_slash {
+            name.trim_start_matches('/')
+        } else {
+            name
+        };
+        match safe_join(Path::new(""), join_name) {
+            Some(path) if path.as_os_str().is_empty() => Ok(None),
+            Some(path) => Ok(Some(path)),
+            None => {
+                self.warn(
+                    WarningKind::UnsafeName,
+                    format!("Refusing to archive entry {name:?}: resolves outside the root"),
+                )?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Extract a decompressed cpio (ODC / portable-ASCII) byte stream into
+    /// `root` (normally `self.output_dir`).
+    fn extract_cpio(&mut self, data: &[u8], root: &Path) -> Result<(), Box<dyn Error>> {
+        // Whatever was made for an earlier payload may have been removed since.
+        self.created_dirs.clear();
+        let cursor = Cursor::new(data);
+        let mut cpio_reader = OdcReader::new(cursor);
+
+        let mut file_count: u64 = 0;
+        let mut total_bytes: u64 = 0;
+        let mut rate_limiter = self.options.rate_limit.map(throttle::RateLimiter::new);
+        let record = self.options.manifest.is_some()
+            || self.options.lsbom.is_some()
+            || self.options.size_index.is_some();
+        #[cfg(feature = "codesign")]
+        let record = record || self.options.code_signatures.is_some();
+
+        let tracked = self.cursor_component.clone();
+        let skip = match (&self.options.resume_from, &tracked) {
+            (Some(cursor), Some(component)) if cursor.component == *component => cursor.entry,
+            _ => 0,
+        };
+        if let Some(component) = tracked.clone() {
+            let cursor = self.stats.cursor.get_or_insert_with(Default::default);
+            cursor.component = component;
+            cursor.entry = 0;
+        }
+        let mut entry = 0;
+
+        while let Some(header) = cpio_reader.read_next()? {
+            if tracked.is_some() {
+                self.advance_cursor(entry);
+            }
+            entry += 1;
+            if entry <= skip {
+                self.stats.skipped += 1;
+                continue;
+            }
+            if let Some(inspector) = &self.header_inspector {
+                inspector(&CpioHeaderView::new(header.as_ref()));
+            }
+            let name = header.name().to_string();
+            let file_size = header.file_size();
+            let (uid, gid, mtime) = (header.uid(), header.gid(), header.mtime());
+            let mut mode = self.options.entry_mode(header.mode());
+
+            // Apple Payload cpios prefix every name with `./`; the `.` root
+            // entry is the only one we need to skip explicitly.
+            if name.is_empty() || name == "." {
+                self.stats.skipped += 1;
+                continue;
+            }
+            // `OdcReader` ends the iteration at the trailer itself, but it
+            // must never become a file should a reader ever surface it.
+            if name == CPIO_TRAILER {
+                break;
+            }
+            if self.options.excludes(&name, mode) {
+                debug!("Skipping {name}: excluded");
+                self.decide(&name, FileType::from_mode(mode), Action::SkipExcluded, None);
+                continue;
+            }
+
+            let mut reconciled = false;
+            if let Some(layout) = &mut self.bom_layout {
+                let declared = layout.remove(bom::bom_key(&name));
+                match declared {
+                    Some(entry) if entry.kind == FileType::from_mode(mode) => {
+                        let declared_mode = entry.mode as u32;
+                        if declared_mode != header.mode() {
+                            // Resolved by taking the Bom's mode: not an error.
+                            self.note_warning(
+                                WarningKind::BomMismatch,
+                                format!(
+                                    "{name}: payload mode {:o} differs from the Bom's {declared_mode:o}; using the Bom's",
+                                    header.mode()
+                                ),
+                            );
+                            mode = self.options.entry_mode(declared_mode);
+                            reconciled = true;
+                        }
+                    }
+                    declared => {
+                        let file_type = FileType::from_mode(mode);
+                        let reason = match declared {
+                            Some(entry) => format!("is a {:?} in the Bom", entry.kind),
+                            None => "is not in the Bom".to_string(),
+                        };
+                        self.decide(&name, file_type, Action::SkipNotInBom, None);
+                        self.warn(
+                            WarningKind::BomMismatch,
+                            format!("Skipping {file_type:?} {name}: it {reason}"),
+                        )?;
+                        continue;
+                    }
+                }
+            }
+
+            // Size filters only look at regular files; directories and
+            // symlinks are structure, not content.
+            let file_type = FileType::from_mode(mode);
+            if file_type == FileType::Regular && !self.options.accepts_size(file_size) {
+                debug!("Skipping {name}: {file_size} bytes is outside the size filter");
+                self.decide(&name, file_type, Action::SkipSize, None);
+                continue;
+            }
+
+            let join_name = if self.options.strip_leading_slash {
+                name.trim_start_matches('/')
+            } else {
+                name.as_str()
+            };
+            let sanitized = self.options.sanitize_name(join_name);
+            if let Cow::Owned(escaped) = &sanitized {
+                info!("Extracting {name:?} as {escaped:?}: control characters escaped");
+            }
+            let join_name = match self.options.cap_components(&sanitized)? {
+                Cow::Owned(capped) => {
+                    info!("Extracting {name:?} as {capped:?}: path component too long");
+                    Cow::Owned(capped)
+                }
+                Cow::Borrowed(_) => sanitized,
+            };
+            let original = match join_name {
+                Cow::Owned(_) => Some(name.clone()),
+                Cow::Borrowed(_) => None,
+            };
+            let routed;
+            let entry_root = match self.options.extension_action(&join_name) {
+                Some(EntryAction::Extract) | None => root,
+                Some(EntryAction::Skip) => {
+                    debug!("Skipping {name}: its extension is mapped to Skip");
+                    self.decide(&name, file_type, Action::SkipExtension, None);
+                    continue;
+                }
+                Some(EntryAction::Route(dir)) => match safe_join(&self.output_dir, dir) {
+                    Some(dir) => {
+                        routed = dir;
+                        &routed
+                    }
+                    None => {
+                        let message = format!(
+                            "Refusing to extract entry {name:?}: its extension is routed to {}, outside the output directory",
+                            dir.display()
+                        );
+                        self.decide(&name, file_type, Action::Refuse, None);
+                        self.warn(WarningKind::UnsafeName, message)?;
+                        continue;
+                    }
+                },
+            };
+            let target_path = match safe_join(entry_root, &*join_name) {
+                Some(p) => p,
+                None => {
+                    self.decide(&name, file_type, Action::Refuse, None);
+                    self.warn(
+                        WarningKind::UnsafeName,
+                        format!(
+                            "Refusing to extract entry {name:?}: resolves outside {}",
+                            root.display()
+                        ),
+                    )?;
+                    continue;
+                }
+            };
+            // A name with nothing left once normalised (`./`, `/` with
+            // `strip_leading_slash`, `./.`, ...) is the root itself, which
+            // already exists and must never be written over.
+            if target_path == entry_root {
+                debug!("Skipping {name:?}: names the extraction root itself");
+                self.stats.skipped += 1;
+                continue;
+            }
+            if file_type == FileType::Other {
+                debug!("Skipping {file_type:?} entry: {name}");
+                self.decide(&name, file_type, Action::SkipType, Some(&target_path));
+                continue;
+            }
+            let (target_path, original) = if self.options.gzip_files
+                && file_type == FileType::Regular
+                && self.writer_factory.is_none()
+                && !self.options.content_addressed
+            {
+                let mut compressed = target_path.into_os_string();
+                compressed.push(".gz");
+                (PathBuf::from(compressed), Some(name.clone()))
+            } else {
+                (target_path, original)
+            };
+            drop(header);
+
+            // Delta extraction needs the contents' CRC before deciding to
+            // write them, so such files are spooled (to disk past
+            // `STREAM_SPILL_THRESHOLD`) while their CRC is computed.
+            let mut spooled = None;
+            if file_type == FileType::Regular && self.options.delta_baseline.is_some() {
+                let mut spool = match &self.temp_dir {
+                    Some(dir) => SpooledTempFile::new_in(STREAM_SPILL_THRESHOLD, dir),
+                    None => SpooledTempFile::new(STREAM_SPILL_THRESHOLD),
+                };
+                let mut cksum = Cksum::new();
+                let mut contents = Read::take(&mut cpio_reader, file_size);
+                let mut buf = [0u8; 64 * 1024];
+                loop {
+                    let n = contents.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    cksum.update(&buf[..n]);
+                    spool.write_all(&buf[..n])?;
+                }
+                let current = (file_size, cksum.finish());
+                if self.baseline()?.get(bom::bom_key(&name)) == Some(&current) {
+                    debug!("Skipping {name}: unchanged from the baseline BOM");
+                    self.decide(&name, file_type, Action::SkipUnchanged, Some(&target_path));
+                    continue;
+                }
+                spool.rewind()?;
+                spooled = Some(spool);
+            }
+            let body: &mut dyn Read = match &mut spooled {
+                Some(spool) => spool,
+                None => &mut cpio_reader,
+            };
+
+            let written = match self.write_entry(
+                file_type,
+                root,
+                &target_path,
+                mode,
+                file_size,
+                body,
+                &mut rate_limiter,
+                record,
+            ) {
+                Ok(written) => written,
+                Err(e) if self.options.continue_on_file_error => {
+                    self.note_warning(
+                        WarningKind::FileFailed,
+                        format!("Failed to extract {name}: {e}"),
+                    );
+                    self.decide(&name, file_type, Action::Failed, Some(&target_path));
+                    self.stats.failed_files.push(FailedFile {
+                        path: target_path,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            self.decide(&name, file_type, Action::Extract, Some(&target_path));
+            if self.writer_factory.is_none() && !self.options.content_addressed {
+                if self.options.defer_metadata
+                    && matches!(file_type, FileType::Directory | FileType::Regular)
+                {
+                    self.deferred_metadata
+                        .push((target_path.clone(), mode, Some(mtime)));
+                } else if file_type == FileType::Directory
+                    && (self.options.force_mode.is_some() || reconciled)
+                {
+                    // Setting a read-only mode now would fail the entries below.
+                    self.deferred_metadata
+                        .push((target_path.clone(), mode, None));
+                }
+            }
+            if spooled.is_some() {
+                self.stats.delta.push(target_path.clone());
+            }
+            if file_type != FileType::Directory {
+                file_count += 1;
+                total_bytes += written.bytes;
+            }
+
+            if record {
+                self.manifest.push(ManifestEntry {
+                    path: target_path,
+                    kind: file_type.as_str(),
+                    size: file_size,
+                    mode,
+                    uid,
+                    gid,
+                    checksum: written.checksum,
+                    link_target: written.link_target,
+                    original,
+                    apple_double: None,
+                });
+            }
+        }
+
+        if tracked.is_some() {
+            self.advance_cursor(entry);
+        }
+        debug!("Extracted {file_count} files, {total_bytes} bytes from cpio");
+        if self.options.check_trailer && !cpio_has_trailer(data)? {
+            self.warn(
+                WarningKind::MissingTrailer,
+                format!(
+                "cpio archive extracted into {} has no {CPIO_TRAILER} entry; it may be truncated",
+                root.display()
+            ),
+            )?;
+        }
+        self.stats.files += file_count;
+        self.stats.bytes += total_bytes;
+        Ok(())
+    }
+
+    /// The regular files of the `delta_baseline` BOM, read on first use.
+    fn baseline(&mut self) -> Result<&Baseline, Box<dyn Error>> {
+        if self.baseline.is_none() {
+            let Some(path) = &self.options.delta_baseline else {
+                return Err("no delta_baseline set".into());
+            };
+            let entries = bom::read_paths(&fs::read(path)?)
+                .map_err(|e| format!("Reading baseline BOM {}: {e}", path.display()))?;
+            let files = entries
+                .iter()
+                .filter(|e| e.kind == FileType::Regular)
+                .map(|e| (e.key().to_string(), (e.size as u64, e.checksum)))
+                .collect();
+            self.baseline = Some(files);
+        }
+        Ok(self.baseline.get_or_insert_with(HashMap::new))
+    }
+
+    /// Append a component's decompressed Payload cpio to the
+    /// `payload_image` file, creating it on first use.
+    fn append_payload_image(&mut self, cpio: &[u8]) -> Result<(), Box<dyn Error>> {
+        let Some(dest) = &self.options.payload_image else {
+            return Ok(());
+        };
+        let image = match &mut self.payload_image {
+            Some(image) => image,
+            None => self.payload_image.insert(File::create(dest)?),
+        };
+        image.write_all(cpio)?;
+        debug!("Appended {} cpio bytes to {}", cpio.len(), dest.display());
+        Ok(())
+    }
+
+    /// Note what was decided for `entry`, when decisions are being recorded
+    /// or replayed.
+    fn decide(&mut self, entry: &str, file_type: FileType, action: Action, target: Option<&Path>) {
+        if !matches!(action, Action::Extract | Action::Failed) {
+            self.stats.skipped += 1;
+        }
+        if self.options.record_decisions.is_none() && self.options.replay_decisions.is_none() {
+            return;
+        }
+        self.decisions.push(Decision {
+            entry: entry.to_string(),
+            kind: file_type.as_str().to_string(),
+            action,
+            target: target.map(|t| PathStyle::Relative.render(&self.output_dir, t)),
+        });
+    }
+
+    /// Materialise one cpio entry at `target_path`, reading its body from
+    /// `body`. Any error here concerns this entry alone, which is what
+    /// `continue_on_file_error` relies on.
+    #[allow(clippy::too_many_arguments)]
+    fn write_entry(
+        &mut self,
+        file_type: FileType,
+        root: &Path,
+        target_path: &Path,
+        mode: u32,
+        file_size: u64,
+        body: &mut dyn Read,
+        rate_limiter: &mut Option<throttle::RateLimiter>,
+        record: bool,
+    ) -> Result<Written, Box<dyn Error>> {
+        if self.options.content_addressed && self.writer_factory.is_none() {
+            return self.store_content(file_type, target_path, mode, file_size, body, rate_limiter);
+        }
+        let factory = self.writer_factory.as_mut();
+        if factory.is_none() {
+            if let Some(parent) = target_path.parent() {
+                create_dir_cached(&mut self.created_dirs, parent)?;
+            }
+        }
+
+        let mut written = Written::default();
+        match file_type {
+            FileType::Directory => {
+                if factory.is_none() {
+                    create_dir_cached(&mut self.created_dirs, target_path)?;
+                }
+            }
+            FileType::Regular => {
+                let mut head = Vec::new();
+                let (thinned, mut thinned_body, mut rest);
+                let (body, file_size): (&mut dyn Read, u64) = match self.options.thin_arch {
+                    None => (body, file_size),
+                    Some(arch) => match macho::thin(body, file_size, arch, &mut head)? {
+                        Some(slice) => {
+                            thinned = slice;
+                            thinned_body = thinned.as_slice();
+                            (&mut thinned_body, thinned.len() as u64)
+                        }
+                        None => {
+                            rest = head.as_slice().chain(body);
+                            (&mut rest, file_size)
+                        }
+                    },
+                };
+                // Transformed before the output is opened, so a failing
+                // command leaves no empty file behind.
+                #[cfg(feature = "transform")]
+                let (transformed, mut transformed_body);
+                #[cfg(feature = "transform")]
+                let (body, file_size): (&mut dyn Read, u64) = match &self.options.transform {
+                    Some(command) if command.applies_to(target_path) => {
+                        transformed = command.run(body, file_size)?;
+                        transformed_body = transformed.as_slice();
+                        (&mut transformed_body, transformed.len() as u64)
+                    }
+                    _ => (body, file_size),
+                };
+                let mut staged = None;
+                // Closed at the end of the block, before being moved into place.
+                {
+                    let mut outfile: Box<dyn Write> = match factory {
+                        Some(factory) => factory(target_path, &file_type, mode)?,
+                        None if self.options.atomic_writes => {
+                            let dir = match &self.temp_dir {
+                                Some(dir) => dir.as_path(),
+                                None => target_path.parent().unwrap_or(Path::new(".")),
+                            };
+                            let temp = tempfile::Builder::new()
+                                .prefix(".pkg-extractor-")
+                                .tempfile_in(dir)?;
+                            let file = temp.reopen()?;
+                            staged = Some(temp);
+                            Box::new(file)
+                        }
+                        None => {
+                            // Deferred modes are applied once the file is written,
+                            // so it must stay writable until then.
+                            let create_mode = if self.options.defer_metadata {
+                                mode | 0o200
+                            } else {
+                                mode
+                            };
+                            let file = create_file_with_mode(target_path, create_mode)?;
+                            if self.options.force_mode.is_some() && !self.options.defer_metadata {
+                                // Not subject to the umask, unlike the creation mode.
+                                set_mode(target_path, mode)?;
+                            }
+                            Box::new(file)
+                        }
+                    };
+                    let mut encoder = None;
+                    let out: &mut dyn Write =
+                        if self.options.gzip_files && self.writer_factory.is_none() {
+                            encoder.insert(libflate::gzip::Encoder::new(outfile)?)
+                        } else {
+                            &mut outfile
+                        };
+                    let mut cksum = record.then(report::Cksum::new);
+                    let mut buf = vec![0u8; 8192];
+                    let mut remaining = file_size;
+                    while remaining > 0 {
+                        let to_read = remaining.min(buf.len() as u64) as usize;
+                        match body.read(&mut buf[..to_read]) {
+                            Ok(0) => break,
+                            Ok(n) => {
+                                out.write_all(&buf[..n])?;
+                                if let Some(cksum) = &mut cksum {
+                                    cksum.update(&buf[..n]);
+                                }
+                                remaining -= n as u64;
+                                written.bytes += n as u64;
+                                if let Some(limiter) = rate_limiter {
+                                    limiter.consume(n);
+                                }
+                            }
+                            Err(e) => {
+                                error!("Error reading cpio entry {}: {e}", target_path.display());
+                                break;
+                            }
+                        }
+                    }
+                    out.flush()?;
+                    if let Some(encoder) = encoder {
+                        encoder.finish().into_result()?.flush()?;
+                    }
+                    written.checksum = cksum.map(report::Cksum::finish);
+                }
+                if let Some(temp) = staged {
+                    if let Err(e) = temp.persist(target_path) {
+                        // Renaming fails across filesystems.
+                        debug!("Copying {} into place: {}", target_path.display(), e.error);
+                        fs::copy(e.file.path(), target_path)?;
+                    }
+                    let mode = if self.options.defer_metadata {
+                        mode | 0o200
+                    } else {
+                        mode
+                    };
+                    set_mode(target_path, mode)?;
+                }
+            }
+            FileType::Symlink => {
+                // The link target is stored as the entry body.
+                let mut target = vec![0u8; file_size as usize];
+                body.read_exact(&mut target)?;
+                if record {
+                    let mut cksum = report::Cksum::new();
+                    cksum.update(&target);
+                    written.checksum = Some(cksum.finish());
+                }
+                // Targets need not be UTF-8: the raw bytes are what the link
+                // points to, reports get `display_bytes`' rendering.
+                let target = match String::from_utf8(target) {
+                    Ok(target_str)
+                        if self.options.relative_symlinks && target_str.starts_with('/') =>
+                    {
+                        // A routed entry lives outside `root`: place both it
+                        // and `root` relative to the output directory.
+                        let base = if target_path.starts_with(root) {
+                            root
+                        } else {
+                            &self.output_dir
+                        };
+                        match (target_path.strip_prefix(base), root.strip_prefix(base)) {
+                            (Ok(link), Ok(payload_root)) => {
+                                let relative =
+                                    relative_link_target(link, payload_root, &target_str);
+                                debug!(
+                                    "Rewriting symlink {} -> {target_str} as {relative}",
+                                    link.display()
+                                );
+                                relative.into_bytes()
+                            }
+                            _ => target_str.into_bytes(),
+                        }
+                    }
+                    Ok(target_str) => target_str.into_bytes(),
+                    Err(e) => e.into_bytes(),
+                };
+                let shown = self.options.display_bytes(&target).into_owned();
+                match factory {
+                    Some(factory) => {
+                        let mut out = factory(target_path, &file_type, mode)?;
+                        out.write_all(&target)?;
+                        out.flush()?;
+                    }
+                    None if self.options.symlinks_sidecar => {
+                        let link = PathStyle::Relative.render(&self.output_dir, target_path);
+                        debug!("Recording symlink {link} -> {shown} in the sidecar");
+                        self.sidecar_links
+                            .insert(link.replace('\\', "/"), shown.clone());
+                    }
+                    None => {
+                        #[cfg(unix)]
+                        let target = {
+                            use std::os::unix::ffi::OsStrExt;
+                            std::ffi::OsStr::from_bytes(&target)
+                        };
+                        #[cfg(not(unix))]
+                        let target = &*String::from_utf8_lossy(&target);
+                        if self.options.atomic_writes {
+                            replace_with_symlink(target, target_path)?
+                        } else {
+                            create_symlink(target, target_path)?
+                        }
+                    }
+                }
+                written.link_target = Some(shown);
+            }
+            FileType::Other => {}
+        }
+        Ok(written)
     }
-    Some(out)
 }
 
-/// Create `link` as a symlink pointing at `target`. If `link` already exists
-/// (e.g. a pre-existing regular file in the destination), it is removed first
-/// so the symlink creation succeeds.
-fn create_symlink(target: &str, link: &Path) -> std::io::Result<()> {
-    if link.symlink_metadata().is_ok() {
-        fs::remove_file(link)?;
+/// What [`PkgExtractor::write_entry`] put on disk.
+#[derive(Default)]
+struct Written {
+    bytes: u64,
+    checksum: Option<u32>,
+    link_target: Option<String>,
+}
+
+/// Extract an already decompressed cpio (odc) archive, as found inside a
+/// component's `Payload`, into `output_dir`: the entry-writing half of
+/// [`PkgExtractor::extract`], without the xar and component layers. The
+/// stream is read to the end up front. `opts` apply as for a package, bar
+/// the ones about components and scripts; `avoid_clobber_dir` and
+/// `require_empty_output` included, so the directory written to is the
+/// one in the returned stats.
+pub fn extract_cpio_stream<R2: Read>(
+    mut stream: R2,
+    output_dir: &Path,
+    opts: &ExtractOptions,
+) -> Result<ExtractionStats, Box<dyn Error>> {
+    let mut cpio = Vec::new();
+    stream.read_to_end(&mut cpio)?;
+    let mut extractor = PkgExtractor::new(Cursor::new(Vec::new()), Some(output_dir.into()))
+        .with_options(opts.clone());
+    extractor.resolve_output_dir()?;
+    let root = extractor.output_dir.clone();
+    fs::create_dir_all(&root)?;
+    extractor.extract_cpio(&cpio, &root)?;
+    extractor.apply_deferred_metadata()?;
+    extractor.write_reports()?;
+    Ok(extractor.stats)
+}
+
+/// Dispatch on the magic bytes of a `Payload` file and return the cpio
+/// inside: `pbzx`-wrapped xz (modern pkgs), gzip-compressed cpio
+/// (pre-Mavericks legacy), or raw cpio (rare but permitted).
+fn decode_payload(data: &[u8]) -> Result<Cow<'_, [u8]>, Box<dyn Error>> {
+    if data.len() >= 4 && &data[0..4] == b"pbzx" {
+        let mut reader = pbzx::PbzxReader::new(Cursor::new(data))?;
+        let mut decompressed = Vec::new();
+        reader.decompress_to(&mut decompressed)?;
+        debug!("pbzx decompressed {} bytes", decompressed.len());
+        Ok(Cow::Owned(decompressed))
+    } else if data.len() >= 3 && data[0..3] == GZIP_MAGIC {
+        let mut decoder = libflate::gzip::Decoder::new(Cursor::new(data))?;
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed)?;
+        debug!("gunzipped {} bytes", decompressed.len());
+        Ok(Cow::Owned(decompressed))
+    } else {
+        debug!("assuming raw cpio ({} bytes)", data.len());
+        Ok(Cow::Borrowed(data))
     }
-    #[cfg(unix)]
-    {
-        std::os::unix::fs::symlink(target, link)
+}
+
+/// Like [`decode_payload`], but decoding as the returned reader is read
+/// rather than all up front.
+fn payload_reader(data: &[u8]) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+    if data.starts_with(b"pbzx") {
+        Ok(Box::new(pbzx::PbzxReader::new(data)?))
+    } else if data.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(libflate::gzip::Decoder::new(data)?))
+    } else {
+        Ok(Box::new(data))
     }
-    #[cfg(windows)]
-    {
-        // cpio doesn't tell us whether the target is a file or a directory.
-        // Default to file-symlink, which is what Apple payloads ship.
-        std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Call `f` for every entry of every component's Payload, in archive order,
+/// with the entry's cpio header and a reader over its body. The `.` root
+/// entry is skipped. This is the read-only counterpart of extraction that
+/// alternative outputs and inspection build on.
+fn walk_payloads<T: Read + Seek + Sized + Debug>(
+    xar: &mut XarReader<T>,
+    components: &[ComponentInfo],
+    f: impl FnMut(
+        &ComponentInfo,
+        &dyn cpio_archive::CpioHeader,
+        &mut dyn Read,
+    ) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    walk_archives(xar, components, "Payload", f)
+}
+
+/// [`walk_payloads`] over another cpio member of each component, such as
+/// `Scripts`.
+fn walk_archives<T: Read + Seek + Sized + Debug>(
+    xar: &mut XarReader<T>,
+    components: &[ComponentInfo],
+    member: &str,
+    mut f: impl FnMut(
+        &ComponentInfo,
+        &dyn cpio_archive::CpioHeader,
+        &mut dyn Read,
+    ) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    for component in components {
+        let Some(data) = xar.get_file_data_from_path(&component.member(member))? else {
+            debug!("Component {:?} has no {member}", component.path);
+            continue;
+        };
+        let cpio = decode_payload(&data)?;
+        let mut cpio_reader = OdcReader::new(Cursor::new(cpio.as_ref()));
+        while let Some(header) = cpio_reader.read_next()? {
+            if header.name().is_empty() || header.name() == "." {
+                continue;
+            }
+            if header.name() == CPIO_TRAILER {
+                break;
+            }
+            f(component, header.as_ref(), &mut cpio_reader)?;
+        }
     }
-    #[cfg(not(any(unix, windows)))]
-    {
-        let _ = (target, link);
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
-            "symlinks not supported on this platform",
-        ))
+    Ok(())
+}
+
+/// Whether `err` (or anything in its source chain) is an I/O error worth
+/// retrying: a timeout, an interruption or a dropped connection.
+fn is_transient(err: &(dyn Error + 'static)) -> bool {
+    use std::io::ErrorKind;
+    let mut current = Some(err);
+    while let Some(e) = current {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                ErrorKind::Interrupted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+            );
+        }
+        current = e.source();
+    }
+    false
+}
+
+/// Byte range of a cpio entry body within its decoded archive.
+type BodyRange = std::ops::Range<usize>;
+
+/// Name of the entry that ends every well-formed cpio archive.
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+/// Read the ODC entry at `cursor` and leave the cursor after its body.
+/// Returns `None` at the end of the data.
+fn next_odc_entry(
+    cursor: &mut Cursor<&[u8]>,
+) -> Result<Option<(OdcHeader, BodyRange)>, Box<dyn Error>> {
+    let mut magic = [0u8; 6];
+    if cursor.read_exact(&mut magic).is_err() {
+        return Ok(None);
+    }
+    if &magic != b"070707" {
+        return Err("bad cpio magic".into());
     }
+    let header = OdcHeader::from_reader(cursor)?;
+    let start = cursor.position() as usize;
+    let end = usize::try_from(header.file_size)
+        .ok()
+        .and_then(|size| start.checked_add(size))
+        .filter(|&end| end <= cursor.get_ref().len())
+        .ok_or_else(|| format!("cpio entry {:?} is truncated", header.name))?;
+    cursor.set_position(end as u64);
+    Ok(Some((header, start..end)))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
+/// Whether a decoded cpio archive ends with its `TRAILER!!!` entry, i.e.
+/// wasn't cut short. Only headers are parsed; bodies are skipped.
+fn cpio_has_trailer(cpio: &[u8]) -> Result<bool, Box<dyn Error>> {
+    let mut cursor = Cursor::new(cpio);
+    while let Some((header, _)) = next_odc_entry(&mut cursor)? {
+        if header.name == CPIO_TRAILER {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Create (or truncate) a regular file carrying the permission bits from a
+/// cpio header. On Unix, only the low 12 bits (`& 0o7777`) are used; the
+/// type-of-file nibble is applied via the create call itself. On non-Unix
+/// hosts we fall back to the default create mode and drop the permission
+/// bits on the floor -- there is no meaningful cross-platform mapping.
+fn create_file_with_mode(path: &Path, mode: u32) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        options.mode(mode & 0o7777);
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+    }
+    options.open(path)
+}
+
+/// Set the permission bits of an existing file or directory to those of
+/// `mode`. A no-op off Unix.
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Join a cpio entry name onto the output directory, refusing any path
+/// component that would escape the root (`..`, or an absolute path, or a
+/// Windows drive prefix). Returns `None` when the entry is unsafe. Normal
+/// Apple Payload entries are `./`-rooted and always resolve inside.
+fn safe_join(root: &Path, entry: impl AsRef<Path>) -> Option<PathBuf> {
+    let candidate = entry.as_ref();
+    if candidate.is_absolute() {
+        return None;
+    }
+
+    let mut out = root.to_path_buf();
+    for component in candidate.components() {
+        use std::path::Component;
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+/// `identifier` as a single path segment safe on every platform: path
+/// separators, `:` and the other characters Windows rejects, and control
+/// characters become `_`, as does a name made only of dots.
+fn sanitize_identifier(identifier: &str) -> String {
+    let name: String = identifier
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '<' | '>' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if name.chars().all(|c| c == '.') {
+        "_".repeat(name.len().max(1))
+    } else {
+        name
+    }
+}
+
+/// Express the absolute symlink `target`, which names a path under the
+/// payload root `root`, relative to the directory of `link`; `link` and
+/// `root` are both relative to the same base directory. With `root` empty,
+/// a link at `a/b/link` to `/a/c/file` becomes `../c/file`.
+fn relative_link_target(link: &Path, root: &Path, target: &str) -> String {
+    use std::path::Component;
+    let normal = |path: &Path| -> Vec<String> {
+        path.components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect()
+    };
+    let from = normal(link.parent().unwrap_or(Path::new("")));
+    let mut to = normal(root);
+    // `..` never climbs above the payload root, as `/..` is `/`.
+    let floor = to.len();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if to.len() > floor {
+                    to.pop();
+                }
+            }
+            part => to.push(part.to_string()),
+        }
+    }
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend(to[common..].iter().map(String::as_str));
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// `fs::create_dir_all(dir)`, unless `created` says it was already made.
+fn create_dir_cached(created: &mut HashSet<PathBuf>, dir: &Path) -> std::io::Result<()> {
+    if !created.contains(dir) {
+        fs::create_dir_all(dir)?;
+        created.insert(dir.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Create `link` as a symlink pointing at `target`. If `link` already exists
+/// (e.g. a pre-existing regular file in the destination), it is removed first
+/// so the symlink creation succeeds.
+fn create_symlink(target: impl AsRef<Path>, link: &Path) -> std::io::Result<()> {
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(link)?;
+    }
+    make_symlink(target, link)
+}
+
+/// Like [`create_symlink`], but without a moment where nothing is at
+/// `link`: the symlink is made under a temporary name next to it, then
+/// renamed over whatever file or symlink was there.
+fn replace_with_symlink(target: impl AsRef<Path>, link: &Path) -> std::io::Result<()> {
+    let dir = link.parent().unwrap_or(Path::new("."));
+    let temp = tempfile::Builder::new()
+        .prefix(".pkg-extractor-")
+        .make_in(dir, |path| make_symlink(&target, path))?;
+    temp.persist(link).map_err(|e| e.error)?;
+    Ok(())
+}
+
+fn make_symlink(target: impl AsRef<Path>, link: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    {
+        // cpio doesn't tell us whether the target is a file or a directory.
+        // Default to file-symlink, which is what Apple payloads ship.
+        std::os::windows::fs::symlink_file(target, link)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (target.as_ref(), link);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "symlinks not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use std::io::Cursor;
+
+    // ---- `FileType::from_mode` ----
+
+    #[test]
+    fn file_type_from_mode_regular() {
+        assert_eq!(FileType::from_mode(0o100644), FileType::Regular);
+        assert_eq!(FileType::from_mode(0o100755), FileType::Regular);
+    }
+
+    #[test]
+    fn file_type_from_mode_directory() {
+        assert_eq!(FileType::from_mode(0o040755), FileType::Directory);
+    }
+
+    #[test]
+    fn file_type_from_mode_symlink() {
+        assert_eq!(FileType::from_mode(0o120777), FileType::Symlink);
+    }
+
+    #[test]
+    fn file_type_from_mode_other() {
+        // Char device, block device, FIFO, socket -- none of these four
+        // should be mistaken for a regular or symlink.
+        assert_eq!(FileType::from_mode(0o020644), FileType::Other);
+        assert_eq!(FileType::from_mode(0o060644), FileType::Other);
+        assert_eq!(FileType::from_mode(0o010644), FileType::Other);
+        assert_eq!(FileType::from_mode(0o140644), FileType::Other);
+    }
+
+    // ---- `safe_join` ----
+
+    #[test]
+    fn safe_join_accepts_relative_and_curdir_paths() {
+        let root = Path::new("/out");
+        assert_eq!(
+            safe_join(root, "foo").as_deref(),
+            Some(Path::new("/out/foo"))
+        );
+        assert_eq!(
+            safe_join(root, "a/b/c").as_deref(),
+            Some(Path::new("/out/a/b/c"))
+        );
+        assert_eq!(
+            safe_join(root, "./foo").as_deref(),
+            Some(Path::new("/out/foo"))
+        );
+        assert_eq!(
+            safe_join(root, "./a/./b").as_deref(),
+            Some(Path::new("/out/a/b"))
+        );
+    }
+
+    #[test]
+    fn safe_join_rejects_parent_dir_anywhere() {
+        let root = Path::new("/out");
+        assert_eq!(safe_join(root, ".."), None);
+        assert_eq!(safe_join(root, "../etc/passwd"), None);
+        assert_eq!(safe_join(root, "a/../../b"), None);
+        assert_eq!(safe_join(root, "a/b/.."), None);
+    }
+
+    #[test]
+    fn safe_join_rejects_absolute_paths() {
+        let root = Path::new("/out");
+        assert_eq!(safe_join(root, "/etc/passwd"), None);
+    }
+
+    // ---- End-to-end `extract_cpio` round-trip ----
+
+    fn test_extractor(out_dir: &Path) -> PkgExtractor<Cursor<Vec<u8>>> {
+        PkgExtractor::new(Cursor::new(Vec::<u8>::new()), Some(out_dir.to_path_buf()))
+    }
+
+    #[test]
+    fn extract_cpio_materialises_files_dirs_and_empties() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mut cpio = Vec::new();
+        cpio.extend(odc_entry("./dir", 0o040755, b""));
+        cpio.extend(odc_entry("./dir/hello.txt", 0o100644, b"hello\n"));
+        cpio.extend(odc_entry("./empty", 0o100644, b""));
+        cpio.extend(trailer());
+
+        test_extractor(tmp.path())
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        let root = tmp.path();
+        assert!(root.join("dir").is_dir());
+        assert_eq!(fs::read(root.join("dir/hello.txt")).unwrap(), b"hello\n");
+        assert!(root.join("empty").is_file());
+        assert_eq!(fs::read(root.join("empty")).unwrap(), Vec::<u8>::new());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_cpio_preserves_unix_mode_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mut cpio = Vec::new();
+        cpio.extend(odc_entry("./script", 0o100755, b"#!/bin/sh\nexit 0\n"));
+        cpio.extend(odc_entry("./readonly", 0o100444, b"data"));
+        cpio.extend(trailer());
+
+        test_extractor(tmp.path())
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        let script_mode = fs::metadata(tmp.path().join("script"))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777;
+        assert_eq!(script_mode, 0o755);
+
+        let ro_mode = fs::metadata(tmp.path().join("readonly"))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777;
+        assert_eq!(ro_mode, 0o444);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn force_mode_overrides_payload_modes() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("./bin", 0o040777, b""),
+            ("./bin/suid", 0o104755, b"x"),
+            ("./bin/private", 0o100600, b"y"),
+            ("./bin/link", 0o120777, b"suid"),
+        ]);
+
+        let mut extractor = test_extractor(tmp.path()).with_options(ExtractOptions {
+            force_mode: Some(ForceMode {
+                file: 0o640,
+                dir: 0o750,
+            }),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, tmp.path()).unwrap();
+        extractor.apply_deferred_metadata().unwrap();
+
+        let mode = |p: &str| {
+            fs::metadata(tmp.path().join(p))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert_eq!(mode("bin"), 0o750);
+        assert_eq!(mode("bin/suid"), 0o640);
+        assert_eq!(mode("bin/private"), 0o640);
+        assert!(fs::symlink_metadata(tmp.path().join("bin/link"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_cpio_materialises_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mut cpio = Vec::new();
+        cpio.extend(odc_entry("./real.txt", 0o100644, b"target\n"));
+        cpio.extend(odc_entry("./link", 0o120777, b"real.txt"));
+        cpio.extend(trailer());
+
+        test_extractor(tmp.path())
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        let meta = fs::symlink_metadata(tmp.path().join("link")).unwrap();
+        assert!(meta.file_type().is_symlink());
+        assert_eq!(
+            fs::read_link(tmp.path().join("link")).unwrap(),
+            Path::new("real.txt")
+        );
+        // Reading through the symlink yields the target's bytes.
+        assert_eq!(fs::read(tmp.path().join("link")).unwrap(), b"target\n");
+    }
+
+    #[test]
+    fn extract_cpio_refuses_path_traversal_but_keeps_safe_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let mut cpio = Vec::new();
+        cpio.extend(odc_entry("./../../evil.txt", 0o100644, b"pwned"));
+        cpio.extend(odc_entry("./safe.txt", 0o100644, b"ok"));
+        cpio.extend(trailer());
+
+        test_extractor(tmp.path())
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        // Nothing was written above the output root.
+        let parent_evil = tmp
+            .path()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("evil.txt");
+        assert!(!parent_evil.exists());
+        // The legitimate entry beside it still landed.
+        assert_eq!(fs::read(tmp.path().join("safe.txt")).unwrap(), b"ok");
+    }
+
+    // ---- Scripts extraction ----
+
+    fn pkg_extractor(pkg: Vec<u8>, out_dir: &Path) -> PkgExtractor<Cursor<Vec<u8>>> {
+        PkgExtractor::new(Cursor::new(pkg), Some(out_dir.to_path_buf()))
+    }
+
+    #[test]
+    fn extract_scripts_namespaces_product_components_by_identifier() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut xar = XarBuilder::new().file("Distribution", "<installer-gui-script/>");
+        for (sub_pkg, identifier) in [("a.pkg", "com.example.a"), ("b.pkg", "com.example.b")] {
+            let payload = cpio(&[(&format!("./{identifier}.txt"), 0o100644, b"payload")]);
+            let script = format!("#!/bin/sh\necho {identifier}\n");
+            let scripts = gzip(&cpio(&[("./postinstall", 0o100755, script.as_bytes())]));
+            xar = xar
+                .file(&format!("{sub_pkg}/PackageInfo"), package_info(identifier))
+                .file(&format!("{sub_pkg}/Payload"), payload)
+                .file(&format!("{sub_pkg}/Scripts"), scripts);
+        }
+
+        pkg_extractor(xar.build(), tmp.path())
+            .with_options(ExtractOptions {
+                extract_scripts: true,
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+
+        let scripts = tmp.path().join("Scripts");
+        assert_eq!(
+            fs::read_to_string(scripts.join("com.example.a/postinstall")).unwrap(),
+            "#!/bin/sh\necho com.example.a\n"
+        );
+        assert_eq!(
+            fs::read_to_string(scripts.join("com.example.b/postinstall")).unwrap(),
+            "#!/bin/sh\necho com.example.b\n"
+        );
+        assert!(tmp.path().join("com.example.a.txt").is_file());
+        assert!(tmp.path().join("com.example.b.txt").is_file());
+    }
+
+    // ---- `from_stream` ----
+
+    /// A source that only implements `Read`, like a pipe.
+    struct ReadOnly<R>(R);
+
+    impl<R: Read> Read for ReadOnly<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn from_stream_extracts_read_only_source() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("Payload", cpio(&[("./hello.txt", 0o100644, b"hello\n")]))
+            .build();
+
+        PkgExtractor::from_stream(ReadOnly(pkg.as_slice()), Some(tmp.path().to_path_buf()))
+            .unwrap()
+            .extract()
+            .unwrap();
+
+        assert_eq!(fs::read(tmp.path().join("hello.txt")).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn atomic_writes_stage_files_in_temp_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let scratch = tmp.path().join("scratch");
+        let pkg = XarBuilder::new()
+            .file(
+                "Payload",
+                cpio(&[
+                    ("./dir", 0o040755, b""),
+                    ("./dir/hello.txt", 0o100644, b"hello\n"),
+                ]),
+            )
+            .build();
+        let options = ExtractOptions {
+            atomic_writes: true,
+            ..Default::default()
+        };
+
+        // Staging happens in the configured directory only: without it the
+        // extraction cannot create its temporary files.
+        let missing = PkgExtractor::new(Cursor::new(pkg.clone()), Some(out.clone()))
+            .with_options(options.clone())
+            .with_temp_dir(scratch.clone())
+            .extract();
+        assert!(missing.is_err());
+        assert!(!out.join("dir/hello.txt").exists());
+
+        fs::create_dir(&scratch).unwrap();
+        PkgExtractor::from_stream_in(ReadOnly(pkg.as_slice()), Some(out.clone()), scratch.clone())
+            .unwrap()
+            .with_options(options)
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(out.join("dir/hello.txt")).unwrap(), b"hello\n");
+        assert_eq!(fs::read_dir(&scratch).unwrap().count(), 0);
+        assert_eq!(fs::read_dir(out.join("dir")).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_replace_existing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("./real", 0o100644, b"real"),
+            ("./link", 0o120777, b"real"),
+            ("./relink", 0o120777, b"real"),
+        ]);
+
+        for atomic_writes in [false, true] {
+            let out = tmp.path().join(format!("out-{atomic_writes}"));
+            fs::create_dir_all(&out).unwrap();
+            fs::write(out.join("link"), b"stale file").unwrap();
+            std::os::unix::fs::symlink("elsewhere", out.join("relink")).unwrap();
+
+            test_extractor(&out)
+                .with_options(ExtractOptions {
+                    atomic_writes,
+                    ..Default::default()
+                })
+                .extract_cpio(&cpio, &out)
+                .unwrap();
+
+            for link in ["link", "relink"] {
+                assert_eq!(fs::read_link(out.join(link)).unwrap(), Path::new("real"));
+                assert_eq!(fs::read(out.join(link)).unwrap(), b"real");
+            }
+            assert_eq!(fs::read_dir(&out).unwrap().count(), 3);
+        }
+    }
+
+    // ---- Empty output requirement ----
+
+    #[test]
+    fn require_empty_output_refuses_populated_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let pkg = XarBuilder::new()
+            .file("Payload", cpio(&[("./hello.txt", 0o100644, b"hello\n")]))
+            .build();
+        let options = ExtractOptions {
+            require_empty_output: true,
+            atomic_writes: true,
+            ..Default::default()
+        };
+
+        // Only the scratch directory inside it: still empty.
+        let scratch = out.join(".scratch");
+        fs::create_dir_all(&scratch).unwrap();
+        pkg_extractor(pkg.clone(), &out)
+            .with_options(options.clone())
+            .with_temp_dir(scratch)
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(out.join("hello.txt")).unwrap(), b"hello\n");
+
+        fs::write(out.join("hello.txt"), b"mine").unwrap();
+        let err = pkg_extractor(pkg, &out)
+            .with_options(options)
+            .extract()
+            .unwrap_err();
+        assert!(err.to_string().contains("is not empty"), "{err}");
+        assert_eq!(fs::read(out.join("hello.txt")).unwrap(), b"mine");
+    }
+
+    #[test]
+    fn require_empty_output_is_checked_once_per_session() {
+        let payload = |name: &str| gzip(&cpio(&[(name, 0o100644, b"data")]));
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.b", "b.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", payload("./a.txt"))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", payload("./b.txt"))
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let options = ExtractOptions {
+            require_empty_output: true,
+            ..Default::default()
+        };
+
+        let mut extractor = pkg_extractor(pkg.clone(), &out).with_options(options.clone());
+        for component in extractor.components().unwrap() {
+            component.unwrap().extract().unwrap();
+        }
+        assert!(out.join("a.txt").is_file() && out.join("b.txt").is_file());
+
+        // Another session still finds it populated.
+        let err = pkg_extractor(pkg, &out)
+            .with_options(options)
+            .extract()
+            .unwrap_err();
+        assert!(err.to_string().contains("is not empty"), "{err}");
+    }
+
+    // ---- Clobber-avoiding output directory ----
+
+    #[test]
+    fn avoid_clobber_dir_extracts_again_into_a_suffixed_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("extracted_pkg");
+        let pkg = XarBuilder::new()
+            .file("Payload", cpio(&[("./hello.txt", 0o100644, b"hello\n")]))
+            .build();
+        let options = ExtractOptions {
+            avoid_clobber_dir: true,
+            ..Default::default()
+        };
+
+        let first = pkg_extractor(pkg.clone(), &out)
+            .with_options(options.clone())
+            .extract()
+            .unwrap();
+        assert_eq!(first.output_dir, out);
+        fs::write(out.join("hello.txt"), b"mine").unwrap();
+
+        let second = pkg_extractor(pkg.clone(), &out)
+            .with_options(options.clone())
+            .extract()
+            .unwrap();
+        assert_eq!(second.output_dir, tmp.path().join("extracted_pkg-2"));
+        assert_eq!(
+            fs::read(second.output_dir.join("hello.txt")).unwrap(),
+            b"hello\n"
+        );
+        assert_eq!(fs::read(out.join("hello.txt")).unwrap(), b"mine");
+
+        // Concurrent runs each claim a directory of their own.
+        let runs: Vec<_> = (0..4)
+            .map(|_| {
+                let (pkg, out, options) = (pkg.clone(), out.clone(), options.clone());
+                std::thread::spawn(move || {
+                    pkg_extractor(pkg, &out)
+                        .with_options(options)
+                        .extract()
+                        .unwrap()
+                        .output_dir
+                })
+            })
+            .collect();
+        let dirs: HashSet<_> = runs.into_iter().map(|run| run.join().unwrap()).collect();
+        assert_eq!(dirs.len(), 4);
+        assert!(!dirs.contains(&out) && !dirs.contains(&second.output_dir));
+    }
+
+    #[test]
+    fn avoid_clobber_dir_picks_one_directory_per_session() {
+        let payload = |name: &str| gzip(&cpio(&[(name, 0o100644, b"data")]));
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.b", "b.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", payload("./a.txt"))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", payload("./b.txt"))
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        fs::create_dir(&out).unwrap();
+        let options = ExtractOptions {
+            avoid_clobber_dir: true,
+            ..Default::default()
+        };
+
+        // Component handles share the directory the first one picked.
+        let mut extractor = pkg_extractor(pkg.clone(), &out).with_options(options.clone());
+        let dirs: Vec<_> = extractor
+            .components()
+            .unwrap()
+            .map(|component| component.unwrap().extract().unwrap().output_dir)
+            .collect();
+        let picked = tmp.path().join("out-2");
+        assert_eq!(dirs, [picked.clone(), picked.clone()]);
+        assert!(picked.join("a.txt").is_file() && picked.join("b.txt").is_file());
+
+        // So do a component extracted first and the rest of the package.
+        let mut extractor = pkg_extractor(pkg, &out).with_options(options);
+        assert!(extractor
+            .extract_first_where(|c| c.identifier == "com.example.b")
+            .unwrap());
+        let stats = extractor.extract().unwrap();
+        let picked = tmp.path().join("out-3");
+        assert_eq!(stats.output_dir, picked);
+        assert!(picked.join("a.txt").is_file() && picked.join("b.txt").is_file());
+        assert!(!tmp.path().join("out-4").exists());
+    }
+
+    // ---- Size filters ----
+
+    #[test]
+    fn extract_cpio_honours_max_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("./dir", 0o040755, b""),
+            ("./dir/small.txt", 0o100644, b"tiny"),
+            ("./dir/big.bin", 0o100644, &[0u8; 100]),
+            ("./link", 0o120777, b"dir/big.bin"),
+        ]);
+
+        test_extractor(tmp.path())
+            .with_options(ExtractOptions {
+                max_size: Some(10),
+                ..Default::default()
+            })
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        assert_eq!(fs::read(tmp.path().join("dir/small.txt")).unwrap(), b"tiny");
+        assert!(!tmp.path().join("dir/big.bin").exists());
+        // Symlinks aren't subject to the size filter, even if dangling.
+        assert!(fs::symlink_metadata(tmp.path().join("link")).is_ok());
+    }
+
+    // ---- Per-file gzip ----
+
+    #[test]
+    fn gzip_files_compresses_each_regular_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = tmp.path().join("manifest.json");
+        let out = tmp.path().join("out");
+        let text = b"compress me ".repeat(100);
+        let cpio = cpio(&[
+            ("./doc", 0o040755, b""),
+            ("./doc/notes.txt", 0o100644, &text),
+            ("./notes", 0o120777, b"doc/notes.txt.gz"),
+        ]);
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            gzip_files: true,
+            manifest: Some(manifest.clone()),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.write_reports().unwrap();
+
+        assert!(!out.join("doc/notes.txt").exists());
+        let compressed = fs::read(out.join("doc/notes.txt.gz")).unwrap();
+        assert!(compressed.len() < text.len());
+        let mut decoded = Vec::new();
+        libflate::gzip::Decoder::new(compressed.as_slice())
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+        assert!(fs::symlink_metadata(out.join("notes")).is_ok());
+        let manifest = fs::read_to_string(manifest).unwrap();
+        let file: serde_json::Value =
+            serde_json::from_str(manifest.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(file["path"], "doc/notes.txt.gz");
+        assert_eq!(file["original"], "./doc/notes.txt");
+    }
+
+    // ---- Excluded directories ----
+
+    #[test]
+    fn exclude_dirs_prune_whole_subtrees() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.docs"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    ("./usr", 0o040755, b""),
+                    ("./usr/share", 0o040755, b""),
+                    ("./usr/share/doc", 0o040755, b""),
+                    ("./usr/share/doc/README", 0o100644, b"docs"),
+                    ("./usr/share/doc/html/index.html", 0o100644, b"<p/>"),
+                    ("./usr/share/docs-extra", 0o100644, b"kept"),
+                    ("./usr/bin/tool", 0o100755, b"tool"),
+                ])),
+            )
+            .build();
+        let options = ExtractOptions {
+            exclude_dirs: vec!["./usr/share/doc/".to_string()],
+            ..Default::default()
+        };
+
+        let names = pkg_extractor(pkg.clone(), tmp.path())
+            .with_options(options.clone())
+            .list_entries()
+            .unwrap();
+        assert!(names.iter().all(|n| !n.starts_with("./usr/share/doc/")));
+        assert!(!names.contains(&"./usr/share/doc".to_string()));
+
+        pkg_extractor(pkg, tmp.path())
+            .with_options(options)
+            .extract()
+            .unwrap();
+        assert!(!tmp.path().join("usr/share/doc").exists());
+        assert_eq!(
+            fs::read(tmp.path().join("usr/share/docs-extra")).unwrap(),
+            b"kept"
+        );
+        assert!(tmp.path().join("usr/bin/tool").is_file());
+    }
+
+    // ---- Heap map ----
+
+    #[test]
+    fn heap_map_lists_every_toc_file_with_data() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("Distribution", "<installer-gui-script/>")
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", cpio(&[("./a", 0o100644, b"a")]))
+            .build();
+        let mut extractor = pkg_extractor(pkg.clone(), tmp.path());
+
+        let heap = extractor.heap_map().unwrap();
+
+        // Three files; the `a.pkg` directory has no heap data.
+        assert_eq!(heap.len(), 3);
+        let payload = heap.iter().find(|e| e.path == "a.pkg/Payload").unwrap();
+        let start = payload.offset as usize;
+        assert_eq!(
+            &pkg[start..start + payload.length as usize],
+            cpio(&[("./a", 0o100644, b"a")]).as_slice()
+        );
+        // The extractor is still usable afterwards.
+        extractor.extract().unwrap();
+        assert!(tmp.path().join("a").is_file());
+    }
+
+    // ---- Path component length cap ----
+
+    #[test]
+    fn overlong_components_error_or_get_shortened() {
+        let long = format!("{}.txt", "x".repeat(296));
+        let cpio = cpio(&[
+            ("./dir", 0o040755, b""),
+            (&format!("./dir/{long}"), 0o100644, b"long"),
+        ]);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let err = test_extractor(tmp.path())
+            .with_options(ExtractOptions {
+                max_component_len: Some(255),
+                ..Default::default()
+            })
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap_err();
+        assert!(err.to_string().contains(&format!("{long:?}")), "{err}");
+        assert!(err.to_string().contains("300 bytes"), "{err}");
+
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = tmp.path().join("manifest.ndjson");
+        let out = tmp.path().join("out");
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            max_component_len: Some(255),
+            long_components: LongComponentPolicy::Shorten,
+            manifest: Some(manifest.clone()),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.write_reports().unwrap();
+
+        let names: Vec<String> = fs::read_dir(out.join("dir"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), 1);
+        assert!(names[0].len() <= 255);
+        assert!(names[0].starts_with("xxxx") && names[0].ends_with(".txt"));
+        assert_eq!(fs::read(out.join("dir").join(&names[0])).unwrap(), b"long");
+
+        let manifest = fs::read_to_string(manifest).unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(manifest.lines().last().unwrap()).unwrap();
+        assert_eq!(line["path"], format!("dir/{}", names[0]));
+        assert_eq!(line["original"], format!("./dir/{long}"));
+    }
+
+    // ---- Header inspector ----
+
+    #[test]
+    fn header_inspector_sees_raw_uid_and_gid() {
+        use std::sync::{Arc, Mutex};
+        let tmp = tempfile::tempdir().unwrap();
+        let mut entry = odc_entry("./owned", 0o100644, b"data");
+        // uid and gid are the 6-digit octal fields at offsets 24 and 30.
+        entry[24..30].copy_from_slice(b"000765");
+        entry[30..36].copy_from_slice(b"000024");
+        let mut cpio = entry;
+        cpio.extend(trailer());
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        test_extractor(tmp.path())
+            .with_header_inspector(move |h| {
+                sink.lock()
+                    .unwrap()
+                    .push((h.name.to_string(), h.uid, h.gid, h.mode, h.nlink));
+            })
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [("./owned".to_string(), 501, 20, 0o100644, 1)]
+        );
+    }
+
+    // ---- Size breakdown ----
+
+    #[test]
+    fn size_breakdown_groups_by_top_level_directory() {
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.sizes"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    (".", 0o040755, b""),
+                    ("./usr/bin/tool", 0o100755, &[0u8; 300]),
+                    ("./usr/lib/libfoo.dylib", 0o100644, &[0u8; 700]),
+                    ("./usr/lib/libfoo.1.dylib", 0o120777, b"libfoo.dylib"),
+                    ("./Library/Prefs/a.plist", 0o100644, &[0u8; 50]),
+                    ("./README", 0o100644, &[0u8; 5]),
+                ])),
+            )
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+
+        let breakdown = pkg_extractor(pkg, tmp.path()).size_breakdown().unwrap();
+        assert_eq!(
+            breakdown.into_iter().collect::<Vec<_>>(),
+            [
+                ("Library".to_string(), 50),
+                ("README".to_string(), 5),
+                ("usr".to_string(), 1000),
+            ]
+        );
+    }
+
+    // ---- Component retries ----
+
+    /// Fails the first read starting at each of `fail_at` with a timeout.
+    #[derive(Debug)]
+    struct FlakyReader {
+        inner: Cursor<Vec<u8>>,
+        fail_at: Vec<u64>,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let pos = self.inner.position();
+            if let Some(i) = self.fail_at.iter().position(|&p| p == pos) {
+                self.fail_at.remove(i);
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for FlakyReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn component_retries_recover_from_transient_failures() {
+        let payload = |name: &str| gzip(&cpio(&[(name, 0o100644, b"data")]));
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.b", "b.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", payload("./a"))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", payload("./b"))
+            .build();
+        let fail_at: Vec<u64> = pkg_extractor(pkg.clone(), Path::new("unused"))
+            .heap_map()
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.path.ends_with("/Payload"))
+            .map(|e| e.offset)
+            .collect();
+        assert_eq!(fail_at.len(), 2);
+        let flaky = || FlakyReader {
+            inner: Cursor::new(pkg.clone()),
+            fail_at: fail_at.clone(),
+        };
+
+        let tmp = tempfile::tempdir().unwrap();
+        let err = PkgExtractor::new(flaky(), Some(tmp.path().join("no-retry")))
+            .extract()
+            .unwrap_err();
+        assert!(err.to_string().contains("No component payload"), "{err}");
+
+        let out = tmp.path().join("retry");
+        PkgExtractor::new(flaky(), Some(out.clone()))
+            .with_options(ExtractOptions {
+                component_retries: 2,
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(out.join("a")).unwrap(), b"data");
+        assert_eq!(fs::read(out.join("b")).unwrap(), b"data");
+    }
+
+    // ---- lsbom listing ----
+
+    #[test]
+    fn lsbom_listing_matches_golden_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let listing = tmp.path().join("listing.lsbom");
+        let out = tmp.path().join("out");
+        let cpio = cpio(&[
+            (".", 0o040755, b""),
+            ("./bin", 0o040755, b""),
+            ("./bin/tool", 0o100755, b"#!/bin/sh\n"),
+            ("./bin/alias", 0o120777, b"tool"),
+            ("./etc/conf", 0o100644, b"hello\n"),
+        ]);
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            lsbom: Some(listing.clone()),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.write_reports().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(listing).unwrap(),
+            include_str!("../tests/fixtures/listing.lsbom")
+        );
+    }
+
+    // ---- Continuing past file errors ----
+
+    #[test]
+    fn continue_on_file_error_salvages_the_rest() {
+        let tmp = tempfile::tempdir().unwrap();
+        // `./blocker` is a file, so `./blocker/child` cannot be created.
+        let cpio = cpio(&[
+            ("./blocker", 0o100644, b"file"),
+            ("./blocker/child", 0o100644, b"unreachable"),
+            ("./after", 0o100644, b"kept"),
+        ]);
+
+        test_extractor(tmp.path())
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap_err();
+        assert!(!tmp.path().join("after").exists());
+
+        let mut extractor = test_extractor(tmp.path()).with_options(ExtractOptions {
+            continue_on_file_error: true,
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, tmp.path()).unwrap();
+        assert_eq!(fs::read(tmp.path().join("after")).unwrap(), b"kept");
+
+        let stats = extractor.stats();
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.failed_files.len(), 1);
+        assert_eq!(stats.failed_files[0].path, tmp.path().join("blocker/child"));
+    }
+
+    // ---- Component directory mapping ----
+
+    #[test]
+    fn component_dir_map_routes_components() {
+        let payload = |name: &str| gzip(&cpio(&[(name, 0o100644, b"data")]));
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[
+                    ("com.example.app", "app.pkg"),
+                    ("com.example.cli", "cli.pkg"),
+                    ("com.example.docs", "docs.pkg"),
+                    ("com.example.evil", "evil.pkg"),
+                ]),
+            )
+            .file("app.pkg/PackageInfo", package_info("com.example.app"))
+            .file("app.pkg/Payload", payload("./App.app"))
+            .file("cli.pkg/PackageInfo", package_info("com.example.cli"))
+            .file("cli.pkg/Payload", payload("./tool"))
+            .file("docs.pkg/PackageInfo", package_info("com.example.docs"))
+            .file("docs.pkg/Payload", payload("./README"))
+            .file("evil.pkg/PackageInfo", package_info("com.example.evil"))
+            .file("evil.pkg/Payload", payload("./evil"))
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let options = ExtractOptions {
+            component_dir_map: BTreeMap::from([
+                ("com.example.app".to_string(), PathBuf::from("Applications")),
+                (
+                    "com.example.cli".to_string(),
+                    PathBuf::from("usr/local/bin"),
+                ),
+                (
+                    "com.example.evil".to_string(),
+                    PathBuf::from("../elsewhere"),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        let stats = pkg_extractor(pkg.clone(), &out)
+            .with_options(options.clone())
+            .extract()
+            .unwrap();
+
+        assert!(out.join("Applications/App.app").is_file());
+        assert!(out.join("usr/local/bin/tool").is_file());
+        assert!(out.join("README").is_file());
+        assert!(!out.join("tool").exists());
+        // A directory outside the output directory is refused.
+        assert!(!tmp.path().join("elsewhere").exists());
+        assert!(!out.join("evil").exists());
+        assert_eq!(stats.warnings.len(), 1, "{:?}", stats.warnings);
+        assert_eq!(stats.warnings[0].kind, WarningKind::UnsafeName);
+
+        let strict = pkg_extractor(pkg, &tmp.path().join("strict"))
+            .with_options(ExtractOptions {
+                warnings_as_errors: true,
+                ..options
+            })
+            .extract();
+        assert!(strict.is_err());
+    }
+
+    // ---- Component iteration ----
+
+    #[test]
+    fn components_iterate_and_extract_selectively() {
+        let payload = |name: &str| gzip(&cpio(&[(name, 0o100644, b"data")]));
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.b", "b.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", payload("./a.txt"))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", payload("./b.txt"))
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let mut extractor = pkg_extractor(pkg, tmp.path());
+
+        let mut seen = Vec::new();
+        for component in extractor.components().unwrap() {
+            let component = component.unwrap();
+            seen.push((
+                component.identifier().to_string(),
+                component.payload_entries().unwrap(),
+            ));
+            if component.identifier() == "com.example.b" {
+                component.extract().unwrap();
+            }
+        }
+        assert_eq!(
+            seen,
+            [
+                ("com.example.a".to_string(), vec!["./a.txt".to_string()]),
+                ("com.example.b".to_string(), vec!["./b.txt".to_string()]),
+            ]
+        );
+        assert!(tmp.path().join("b.txt").is_file());
+        assert!(!tmp.path().join("a.txt").exists());
+
+        // The reader is handed back, so the extractor is still usable.
+        assert_eq!(extractor.components().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn component_extract_to_lays_out_like_a_full_extraction() {
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.b", "b.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", gzip(&cpio(&[("./a.txt", 0o100644, b"a")])))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", gzip(&cpio(&[("./b.txt", 0o100644, b"b")])))
+            .file(
+                "b.pkg/Scripts",
+                gzip(&cpio(&[("./postinstall", 0o100755, b"#!/bin/sh\n")])),
+            )
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let mut extractor = pkg_extractor(pkg, tmp.path()).with_options(ExtractOptions {
+            extract_scripts: true,
+            component_dir_map: BTreeMap::from([("com.example.b".to_string(), "b".into())]),
+            ..Default::default()
+        });
+
+        let b = extractor
+            .components()
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|component| component.identifier() == "com.example.b")
+            .unwrap();
+        let stats = b.extract_to(&out).unwrap();
+
+        assert_eq!(fs::read(out.join("b/b.txt")).unwrap(), b"b");
+        assert!(out.join("Scripts/com.example.b/postinstall").is_file());
+        assert!(!out.join("a.txt").exists());
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.output_dir, out);
+    }
+
+    // ---- cpio trailer ----
+
+    #[test]
+    fn missing_trailer_is_reported_when_checked() {
+        let entries = [("./a", 0o100644, &b"aaa"[..]), ("./b", 0o100644, b"bbb")];
+        let with_trailer = cpio(&entries);
+        let mut without_trailer = Vec::new();
+        for (name, mode, body) in entries {
+            without_trailer.extend(odc_entry(name, mode, body));
+        }
+        let options = ExtractOptions {
+            check_trailer: true,
+            warnings_as_errors: true,
+            ..Default::default()
+        };
+
+        let tmp = tempfile::tempdir().unwrap();
+        test_extractor(tmp.path())
+            .with_options(options.clone())
+            .extract_cpio(&with_trailer, tmp.path())
+            .unwrap();
+        assert!(!tmp.path().join(CPIO_TRAILER).exists());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let err = test_extractor(tmp.path())
+            .with_options(options)
+            .extract_cpio(&without_trailer, tmp.path())
+            .unwrap_err();
+        assert!(err.to_string().contains("may be truncated"), "{err}");
+        // Everything present was still extracted.
+        assert_eq!(fs::read(tmp.path().join("b")).unwrap(), b"bbb");
+
+        // Unchecked, a missing trailer goes unremarked.
+        test_extractor(tmp.path())
+            .with_options(ExtractOptions {
+                warnings_as_errors: true,
+                ..Default::default()
+            })
+            .extract_cpio(&without_trailer, tmp.path())
+            .unwrap();
+    }
+
+    // ---- Writer factory ----
+
+    #[test]
+    fn writer_factory_routes_output_to_memory() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        /// Type, mode, contents and whether they were flushed, by path.
+        type Outputs = Rc<RefCell<HashMap<PathBuf, (FileType, u32, Vec<u8>, bool)>>>;
+        struct Buffer(Outputs, PathBuf);
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let mut outputs = self.0.borrow_mut();
+                outputs.get_mut(&self.1).unwrap().2.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.borrow_mut().get_mut(&self.1).unwrap().3 = true;
+                Ok(())
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("out");
+        let cpio = cpio(&[
+            ("./dir", 0o040755, b""),
+            ("./dir/file", 0o100640, b"contents"),
+            ("./dir/link", 0o120777, b"file"),
+        ]);
+        let outputs = Outputs::default();
+        let sink = Rc::clone(&outputs);
+
+        test_extractor(&root)
+            .with_options(ExtractOptions {
+                force_mode: Some(ForceMode {
+                    file: 0o600,
+                    dir: 0o700,
+                }),
+                ..Default::default()
+            })
+            .with_writer_factory(move |path, file_type, mode| {
+                sink.borrow_mut()
+                    .insert(path.to_path_buf(), (*file_type, mode, Vec::new(), false));
+                Ok(Box::new(Buffer(Rc::clone(&sink), path.to_path_buf())))
+            })
+            .extract_cpio(&cpio, &root)
+            .unwrap();
+
+        let outputs = outputs.borrow();
+        assert_eq!(
+            outputs[&root.join("dir/file")],
+            (FileType::Regular, 0o100600, b"contents".to_vec(), true)
+        );
+        assert_eq!(
+            outputs[&root.join("dir/link")],
+            (FileType::Symlink, 0o120777, b"file".to_vec(), true)
+        );
+        assert_eq!(outputs.len(), 2);
+        assert!(!root.exists());
+    }
+
+    // ---- Decision record/replay ----
+
+    #[test]
+    fn decisions_record_then_replay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = tmp.path().join("decisions.ndjson");
+        let out = tmp.path().join("out");
+        let cpio = cpio(&[
+            ("./small", 0o100644, b"x"),
+            ("./big", 0o100644, &[0u8; 64]),
+            ("./../escape", 0o100644, b"nope"),
+            ("./fifo", 0o010644, b""),
+            ("./dir", 0o040755, b""),
+        ]);
+        let run = |options: ExtractOptions| {
+            let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+                max_size: Some(16),
+                ..options
+            });
+            extractor.extract_cpio(&cpio, &out)?;
+            extractor.write_reports()
+        };
+
+        run(ExtractOptions {
+            record_decisions: Some(log.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let actions: Vec<_> = lines
+            .iter()
+            .map(|l| l["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            actions,
+            ["extract", "skip_size", "refuse", "skip_type", "extract"]
+        );
+        assert_eq!(lines[0]["target"], "small");
+
+        // Same inputs and options: the replay agrees.
+        run(ExtractOptions {
+            replay_decisions: Some(log.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // A changed filter makes a different decision, which replay catches.
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            replay_decisions: Some(log.clone()),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        let err = extractor.write_reports().unwrap_err();
+        assert!(err.to_string().contains("Decision #1 differs"), "{err}");
+    }
+
+    // ---- Relative symlinks ----
+
+    #[test]
+    fn relative_link_target_walks_up_from_the_link() {
+        let rel =
+            |link: &str, target: &str| relative_link_target(Path::new(link), Path::new(""), target);
+        assert_eq!(rel("a/b/link", "/a/c/file"), "../c/file");
+        assert_eq!(rel("a/link", "/a/file"), "file");
+        assert_eq!(rel("link", "/usr/lib/x"), "usr/lib/x");
+        assert_eq!(rel("a/b/link", "/a/b"), ".");
+        assert_eq!(rel("a/link", "/x/../y"), "../y");
+        // Relative to a payload root that is not the link's base.
+        let routed = |link: &str, root: &str, target: &str| {
+            relative_link_target(Path::new(link), Path::new(root), target)
+        };
+        assert_eq!(
+            routed("links/a/link", "comp", "/a/file"),
+            "../../comp/a/file"
+        );
+        assert_eq!(routed("comp/link", "comp", "/../x"), "x");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_symlinks_resolve_inside_the_tree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            (
+                "./Applications/Foo.app/Contents/Frameworks/Lib",
+                0o100644,
+                b"lib",
+            ),
+            (
+                "./Applications/Foo.app/Contents/MacOS/Lib",
+                0o120755,
+                b"/Applications/Foo.app/Contents/Frameworks/Lib",
+            ),
+        ]);
+
+        test_extractor(tmp.path())
+            .with_options(ExtractOptions {
+                relative_symlinks: true,
+                ..Default::default()
+            })
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        let link = tmp.path().join("Applications/Foo.app/Contents/MacOS/Lib");
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            Path::new("../Frameworks/Lib")
+        );
+        assert_eq!(fs::read(&link).unwrap(), b"lib");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn relative_symlinks_routed_by_extension_point_into_the_payload() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let root = out.join("com.example.app");
+        let cpio = cpio(&[
+            ("./bin/tool", 0o100755, b"tool"),
+            ("./bin/tool.lnk", 0o120755, b"/bin/tool"),
+        ]);
+
+        test_extractor(&out)
+            .with_options(ExtractOptions {
+                relative_symlinks: true,
+                extension_actions: BTreeMap::from([(
+                    "lnk".to_string(),
+                    EntryAction::Route(PathBuf::from("links")),
+                )]),
+                ..Default::default()
+            })
+            .extract_cpio(&cpio, &root)
+            .unwrap();
+
+        let link = out.join("links/bin/tool.lnk");
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            Path::new("../../com.example.app/bin/tool")
+        );
+        assert_eq!(fs::read(&link).unwrap(), b"tool");
+    }
+
+    // ---- Payload image ----
+
+    #[test]
+    fn payload_image_concatenates_every_component_cpio() {
+        let payload_a = cpio(&[("./a", 0o100644, b"aaa"), ("./shared", 0o040755, b"")]);
+        let payload_b = cpio(&[("./b", 0o100644, b"bbb")]);
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.b", "b.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", gzip(&payload_a))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", gzip(&payload_b))
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let image = tmp.path().join("payload.cpio");
+
+        pkg_extractor(pkg, &tmp.path().join("out"))
+            .with_options(ExtractOptions {
+                payload_image: Some(image.clone()),
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+
+        let bytes = fs::read(&image).unwrap();
+        assert_eq!(bytes, [payload_a, payload_b].concat());
+
+        // Reading on past each stream's trailer yields every entry.
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let mut names = Vec::new();
+        while let Some((header, _)) = next_odc_entry(&mut cursor).unwrap() {
+            if header.name != CPIO_TRAILER {
+                names.push(header.name);
+            }
+        }
+        assert_eq!(names, ["./a", "./shared", "./b"]);
+    }
+
+    // ---- Extension actions ----
+
+    #[test]
+    fn extension_actions_skip_and_route_by_extension() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("./var/app.log", 0o100644, b"log"),
+            ("./var/OLD.LOG", 0o100644, b"log"),
+            ("./bin/tool", 0o100755, b"tool"),
+            ("./bin/tool.dSYM/Contents/Info.plist", 0o100644, b"dsym"),
+            ("./etc/com.example.plist", 0o100644, b"plist"),
+            ("./etc/app.conf", 0o100644, b"conf"),
+        ]);
+
+        let mut extractor = test_extractor(tmp.path()).with_options(ExtractOptions {
+            extension_actions: BTreeMap::from([
+                ("LOG".to_string(), EntryAction::Skip),
+                // Sorts after "LOG", so never applies.
+                ("log".to_string(), EntryAction::Extract),
+                (".dSYM".to_string(), EntryAction::Skip),
+                (
+                    "plist".to_string(),
+                    EntryAction::Route(PathBuf::from("plists")),
+                ),
+                (
+                    "conf".to_string(),
+                    EntryAction::Route(PathBuf::from("../outside")),
+                ),
+            ]),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, tmp.path()).unwrap();
+
+        assert!(!tmp.path().join("var/app.log").exists());
+        assert!(!tmp.path().join("var/OLD.LOG").exists());
+        assert!(tmp.path().join("bin/tool").is_file());
+        // The bundle's extension wins over the plist inside it.
+        assert!(!tmp.path().join("bin/tool.dSYM").exists());
+        assert!(!tmp.path().join("plists/bin/tool.dSYM").exists());
+        assert_eq!(
+            fs::read(tmp.path().join("plists/etc/com.example.plist")).unwrap(),
+            b"plist"
+        );
+        assert!(!tmp.path().join("etc/com.example.plist").exists());
+        // A route outside the output directory is refused.
+        assert!(!tmp.path().join("etc/app.conf").exists());
+        assert!(!tmp.path().parent().unwrap().join("outside").exists());
+        let warnings = &extractor.stats().warnings;
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert_eq!(warnings[0].kind, WarningKind::UnsafeName);
+    }
+
+    // ---- Presence queries ----
+
+    #[test]
+    fn contains_finds_present_paths_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.has"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    (".", 0o040755, b""),
+                    ("./usr", 0o040755, b""),
+                    ("./usr/bin/tool", 0o100755, b"tool"),
+                ])),
+            )
+            .build();
+        let mut extractor = pkg_extractor(pkg, tmp.path());
+
+        assert!(extractor.contains("usr/bin/tool").unwrap());
+        assert!(extractor.contains("/usr/bin/tool").unwrap());
+        assert!(extractor.contains("./usr/").unwrap());
+        assert!(!extractor.contains("usr/bin/other").unwrap());
+        assert!(!extractor.contains("usr/bin").unwrap());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    // ---- Directory size index ----
+
+    #[test]
+    fn size_index_aggregates_nested_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let sizes = tmp.path().join("sizes.json");
+        let cpio = cpio(&[
+            ("./usr", 0o040755, b""),
+            ("./usr/bin/tool", 0o100755, b"12345"),
+            ("./usr/share/doc/README", 0o100644, b"123"),
+            ("./usr/share/doc/NEWS", 0o100644, b"12"),
+            ("./usr/share/empty", 0o040755, b""),
+            ("./usr/share/link", 0o120777, b"doc/README"),
+            ("./top", 0o100644, b"1"),
+        ]);
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            size_index: Some(sizes.clone()),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.write_reports().unwrap();
+
+        let sizes: HashMap<String, u64> =
+            serde_json::from_slice(&fs::read(sizes).unwrap()).unwrap();
+        assert_eq!(
+            sizes,
+            HashMap::from([
+                (".".to_string(), 11),
+                ("usr".to_string(), 10),
+                ("usr/bin".to_string(), 5),
+                ("usr/share".to_string(), 5),
+                ("usr/share/doc".to_string(), 5),
+            ])
+        );
+    }
+
+    // ---- Control characters in names ----
+
+    #[test]
+    fn sanitize_control_chars_escapes_newlines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let manifest = tmp.path().join("manifest.json");
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.ctrl"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    ("./evil\nname", 0o100644, b"evil"),
+                    ("./plain", 0o100644, b"plain"),
+                ])),
+            )
+            .build();
+        let options = ExtractOptions {
+            sanitize_control_chars: true,
+            manifest: Some(manifest.clone()),
+            ..Default::default()
+        };
+
+        let names = pkg_extractor(pkg.clone(), &out)
+            .with_options(options.clone())
+            .list_entries()
+            .unwrap();
+        assert_eq!(names, ["./evil\\x0aname", "./plain"]);
+
+        pkg_extractor(pkg, &out)
+            .with_options(options)
+            .extract()
+            .unwrap();
+        assert_eq!(fs::read(out.join("evil\\x0aname")).unwrap(), b"evil");
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 2);
+        let first = fs::read_to_string(manifest).unwrap();
+        let first: serde_json::Value = serde_json::from_str(first.lines().next().unwrap()).unwrap();
+        assert_eq!(first["original"], "./evil\nname");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_symlink_targets_are_kept_raw_and_reported_escaped() {
+        use std::os::unix::ffi::OsStrExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[("./link", 0o120777, b"caf\xe9\n")]);
+
+        for (sanitize, reported) in [(false, "caf\u{fffd}\n"), (true, "caf\\xe9\\x0a")] {
+            let out = tmp.path().join(format!("out-{sanitize}"));
+            let manifest = tmp.path().join(format!("manifest-{sanitize}.json"));
+            let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+                sanitize_control_chars: sanitize,
+                manifest: Some(manifest.clone()),
+                ..Default::default()
+            });
+            extractor.extract_cpio(&cpio, &out).unwrap();
+            extractor.write_reports().unwrap();
+
+            let target = fs::read_link(out.join("link")).unwrap();
+            assert_eq!(target.as_os_str().as_bytes(), b"caf\xe9\n");
+            let line: serde_json::Value =
+                serde_json::from_str(fs::read_to_string(manifest).unwrap().trim()).unwrap();
+            assert_eq!(line["target"], reported);
+        }
+    }
+
+    // ---- Delta against a baseline BOM ----
+
+    #[test]
+    fn delta_baseline_extracts_only_new_and_changed_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let baseline = tmp.path().join("Bom");
+        let crc = |data: &[u8]| {
+            let mut cksum = Cksum::new();
+            cksum.update(data);
+            cksum.finish()
+        };
+        fs::write(
+            &baseline,
+            bom(&[
+                (".", 0o040755, 0, 0, None),
+                ("./etc", 0o040755, 0, 0, None),
+                ("./etc/same", 0o100644, 4, crc(b"same"), None),
+                ("./etc/edited", 0o100644, 3, crc(b"old"), None),
+            ]),
+        )
+        .unwrap();
+        let cpio = cpio(&[
+            (".", 0o040755, b""),
+            ("./etc", 0o040755, b""),
+            ("./etc/same", 0o100644, b"same"),
+            ("./etc/edited", 0o100644, b"new"),
+            ("./etc/added", 0o100644, b"added"),
+        ]);
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            delta_baseline: Some(baseline),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+
+        assert!(out.join("etc").is_dir());
+        assert!(!out.join("etc/same").exists());
+        assert_eq!(fs::read(out.join("etc/edited")).unwrap(), b"new");
+        assert_eq!(fs::read(out.join("etc/added")).unwrap(), b"added");
+        assert_eq!(
+            extractor.stats().delta,
+            [out.join("etc/edited"), out.join("etc/added")]
+        );
+    }
+
+    // ---- BOM layout ----
+
+    #[cfg(unix)]
+    #[test]
+    fn bom_layout_takes_modes_and_paths_from_the_bom() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.bom"))
+            .file(
+                "Bom",
+                bom(&[
+                    (".", 0o040755, 0, 0, None),
+                    ("./bin", 0o040755, 0, 0, None),
+                    ("./bin/tool", 0o100755, 4, 0, None),
+                    ("./lib", 0o040555, 0, 0, None),
+                    ("./lib/libfoo.dylib", 0o100644, 3, 0, None),
+                    ("./share", 0o040700, 0, 0, None),
+                ]),
+            )
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    (".", 0o040755, b""),
+                    ("./bin", 0o040755, b""),
+                    ("./bin/tool", 0o100644, b"tool"),
+                    ("./lib", 0o040755, b""),
+                    ("./lib/libfoo.dylib", 0o100644, b"foo"),
+                    ("./stray", 0o100644, b"stray"),
+                ])),
+            )
+            .build();
+
+        let mut extractor = pkg_extractor(pkg.clone(), tmp.path()).with_options(ExtractOptions {
+            bom_layout: true,
+            ..Default::default()
+        });
+        extractor.extract_first_where(|_| true).unwrap();
+
+        let mode = |path: &str| {
+            fs::metadata(tmp.path().join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert_eq!(mode("bin/tool"), 0o755);
+        // The directory is locked down once its contents are written.
+        assert_eq!(mode("lib"), 0o555);
+        assert_eq!(
+            fs::read(tmp.path().join("lib/libfoo.dylib")).unwrap(),
+            b"foo"
+        );
+        assert_eq!(mode("share"), 0o700);
+        assert!(!tmp.path().join("stray").exists());
+        let mismatches: Vec<_> = extractor
+            .stats()
+            .warnings
+            .iter()
+            .filter(|w| w.kind == WarningKind::BomMismatch)
+            .map(|w| w.message.as_str())
+            .collect();
+        assert_eq!(mismatches.len(), 3, "{mismatches:?}");
+        assert!(mismatches[0].starts_with("./bin/tool: payload mode 100644"));
+        assert!(mismatches[1].starts_with("./lib: payload mode 40755"));
+        assert!(mismatches[2].contains("./stray: it is not in the Bom"));
+        fs::set_permissions(tmp.path().join("lib"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Reconciled modes are not errors; entries missing from the Bom are.
+        let tmp = tempfile::tempdir().unwrap();
+        let err = pkg_extractor(pkg, tmp.path())
+            .with_options(ExtractOptions {
+                bom_layout: true,
+                warnings_as_errors: true,
+                ..Default::default()
+            })
+            .extract_first_where(|_| true)
+            .unwrap_err();
+        assert!(err.to_string().contains("./stray"), "{err}");
+        fs::set_permissions(tmp.path().join("lib"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    // ---- Symlinks sidecar ----
+
+    #[test]
+    fn symlinks_sidecar_records_links_instead_of_creating_them() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.links"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    ("./lib/libfoo.1.dylib", 0o100755, b"lib"),
+                    ("./lib/libfoo.dylib", 0o120755, b"libfoo.1.dylib"),
+                ])),
+            )
+            .build();
+
+        pkg_extractor(pkg, tmp.path())
+            .with_options(ExtractOptions {
+                symlinks_sidecar: true,
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
 
-    // ---- `FileType::from_mode` ----
+        assert!(tmp.path().join("lib/libfoo.1.dylib").is_file());
+        assert!(fs::symlink_metadata(tmp.path().join("lib/libfoo.dylib")).is_err());
+        let sidecar: HashMap<String, String> =
+            serde_json::from_slice(&fs::read(tmp.path().join(SYMLINKS_SIDECAR)).unwrap()).unwrap();
+        assert_eq!(
+            sidecar,
+            HashMap::from([("lib/libfoo.dylib".to_string(), "libfoo.1.dylib".to_string())])
+        );
+    }
 
+    // ---- Deferred metadata ----
+
+    #[cfg(unix)]
     #[test]
-    fn file_type_from_mode_regular() {
-        assert_eq!(FileType::from_mode(0o100644), FileType::Regular);
-        assert_eq!(FileType::from_mode(0o100755), FileType::Regular);
+    fn defer_metadata_fills_read_only_directories() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let mut cpio = Vec::new();
+        cpio.extend(odc_entry("./ro", 0o040500, b""));
+        cpio.extend(odc_entry("./ro/sub", 0o040555, b""));
+        cpio.extend(odc_entry("./ro/sub/file", 0o100444, b"read only"));
+        cpio.extend(odc_entry("./ro/tool", 0o100555, b"#!/bin/sh\n"));
+        cpio.extend(trailer());
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            defer_metadata: true,
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.apply_deferred_metadata().unwrap();
+
+        let mode = |p: &str| fs::metadata(out.join(p)).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(fs::read(out.join("ro/sub/file")).unwrap(), b"read only");
+        assert_eq!(mode("ro"), 0o500);
+        assert_eq!(mode("ro/sub"), 0o555);
+        assert_eq!(mode("ro/sub/file"), 0o444);
+        assert_eq!(mode("ro/tool"), 0o555);
+        // Every odc_entry has mtime 0.
+        let mtime = fs::metadata(out.join("ro")).unwrap().modified().unwrap();
+        assert_eq!(mtime, std::time::UNIX_EPOCH);
+
+        // Let the temporary directory be removed.
+        for dir in ["ro", "ro/sub"] {
+            fs::set_permissions(out.join(dir), fs::Permissions::from_mode(0o755)).unwrap();
+        }
     }
 
+    #[cfg(unix)]
     #[test]
-    fn file_type_from_mode_directory() {
-        assert_eq!(FileType::from_mode(0o040755), FileType::Directory);
+    fn force_mode_locks_directories_after_their_contents() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let cpio = cpio(&[
+            ("./ro", 0o040755, b""),
+            ("./ro/sub", 0o040755, b""),
+            ("./ro/sub/file", 0o100644, b"inside"),
+            ("./ro/tool", 0o100755, b"#!/bin/sh\n"),
+        ]);
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            force_mode: Some(ForceMode {
+                file: 0o444,
+                dir: 0o555,
+            }),
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+        extractor.apply_deferred_metadata().unwrap();
+
+        let mode = |p: &str| fs::metadata(out.join(p)).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(fs::read(out.join("ro/sub/file")).unwrap(), b"inside");
+        assert_eq!(mode("ro"), 0o555);
+        assert_eq!(mode("ro/sub"), 0o555);
+        assert_eq!(mode("ro/sub/file"), 0o444);
+
+        for dir in ["ro", "ro/sub"] {
+            fs::set_permissions(out.join(dir), fs::Permissions::from_mode(0o755)).unwrap();
+        }
     }
 
+    // ---- Component flags ----
+
     #[test]
-    fn file_type_from_mode_symlink() {
-        assert_eq!(FileType::from_mode(0o120777), FileType::Symlink);
+    fn component_report_lists_auth_relocatable_and_scripts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = tmp.path().join("components.json");
+        let pkg = XarBuilder::new()
+            .file("Distribution", distribution(&[]))
+            .file(
+                "a.pkg/PackageInfo",
+                r#"<pkg-info format-version="2" identifier="com.example.a" version="1" auth="root" relocatable="true"/>"#,
+            )
+            .file("a.pkg/Payload", gzip(&cpio(&[("./a", 0o100644, b"a")])))
+            .file("a.pkg/Scripts", gzip(&cpio(&[("./postinstall", 0o100755, b"")])))
+            .file(
+                "b.pkg/PackageInfo",
+                r#"<pkg-info format-version="2" identifier="com.example.b" version="1" auth="none"/>"#,
+            )
+            .file("b.pkg/Payload", gzip(&cpio(&[("./b", 0o100644, b"b")])))
+            .build();
+
+        pkg_extractor(pkg, &tmp.path().join("out"))
+            .with_options(ExtractOptions {
+                component_report: Some(report.clone()),
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+
+        let components: Vec<serde_json::Value> =
+            serde_json::from_slice(&fs::read(report).unwrap()).unwrap();
+        let flags: Vec<_> = components
+            .iter()
+            .map(|c| {
+                (
+                    c["identifier"].as_str().unwrap(),
+                    c["auth"].as_str().unwrap(),
+                    c["relocatable"].as_bool(),
+                    c["runs_scripts"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            [
+                ("com.example.a", "root", Some(true), true),
+                ("com.example.b", "none", None, false),
+            ]
+        );
     }
 
+    // ---- Component order ----
+
     #[test]
-    fn file_type_from_mode_other() {
-        // Char device, block device, FIFO, socket -- none of these four
-        // should be mistaken for a regular or symlink.
-        assert_eq!(FileType::from_mode(0o020644), FileType::Other);
-        assert_eq!(FileType::from_mode(0o060644), FileType::Other);
-        assert_eq!(FileType::from_mode(0o010644), FileType::Other);
-        assert_eq!(FileType::from_mode(0o140644), FileType::Other);
+    fn component_order_puts_listed_components_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("Distribution", distribution(&[]))
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", gzip(&cpio(&[("./a", 0o100644, b"a")])))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("b.pkg/Payload", gzip(&cpio(&[("./b", 0o100644, b"b")])))
+            .file("c.pkg/PackageInfo", package_info("com.example.c"))
+            .file("c.pkg/Payload", gzip(&cpio(&[("./c", 0o100644, b"c")])))
+            .file("d.pkg/PackageInfo", package_info("com.example.d"))
+            .file("d.pkg/Payload", gzip(&cpio(&[("./d", 0o100644, b"d")])))
+            .build();
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = seen.clone();
+
+        pkg_extractor(pkg, tmp.path())
+            .with_component_order(vec!["com.example.c".into(), "com.example.a".into()])
+            .with_header_inspector(move |header| log.borrow_mut().push(header.name.to_string()))
+            .extract()
+            .unwrap();
+
+        assert_eq!(*seen.borrow(), ["./c", "./a", "./b", "./d"]);
     }
 
-    // ---- `safe_join` ----
+    // ---- Component count ----
 
     #[test]
-    fn safe_join_accepts_relative_and_curdir_paths() {
-        let root = Path::new("/out");
+    fn component_count_covers_both_flavors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let single = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.one"))
+            .file("Payload", gzip(&cpio(&[("./a", 0o100644, b"a")])))
+            .build();
+        let product = XarBuilder::new()
+            .file("Distribution", distribution(&[]))
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", gzip(&cpio(&[("./a", 0o100644, b"a")])))
+            .file("b.pkg/PackageInfo", package_info("com.example.b"))
+            .file("c.pkg/Payload", gzip(&cpio(&[("./c", 0o100644, b"c")])))
+            .file("Resources/en.lproj/License.txt", "license")
+            .build();
+
         assert_eq!(
-            safe_join(root, "foo").as_deref(),
-            Some(Path::new("/out/foo"))
+            pkg_extractor(single, tmp.path()).component_count().unwrap(),
+            1
         );
+        let mut extractor = pkg_extractor(product, tmp.path());
+        assert_eq!(extractor.component_count().unwrap(), 3);
+        // The reader is handed back, so the extractor is still usable.
+        assert_eq!(extractor.component_count().unwrap(), 3);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    // ---- Per-component directories ----
+
+    #[test]
+    fn per_component_dirs_sanitize_identifiers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("Distribution", distribution(&[]))
+            .file("a.pkg/PackageInfo", package_info("com.example/evil:id"))
+            .file("a.pkg/Payload", gzip(&cpio(&[("./a", 0o100644, b"a")])))
+            .file("b.pkg/PackageInfo", package_info("com.example_evil_id"))
+            .file("b.pkg/Payload", gzip(&cpio(&[("./b", 0o100644, b"b")])))
+            .file("c.pkg/PackageInfo", package_info(".."))
+            .file("c.pkg/Payload", gzip(&cpio(&[("./c", 0o100644, b"c")])))
+            .build();
+
+        let stats = pkg_extractor(pkg, tmp.path())
+            .with_options(ExtractOptions {
+                per_component_dirs: true,
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+
         assert_eq!(
-            safe_join(root, "a/b/c").as_deref(),
-            Some(Path::new("/out/a/b/c"))
+            stats.component_dirs,
+            BTreeMap::from([
+                ("..".to_string(), "__".to_string()),
+                (
+                    "com.example/evil:id".to_string(),
+                    "com.example_evil_id".to_string()
+                ),
+                (
+                    "com.example_evil_id".to_string(),
+                    "com.example_evil_id-2".to_string()
+                ),
+            ])
         );
         assert_eq!(
-            safe_join(root, "./foo").as_deref(),
-            Some(Path::new("/out/foo"))
+            fs::read(tmp.path().join("com.example_evil_id/a")).unwrap(),
+            b"a"
         );
         assert_eq!(
-            safe_join(root, "./a/./b").as_deref(),
-            Some(Path::new("/out/a/b"))
+            fs::read(tmp.path().join("com.example_evil_id-2/b")).unwrap(),
+            b"b"
         );
+        assert_eq!(fs::read(tmp.path().join("__/c")).unwrap(), b"c");
+        assert!(!tmp.path().join("com.example").exists());
     }
 
+    // ---- Resume cursor ----
+
     #[test]
-    fn safe_join_rejects_parent_dir_anywhere() {
-        let root = Path::new("/out");
-        assert_eq!(safe_join(root, ".."), None);
-        assert_eq!(safe_join(root, "../etc/passwd"), None);
-        assert_eq!(safe_join(root, "a/../../b"), None);
-        assert_eq!(safe_join(root, "a/b/.."), None);
+    fn resume_cursor_round_trips_and_skips_done_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        // `./blocker` is a file, so `./blocker/child` cannot be created.
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.resume"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    (".", 0o040755, b""),
+                    ("./first", 0o100644, b"first"),
+                    ("./blocker", 0o100644, b"file"),
+                    ("./blocker/child", 0o100644, b"child"),
+                    ("./after", 0o100644, b"after"),
+                ])),
+            )
+            .build();
+
+        // What a GUI would write to disk as extraction goes.
+        let saved = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
+        let sink = saved.clone();
+        pkg_extractor(pkg.clone(), tmp.path())
+            .with_cursor_callback(move |cursor| {
+                *sink.borrow_mut() = serde_json::to_string(cursor).unwrap();
+            })
+            .extract()
+            .unwrap_err();
+        let restored: ResumeCursor = serde_json::from_str(&saved.borrow()).unwrap();
+        assert_eq!(
+            restored,
+            ResumeCursor {
+                done: Vec::new(),
+                component: "com.example.resume".into(),
+                entry: 3
+            }
+        );
+
+        // Fix what broke, and show that done entries are not redone.
+        fs::remove_file(tmp.path().join("blocker")).unwrap();
+        fs::create_dir(tmp.path().join("blocker")).unwrap();
+        fs::remove_file(tmp.path().join("first")).unwrap();
+        let stats = pkg_extractor(pkg, tmp.path())
+            .with_options(ExtractOptions {
+                resume_from: Some(restored),
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+
+        assert!(!tmp.path().join("first").exists());
+        assert_eq!(
+            fs::read(tmp.path().join("blocker/child")).unwrap(),
+            b"child"
+        );
+        assert_eq!(fs::read(tmp.path().join("after")).unwrap(), b"after");
+        assert_eq!(stats.files, 2);
+        assert_eq!(
+            stats.cursor,
+            Some(ResumeCursor {
+                done: vec!["com.example.resume".into()],
+                component: "com.example.resume".into(),
+                entry: 5
+            })
+        );
     }
 
     #[test]
-    fn safe_join_rejects_absolute_paths() {
-        let root = Path::new("/out");
-        assert_eq!(safe_join(root, "/etc/passwd"), None);
+    fn resume_cursor_names_components_whatever_their_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut pkg = XarBuilder::new().file(
+            "Distribution",
+            distribution(&[
+                ("com.example.a", "a.pkg"),
+                ("com.example.b", "b.pkg"),
+                ("com.example.c", "c.pkg"),
+            ]),
+        );
+        for name in ["a", "b", "c"] {
+            let first = format!("./{name}1");
+            let second = format!("./{name}2");
+            pkg = pkg
+                .file(
+                    &format!("{name}.pkg/PackageInfo"),
+                    package_info(&format!("com.example.{name}")),
+                )
+                .file(
+                    &format!("{name}.pkg/Payload"),
+                    gzip(&cpio(&[
+                        (".", 0o040755, b""),
+                        (&first, 0o100644, b"1"),
+                        (&second, 0o100644, b"2"),
+                    ])),
+                );
+        }
+
+        // Interrupted in `a` after its first file, `c` being done already.
+        let stats = pkg_extractor(pkg.build(), tmp.path())
+            .with_component_order(vec!["com.example.c".into(), "com.example.a".into()])
+            .with_options(ExtractOptions {
+                resume_from: Some(ResumeCursor {
+                    done: vec!["com.example.c".into()],
+                    component: "com.example.a".into(),
+                    entry: 2,
+                }),
+                ..Default::default()
+            })
+            .extract()
+            .unwrap();
+
+        for skipped in ["c1", "c2", "a1"] {
+            assert!(!tmp.path().join(skipped).exists(), "{skipped}");
+        }
+        for written in ["a2", "b1", "b2"] {
+            assert!(tmp.path().join(written).is_file(), "{written}");
+        }
+        // `a`'s root and first file, then `b`'s root.
+        assert_eq!(stats.skipped, 3);
+        assert_eq!(
+            stats.cursor.unwrap().done,
+            ["com.example.c", "com.example.a", "com.example.b"]
+        );
     }
 
-    // ---- End-to-end `extract_cpio` round-trip ----
+    // ---- Resuming a single file ----
+
+    #[test]
+    fn resume_file_completes_a_half_written_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.big"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    ("./small", 0o100644, b"small"),
+                    ("./data/big.bin", 0o100644, &big),
+                ])),
+            )
+            .build();
+
+        fs::create_dir_all(tmp.path().join("data")).unwrap();
+        fs::write(tmp.path().join("data/big.bin"), &big[..big.len() / 2]).unwrap();
 
-    /// Build one ODC ("070707") cpio header-plus-body for the given entry.
-    /// `name` must be valid UTF-8; the NUL terminator is added here. For
-    /// symlinks, pass the link target in `body`.
-    fn odc_entry(name: &str, mode: u32, body: &[u8]) -> Vec<u8> {
-        let mut buf = Vec::new();
-        buf.extend_from_slice(b"070707");
-        for _ in 0..2 {
-            buf.extend_from_slice(b"000000"); // dev, ino
-        }
-        buf.extend_from_slice(format!("{mode:06o}").as_bytes());
-        buf.extend_from_slice(b"000000"); // uid
-        buf.extend_from_slice(b"000000"); // gid
-        buf.extend_from_slice(b"000001"); // nlink
-        buf.extend_from_slice(b"000000"); // rdev
-        buf.extend_from_slice(b"00000000000"); // mtime
-        let name_bytes = name.as_bytes();
-        let namesize = name_bytes.len() + 1; // includes trailing NUL
-        buf.extend_from_slice(format!("{namesize:06o}").as_bytes());
-        buf.extend_from_slice(format!("{:011o}", body.len()).as_bytes());
-        buf.extend_from_slice(name_bytes);
-        buf.push(0);
-        buf.extend_from_slice(body);
-        buf
-    }
-
-    fn trailer() -> Vec<u8> {
-        odc_entry("TRAILER!!!", 0, b"")
+        let mut extractor = pkg_extractor(pkg, tmp.path());
+        let appended = extractor.resume_file("data/big.bin").unwrap();
+        assert_eq!(appended, (big.len() - big.len() / 2) as u64);
+        assert_eq!(fs::read(tmp.path().join("data/big.bin")).unwrap(), big);
+        // Already complete: nothing left to append.
+        assert_eq!(extractor.resume_file("./data/big.bin").unwrap(), 0);
+        assert!(extractor.resume_file("./missing").is_err());
     }
 
-    fn test_extractor(out_dir: &Path) -> PkgExtractor<Cursor<Vec<u8>>> {
-        PkgExtractor::new(Cursor::new(Vec::<u8>::new()), Some(out_dir.to_path_buf()))
+    #[test]
+    fn resume_file_finds_the_file_where_extraction_put_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let big: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.big"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    ("/opt/big.bin", 0o100644, &big),
+                    ("./link.bin", 0o100644, b"data"),
+                ])),
+            )
+            .build();
+        let partial = tmp.path().join("routed/opt/big.bin");
+        fs::create_dir_all(partial.parent().unwrap()).unwrap();
+        fs::write(&partial, &big[..1000]).unwrap();
+
+        let mut extractor = pkg_extractor(pkg, tmp.path()).with_options(ExtractOptions {
+            strip_leading_slash: true,
+            component_dir_map: BTreeMap::from([("com.example.big".to_string(), "routed".into())]),
+            ..Default::default()
+        });
+        assert_eq!(
+            extractor.resume_file("/opt/big.bin").unwrap(),
+            (big.len() - 1000) as u64
+        );
+        assert_eq!(fs::read(&partial).unwrap(), big);
+
+        // A symlink where the file should be is not written through.
+        #[cfg(unix)]
+        {
+            let outside = tmp.path().join("outside");
+            fs::write(&outside, b"da").unwrap();
+            std::os::unix::fs::symlink(&outside, tmp.path().join("routed/link.bin")).unwrap();
+            let err = extractor.resume_file("link.bin").unwrap_err();
+            assert!(err.to_string().contains("symlink"), "{err}");
+            assert_eq!(fs::read(&outside).unwrap(), b"da");
+        }
     }
 
+    // ---- Standalone cpio streams ----
+
     #[test]
-    fn extract_cpio_materialises_files_dirs_and_empties() {
+    fn extract_cpio_stream_writes_tree_without_a_package() {
         let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        let stream = cpio(&[
+            (".", 0o040755, b""),
+            ("./etc", 0o040755, b""),
+            ("./etc/motd", 0o100644, b"hello\n"),
+            ("./motd", 0o120777, b"etc/motd"),
+            ("./big", 0o100644, &[0u8; 64]),
+        ]);
 
-        let mut cpio = Vec::new();
-        cpio.extend(odc_entry("./dir", 0o040755, b""));
-        cpio.extend(odc_entry("./dir/hello.txt", 0o100644, b"hello\n"));
-        cpio.extend(odc_entry("./empty", 0o100644, b""));
-        cpio.extend(trailer());
+        let stats = extract_cpio_stream(
+            ReadOnly(stream.as_slice()),
+            &out,
+            &ExtractOptions {
+                max_size: Some(16),
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
-        test_extractor(tmp.path()).extract_cpio(&cpio).unwrap();
+        assert!(out.join("etc").is_dir());
+        assert_eq!(fs::read(out.join("etc/motd")).unwrap(), b"hello\n");
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(out.join("motd")).unwrap(),
+            Path::new("etc/motd")
+        );
+        assert!(!out.join("big").exists());
+        assert_eq!(stats.bytes, 6);
+        assert_eq!(stats.output_dir, out);
 
-        let root = tmp.path();
-        assert!(root.join("dir").is_dir());
-        assert_eq!(fs::read(root.join("dir/hello.txt")).unwrap(), b"hello\n");
-        assert!(root.join("empty").is_file());
-        assert_eq!(fs::read(root.join("empty")).unwrap(), Vec::<u8>::new());
+        // The output directory options apply too.
+        let err = extract_cpio_stream(
+            ReadOnly(stream.as_slice()),
+            &out,
+            &ExtractOptions {
+                require_empty_output: true,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("is not empty"), "{err}");
+        let stats = extract_cpio_stream(
+            ReadOnly(stream.as_slice()),
+            &out,
+            &ExtractOptions {
+                avoid_clobber_dir: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(stats.output_dir, tmp.path().join("out-2"));
+        assert!(stats.output_dir.join("etc/motd").is_file());
     }
 
-    #[cfg(unix)]
+    // ---- Panic-free entry point ----
+
     #[test]
-    fn extract_cpio_preserves_unix_mode_bits() {
-        use std::os::unix::fs::PermissionsExt;
+    fn try_extract_reports_malformed_input_cleanly() {
         let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.broken"))
+            .file("Payload", gzip(&cpio(&[("./a", 0o100644, b"abc")])))
+            .build();
 
-        let mut cpio = Vec::new();
-        cpio.extend(odc_entry("./script", 0o100755, b"#!/bin/sh\nexit 0\n"));
-        cpio.extend(odc_entry("./readonly", 0o100444, b"data"));
-        cpio.extend(trailer());
+        let mut truncated_payload = pkg.clone();
+        let len = truncated_payload.len();
+        truncated_payload.truncate(len - 8);
+        // The zlib-compressed TOC starts right after the 28-byte header.
+        let mut corrupt_toc = pkg.clone();
+        corrupt_toc[28..40].copy_from_slice(b"garbagegarba");
 
-        test_extractor(tmp.path()).extract_cpio(&cpio).unwrap();
+        for (i, input) in [
+            Vec::new(),
+            b"not a package at all".to_vec(),
+            pkg[..pkg.len() / 2].to_vec(),
+            truncated_payload,
+            corrupt_toc,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let result = pkg_extractor(input, tmp.path()).try_extract();
+            assert!(
+                matches!(result, Err(ExtractError::Failed(_))),
+                "input #{i}: {result:?}"
+            );
+        }
+    }
 
-        let script_mode = fs::metadata(tmp.path().join("script"))
-            .unwrap()
-            .permissions()
-            .mode()
-            & 0o7777;
-        assert_eq!(script_mode, 0o755);
+    // ---- Warnings as errors ----
 
-        let ro_mode = fs::metadata(tmp.path().join("readonly"))
-            .unwrap()
-            .permissions()
-            .mode()
-            & 0o7777;
-        assert_eq!(ro_mode, 0o444);
+    #[test]
+    fn warnings_as_errors_fails_on_missing_payload() {
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.empty"))
+            .build();
+
+        let tmp = tempfile::tempdir().unwrap();
+        pkg_extractor(pkg.clone(), tmp.path()).extract().unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let err = pkg_extractor(pkg, tmp.path())
+            .with_options(ExtractOptions {
+                warnings_as_errors: true,
+                ..Default::default()
+            })
+            .extract()
+            .unwrap_err();
+        assert!(err.to_string().contains("has no Payload"), "{err}");
     }
 
-    #[cfg(unix)]
+    // ---- Predicate-selected component ----
+
     #[test]
-    fn extract_cpio_materialises_symlinks() {
+    fn extract_first_where_picks_first_matching_component() {
         let tmp = tempfile::tempdir().unwrap();
+        let mut xar = XarBuilder::new().file("Distribution", "<installer-gui-script/>");
+        for (sub_pkg, identifier) in [
+            ("a.pkg", "org.example.tools"),
+            ("b.pkg", "com.apple.first"),
+            ("c.pkg", "com.apple.second"),
+        ] {
+            xar = xar
+                .file(&format!("{sub_pkg}/PackageInfo"), package_info(identifier))
+                .file(
+                    &format!("{sub_pkg}/Payload"),
+                    cpio(&[(&format!("./{identifier}"), 0o100644, b"x")]),
+                );
+        }
+        let mut extractor = pkg_extractor(xar.build(), tmp.path());
 
-        let mut cpio = Vec::new();
-        cpio.extend(odc_entry("./real.txt", 0o100644, b"target\n"));
-        cpio.extend(odc_entry("./link", 0o120777, b"real.txt"));
-        cpio.extend(trailer());
+        assert!(!extractor
+            .extract_first_where(|c| c.identifier.starts_with("net."))
+            .unwrap());
+        assert!(extractor
+            .extract_first_where(|c| c.identifier.starts_with("com.apple."))
+            .unwrap());
 
-        test_extractor(tmp.path()).extract_cpio(&cpio).unwrap();
+        assert!(tmp.path().join("com.apple.first").is_file());
+        assert!(!tmp.path().join("com.apple.second").exists());
+        assert!(!tmp.path().join("org.example.tools").exists());
+    }
 
-        let meta = fs::symlink_metadata(tmp.path().join("link")).unwrap();
-        assert!(meta.file_type().is_symlink());
+    #[test]
+    fn extract_largest_component_picks_biggest_install_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sized = |identifier: &str, kbytes: u64| {
+            format!(
+                r#"<pkg-info format-version="2" identifier="{identifier}" version="1" auth="root"><payload installKBytes="{kbytes}" numberOfFiles="1"/></pkg-info>"#
+            )
+        };
+        let pkg = XarBuilder::new()
+            .file("Distribution", "<installer-gui-script/>")
+            .file("helper.pkg/PackageInfo", sized("com.example.helper", 12))
+            .file("helper.pkg/Payload", cpio(&[("./helper", 0o100644, b"h")]))
+            .file("app.pkg/PackageInfo", sized("com.example.app", 48_000))
+            .file("app.pkg/Payload", cpio(&[("./App", 0o100644, b"a")]))
+            // No installKBytes: ranked by its (small) Payload size instead.
+            .file("docs.pkg/PackageInfo", package_info("com.example.docs"))
+            .file("docs.pkg/Payload", cpio(&[("./README", 0o100644, b"d")]))
+            .build();
+
+        let chosen = pkg_extractor(pkg, tmp.path())
+            .extract_largest_component()
+            .unwrap();
+
+        assert_eq!(chosen.as_deref(), Some("com.example.app"));
+        assert!(tmp.path().join("App").is_file());
+        assert!(!tmp.path().join("helper").exists());
+        assert!(!tmp.path().join("README").exists());
+    }
+
+    // ---- Directory creation cache ----
+
+    #[test]
+    fn many_files_in_one_directory_create_it_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let names: Vec<String> = (0..1000).map(|i| format!("./dir/file{i}")).collect();
+        let entries: Vec<_> = names
+            .iter()
+            .map(|name| (name.as_str(), 0o100644, b"x".as_slice()))
+            .collect();
+
+        let mut extractor = test_extractor(tmp.path());
+        extractor.extract_cpio(&cpio(&entries), tmp.path()).unwrap();
+
+        assert_eq!(fs::read_dir(tmp.path().join("dir")).unwrap().count(), 1000);
+        // One cache entry per distinct parent, not one per file.
         assert_eq!(
-            fs::read_link(tmp.path().join("link")).unwrap(),
-            Path::new("real.txt")
+            extractor.created_dirs,
+            HashSet::from([tmp.path().join("dir")])
         );
-        // Reading through the symlink yields the target's bytes.
-        assert_eq!(fs::read(tmp.path().join("link")).unwrap(), b"target\n");
     }
 
     #[test]
-    fn extract_cpio_refuses_path_traversal_but_keeps_safe_entries() {
+    fn create_dir_cached_skips_directories_it_already_made() {
         let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("a/b");
+        let mut created = HashSet::new();
 
-        let mut cpio = Vec::new();
-        cpio.extend(odc_entry("./../../evil.txt", 0o100644, b"pwned"));
-        cpio.extend(odc_entry("./safe.txt", 0o100644, b"ok"));
-        cpio.extend(trailer());
+        create_dir_cached(&mut created, &dir).unwrap();
+        assert!(dir.is_dir());
 
-        test_extractor(tmp.path()).extract_cpio(&cpio).unwrap();
+        // A cache hit must not touch the filesystem again.
+        fs::remove_dir(&dir).unwrap();
+        create_dir_cached(&mut created, &dir).unwrap();
+        assert!(!dir.exists());
+    }
 
-        // Nothing was written above the output root.
-        let parent_evil = tmp
-            .path()
-            .parent()
-            .unwrap()
-            .parent()
+    // ---- Leading-slash stripping ----
+
+    #[test]
+    fn strip_leading_slash_relocates_absolute_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("/usr/bin/foo", 0o100755, b"foo"),
+            ("/../escape", 0o100644, b"nope"),
+        ]);
+
+        // Default: absolute entries are refused outright.
+        test_extractor(tmp.path())
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+        assert!(!tmp.path().join("usr").exists());
+
+        test_extractor(tmp.path())
+            .with_options(ExtractOptions {
+                strip_leading_slash: true,
+                ..Default::default()
+            })
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+        assert_eq!(fs::read(tmp.path().join("usr/bin/foo")).unwrap(), b"foo");
+        // `..` is still refused once the slash is gone.
+        assert!(!tmp.path().parent().unwrap().join("escape").exists());
+    }
+
+    #[test]
+    fn entries_naming_the_root_are_skipped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("out");
+        fs::create_dir(&out).unwrap();
+        let cpio = cpio(&[
+            ("./", 0o040700, b""),
+            ("/", 0o100644, b"not a file"),
+            ("./.", 0o100644, b"nor this"),
+            ("./kept", 0o100644, b"kept"),
+        ]);
+
+        let mut extractor = test_extractor(&out).with_options(ExtractOptions {
+            strip_leading_slash: true,
+            ..Default::default()
+        });
+        extractor.extract_cpio(&cpio, &out).unwrap();
+
+        assert!(out.is_dir());
+        assert_eq!(extractor.stats().skipped, 3);
+        let names: Vec<_> = fs::read_dir(&out)
             .unwrap()
-            .join("evil.txt");
-        assert!(!parent_evil.exists());
-        // The legitimate entry beside it still landed.
-        assert_eq!(fs::read(tmp.path().join("safe.txt")).unwrap(), b"ok");
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["kept"]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&out).unwrap().permissions().mode() & 0o777;
+            assert_ne!(mode, 0o700);
+        }
+    }
+
+    // ---- Rate limiting ----
+
+    #[test]
+    fn rate_limit_throttles_writes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("./a.bin", 0o100644, &[1u8; 6_000]),
+            ("./b.bin", 0o100644, &[2u8; 6_000]),
+        ]);
+
+        let start = std::time::Instant::now();
+        test_extractor(tmp.path())
+            .with_rate_limit(20_000)
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        // 12 000 bytes at 20 000 B/s from an empty bucket: at least 0.6s.
+        assert!(start.elapsed() >= std::time::Duration::from_millis(600));
+        assert_eq!(fs::read(tmp.path().join("b.bin")).unwrap(), [2u8; 6_000]);
+    }
+
+    // ---- Manifest path style ----
+
+    #[test]
+    fn manifest_honours_report_path_style() {
+        let pkg = XarBuilder::new()
+            .file(
+                "Payload",
+                cpio(&[
+                    ("./dir", 0o040755, b""),
+                    ("./dir/hello.txt", 0o100644, b"hello\n"),
+                ]),
+            )
+            .build();
+
+        for style in [PathStyle::Relative, PathStyle::Absolute] {
+            let tmp = tempfile::tempdir().unwrap();
+            let out = tmp.path().join("out");
+            let manifest = tmp.path().join("manifest.ndjson");
+            pkg_extractor(pkg.clone(), &out)
+                .with_options(ExtractOptions {
+                    manifest: Some(manifest.clone()),
+                    report_paths: style,
+                    ..Default::default()
+                })
+                .extract()
+                .unwrap();
+
+            let lines: Vec<serde_json::Value> = fs::read_to_string(&manifest)
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect();
+            assert_eq!(lines.len(), 2);
+            let hello = &lines[1];
+            assert_eq!(hello["type"], "file");
+            assert_eq!(hello["size"], 6);
+            assert_eq!(hello["mode"], "0644");
+            let expected = match style {
+                PathStyle::Relative => "dir/hello.txt".to_string(),
+                PathStyle::Absolute => out.join("dir/hello.txt").to_string_lossy().into_owned(),
+            };
+            assert_eq!(hello["path"], expected);
+        }
+    }
+
+    // ---- Distribution cross-check ----
+
+    #[test]
+    fn missing_declared_component_is_reported() {
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.gone", "gone.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", cpio(&[("./a", 0o100644, b"a")]))
+            .build();
+
+        // Only a warning by default.
+        let tmp = tempfile::tempdir().unwrap();
+        pkg_extractor(pkg.clone(), tmp.path()).extract().unwrap();
+        assert!(tmp.path().join("a").is_file());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let err = pkg_extractor(pkg, tmp.path())
+            .with_options(ExtractOptions {
+                warnings_as_errors: true,
+                ..Default::default()
+            })
+            .extract()
+            .unwrap_err();
+        assert!(err.to_string().contains("com.example.gone"), "{err}");
+        assert!(!err.to_string().contains("com.example.a "), "{err}");
+    }
+
+    #[test]
+    fn warnings_are_collected_in_the_stats() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                distribution(&[("com.example.a", "a.pkg"), ("com.example.gone", "gone.pkg")]),
+            )
+            .file("a.pkg/PackageInfo", package_info("com.example.a"))
+            .file("a.pkg/Payload", cpio(&[("./a", 0o100644, b"a")]))
+            .build();
+
+        let stats = pkg_extractor(pkg, tmp.path()).extract().unwrap();
+        assert_eq!(stats.warnings.len(), 1, "{:?}", stats.warnings);
+        assert_eq!(stats.warnings[0].kind, WarningKind::MissingComponent);
+        assert!(stats.warnings[0].message.contains("com.example.gone"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let bare = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.bare"))
+            .build();
+        let stats = pkg_extractor(bare, tmp.path()).extract().unwrap();
+        assert_eq!(
+            stats.warnings,
+            [Warning {
+                kind: WarningKind::MissingPayload,
+                message: "Component package has no Payload".into(),
+            }]
+        );
     }
 }
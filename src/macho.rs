@@ -0,0 +1,209 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Thinning Mach-O universal ("fat") binaries to one architecture, as
+//! `lipo -thin` does.
+
+use log::debug;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::str::FromStr;
+
+pub(crate) const FAT_MAGIC: u32 = 0xcafe_babe;
+pub(crate) const FAT_MAGIC_64: u32 = 0xcafe_babf;
+
+/// An architecture to keep from universal binaries, set as
+/// [`crate::ExtractOptions::thin_arch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachArch {
+    X86_64,
+    Arm64,
+}
+
+impl MachArch {
+    fn cpu_type(self) -> u32 {
+        match self {
+            MachArch::X86_64 => 0x0100_0007,
+            MachArch::Arm64 => 0x0100_000c,
+        }
+    }
+}
+
+impl FromStr for MachArch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x86_64" => Ok(MachArch::X86_64),
+            "arm64" => Ok(MachArch::Arm64),
+            _ => Err(format!("unknown architecture {s:?} (x86_64, arm64)")),
+        }
+    }
+}
+
+/// One architecture slice of a universal binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FatSlice {
+    pub cpu_type: u32,
+    /// Where the slice lies in the file.
+    pub range: Range<usize>,
+}
+
+/// The magic of the fat header `head` starts with and its slice count;
+/// `None` when `head` does not start with one.
+pub(crate) fn fat_header(head: &[u8]) -> Option<(u32, u32)> {
+    let magic = u32::from_be_bytes(head.get(..4)?.try_into().ok()?);
+    let count = u32::from_be_bytes(head.get(4..8)?.try_into().ok()?);
+    // Java class files share the 32-bit magic; their version field in
+    // place of the count is at least 45.
+    ((magic == FAT_MAGIC || magic == FAT_MAGIC_64) && count != 0 && count < 45)
+        .then_some((magic, count))
+}
+
+/// The slices of the universal binary `file`, `None` when it is not one.
+/// A header listing slices past the end of the file is an error.
+pub(crate) fn fat_slices(file: &[u8]) -> io::Result<Option<Vec<FatSlice>>> {
+    let Some((magic, count)) = fat_header(file) else {
+        return Ok(None);
+    };
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let entry_len = if magic == FAT_MAGIC { 20 } else { 32 };
+    let mut slices = Vec::with_capacity(count as usize);
+    for i in 0..count as usize {
+        let entry = file
+            .get(8 + i * entry_len..8 + (i + 1) * entry_len)
+            .ok_or_else(|| invalid("truncated fat header"))?;
+        let field32 = |at: usize| u32::from_be_bytes(entry[at..at + 4].try_into().unwrap());
+        let field64 = |at: usize| u64::from_be_bytes(entry[at..at + 8].try_into().unwrap());
+        let (offset, len) = if magic == FAT_MAGIC {
+            (field32(8) as u64, field32(12) as u64)
+        } else {
+            (field64(8), field64(16))
+        };
+        let range = offset
+            .checked_add(len)
+            .filter(|&end| end <= file.len() as u64)
+            .map(|end| offset as usize..end as usize)
+            .ok_or_else(|| invalid("fat slice past end of file"))?;
+        slices.push(FatSlice {
+            cpu_type: field32(0),
+            range,
+        });
+    }
+    Ok(Some(slices))
+}
+
+/// Read the `size`-byte file from `body`, and return the `arch` slice when
+/// it is a universal binary containing one. Anything else returns `None`
+/// with the bytes consumed to find out left in `head`, the rest of the file
+/// still unread in `body`; a universal binary without `arch` comes back
+/// whole.
+pub(crate) fn thin(
+    body: &mut dyn Read,
+    size: u64,
+    arch: MachArch,
+    head: &mut Vec<u8>,
+) -> io::Result<Option<Vec<u8>>> {
+    body.take(size.min(8)).read_to_end(head)?;
+    if fat_header(head).is_none() {
+        return Ok(None);
+    }
+
+    let mut file = std::mem::take(head);
+    body.take(size - 8).read_to_end(&mut file)?;
+    let slices = fat_slices(&file)?.unwrap_or_default();
+    if let Some(slice) = slices
+        .iter()
+        .find(|slice| slice.cpu_type == arch.cpu_type())
+    {
+        return Ok(Some(file[slice.range.clone()].to_vec()));
+    }
+    debug!("Universal binary has no {arch:?} slice, keeping it whole");
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{ExtractOptions, PkgExtractor};
+    use std::fs;
+    use std::io::Cursor;
+
+    /// A 32-bit fat header with an x86_64 and an arm64 slice.
+    fn universal() -> Vec<u8> {
+        let mut fat = Vec::new();
+        fat.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+        fat.extend_from_slice(&2u32.to_be_bytes());
+        for (cpu, offset, len) in [(0x0100_0007u32, 64u32, 6u32), (0x0100_000c, 128, 5)] {
+            for field in [cpu, 3, offset, len, 4] {
+                fat.extend_from_slice(&field.to_be_bytes());
+            }
+        }
+        fat.resize(64, 0);
+        fat.extend_from_slice(b"x86-64");
+        fat.resize(128, 0);
+        fat.extend_from_slice(b"arm64");
+        fat
+    }
+
+    #[test]
+    fn thin_arch_keeps_one_slice_of_universal_binaries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let fat = universal();
+        // Starts like a fat header but is a Java class file (version 52).
+        let class = [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52, 1, 2, 3];
+        let cpio = cpio(&[
+            ("./bin/tool", 0o100755, &fat),
+            ("./lib/Main.class", 0o100644, &class),
+            ("./README", 0o100644, b"hi"),
+        ]);
+
+        PkgExtractor::new(Cursor::new(Vec::new()), Some(tmp.path().to_path_buf()))
+            .with_options(ExtractOptions {
+                thin_arch: Some(MachArch::Arm64),
+                ..Default::default()
+            })
+            .extract_cpio(&cpio, tmp.path())
+            .unwrap();
+
+        assert_eq!(fs::read(tmp.path().join("bin/tool")).unwrap(), b"arm64");
+        assert_eq!(fs::read(tmp.path().join("lib/Main.class")).unwrap(), class);
+        assert_eq!(fs::read(tmp.path().join("README")).unwrap(), b"hi");
+
+        let mut head = Vec::new();
+        let x86 = thin(&mut &fat[..], fat.len() as u64, MachArch::X86_64, &mut head);
+        assert_eq!(x86.unwrap().unwrap(), b"x86-64");
+    }
+
+    #[test]
+    fn fat_slices_past_the_end_are_errors() {
+        // A 64-bit fat header whose offset + size overflows a u64.
+        let mut fat64 = Vec::new();
+        fat64.extend_from_slice(&FAT_MAGIC_64.to_be_bytes());
+        fat64.extend_from_slice(&1u32.to_be_bytes());
+        fat64.extend_from_slice(&0x0100_000cu32.to_be_bytes());
+        fat64.extend_from_slice(&0u32.to_be_bytes());
+        fat64.extend_from_slice(&u64::MAX.to_be_bytes());
+        fat64.extend_from_slice(&2u64.to_be_bytes());
+        fat64.extend_from_slice(&[0; 8]);
+        let mut head = Vec::new();
+        let err = thin(
+            &mut &fat64[..],
+            fat64.len() as u64,
+            MachArch::Arm64,
+            &mut head,
+        );
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut truncated = universal();
+        truncated.truncate(20);
+        assert!(fat_slices(&truncated).is_err());
+        let mut short = universal();
+        short.truncate(130);
+        assert!(fat_slices(&short).is_err());
+    }
+}
@@ -5,18 +5,414 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use env_logger::Env;
-use log::debug;
-use std::{fs::File, io::BufReader, path::PathBuf};
+use log::{debug, info};
+#[cfg(feature = "signature")]
+use pkg_extractor::DetachedSignature;
+use pkg_extractor::{ExtractOptions, ExtractionStats, PathStyle, PkgExtractor};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt::Debug,
+    fs::{self, File},
+    io::{BufReader, Read, Seek},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "pkg-extractor", about = "Extract macOS .pkg files")]
 struct Opt {
+    /// Path to the .pkg, or `-` to read it from stdin
     #[structopt(parse(from_os_str))]
     pkg_path: PathBuf,
 
     #[structopt(short = "o", long = "output", parse(from_os_str))]
     output_dir: Option<PathBuf>,
+
+    /// Write the xar heap map (offset, length and checksum of every TOC
+    /// entry) as JSON to this file before extracting
+    #[structopt(long = "heap-json", parse(from_os_str))]
+    heap_json: Option<PathBuf>,
+
+    /// Skip everything below this payload directory (e.g. `usr/share/doc`);
+    /// may be repeated
+    #[structopt(long = "exclude-dir", number_of_values = 1)]
+    exclude_dirs: Vec<String>,
+
+    /// Skip the entries matched by this gitignore-style file (e.g. a
+    /// `.pkgignore`)
+    #[structopt(long = "ignore-file", parse(from_os_str))]
+    ignore_file: Option<PathBuf>,
+
+    /// Fail on anything that would otherwise only be logged as a warning
+    #[structopt(long = "warnings-as-errors")]
+    warnings_as_errors: bool,
+
+    /// Warn when a payload's cpio archive lacks its end-of-archive trailer
+    #[structopt(long = "check-trailer")]
+    check_trailer: bool,
+
+    /// Extract absolute entries below the output directory (`/usr/bin/foo`
+    /// becomes `usr/bin/foo`) instead of refusing them
+    #[structopt(long = "strip-leading-slash-only")]
+    strip_leading_slash: bool,
+
+    /// Rewrite absolute symlink targets relative to the link so they
+    /// resolve inside the extracted tree
+    #[structopt(long = "relative-symlinks")]
+    relative_symlinks: bool,
+
+    /// Write file contents at most this many bytes per second
+    #[structopt(long = "rate-limit")]
+    rate_limit: Option<u64>,
+
+    /// Write an NDJSON manifest of extracted entries to this file
+    #[structopt(long = "manifest", parse(from_os_str))]
+    manifest: Option<PathBuf>,
+
+    /// Print how much of the payload lives under each top-level directory
+    /// and exit without extracting
+    #[structopt(long = "breakdown")]
+    breakdown: bool,
+
+    /// Exit with status 0 if the package has an entry at this path and 1
+    /// otherwise, without extracting
+    #[structopt(long = "has")]
+    has: Option<String>,
+
+    /// Print a fingerprint of the extracted tree that is the same on every
+    /// platform, without extracting
+    #[structopt(long = "tree-digest")]
+    tree_digest: bool,
+
+    /// Print the name of every payload entry and exit without extracting
+    #[structopt(long = "list")]
+    list: bool,
+
+    /// Escape control characters in entry names, both when listing and
+    /// when extracting
+    #[structopt(long = "sanitize-control-chars")]
+    sanitize_control_chars: bool,
+
+    /// Only extract regular files that are new or changed compared to this
+    /// `Bom` from an earlier version of the package
+    #[structopt(long = "delta-baseline", parse(from_os_str))]
+    delta_baseline: Option<PathBuf>,
+
+    /// Extract each component into a directory named after its identifier
+    #[structopt(long = "per-component-dirs")]
+    per_component_dirs: bool,
+
+    /// Record symlinks in symlinks.json in the output directory instead of
+    /// creating them
+    #[structopt(long = "symlinks-sidecar")]
+    symlinks_sidecar: bool,
+
+    /// Keep only this architecture (x86_64, arm64) of universal binaries
+    #[structopt(long = "thin")]
+    thin: Option<pkg_extractor::MachArch>,
+
+    /// Write every component's metadata (auth, relocatable, scripts, ...) as
+    /// JSON to this file
+    #[structopt(long = "component-report", parse(from_os_str))]
+    component_report: Option<PathBuf>,
+
+    /// Write an `lsbom`-style listing of extracted entries to this file
+    #[structopt(long = "lsbom", parse(from_os_str))]
+    lsbom: Option<PathBuf>,
+
+    /// Write a JSON map of each extracted directory to the total size of the
+    /// files beneath it to this file
+    #[structopt(long = "size-index", parse(from_os_str))]
+    size_index: Option<PathBuf>,
+
+    /// In the manifest, pair each AppleDouble `._name` file with `name` and
+    /// list the metadata it carries
+    #[structopt(long = "pair-apple-double", requires = "manifest")]
+    pair_apple_double: bool,
+
+    /// Write a JSON report of the code signatures of every extracted Mach-O
+    /// binary to this file
+    #[cfg(feature = "codesign")]
+    #[structopt(long = "code-signatures", parse(from_os_str))]
+    code_signatures: Option<PathBuf>,
+
+    /// Refuse the package unless this raw Ed25519 signature of its SHA-256
+    /// digest matches `--public-key`
+    #[cfg(feature = "signature")]
+    #[structopt(
+        long = "detached-signature",
+        parse(from_os_str),
+        requires = "public-key"
+    )]
+    detached_signature: Option<PathBuf>,
+
+    /// Raw Ed25519 public key checking `--detached-signature`
+    #[cfg(feature = "signature")]
+    #[structopt(
+        long = "public-key",
+        parse(from_os_str),
+        requires = "detached-signature"
+    )]
+    public_key: Option<PathBuf>,
+
+    /// Also write every component's decompressed cpio, concatenated in
+    /// component order, to this file
+    #[structopt(long = "payload-image", parse(from_os_str))]
+    payload_image: Option<PathBuf>,
+
+    /// Log every extraction decision to this file (NDJSON)
+    #[structopt(long = "record-decisions", parse(from_os_str))]
+    record_decisions: Option<PathBuf>,
+
+    /// Fail unless extraction makes the decisions logged in this file by
+    /// `--record-decisions`
+    #[structopt(long = "replay-decisions", parse(from_os_str))]
+    replay_decisions: Option<PathBuf>,
+
+    /// Write each file to a temporary file and rename it into place once
+    /// complete
+    #[structopt(long = "atomic")]
+    atomic: bool,
+
+    /// Fail if the output directory exists and is not empty
+    #[structopt(long = "require-empty-output")]
+    require_empty_output: bool,
+
+    /// If the output directory exists, extract into `<dir>-2` (or `-3`, ...)
+    /// instead
+    #[structopt(long = "avoid-clobber-dir")]
+    avoid_clobber_dir: bool,
+
+    /// Write every file gzip-compressed as `<path>.gz`
+    #[structopt(long = "gzip-files")]
+    gzip_files: bool,
+
+    /// Store files by SHA-256 as `ab/cd/abcd...` objects, with a
+    /// cas-manifest.json mapping payload paths to them
+    #[structopt(long = "content-addressed")]
+    content_addressed: bool,
+
+    /// Lay each component out as its Bom declares (modes, paths),
+    /// reporting payload entries that disagree
+    #[structopt(long = "bom-layout")]
+    bom_layout: bool,
+
+    /// Create temporary files (stdin spill, `--atomic`,
+    /// `--content-addressed`) in this directory
+    #[structopt(long = "temp-dir", parse(from_os_str))]
+    temp_dir: Option<PathBuf>,
+
+    /// Print a JSON summary of the run (status and error, files, bytes,
+    /// skipped, warnings, elapsed time, output directory) to stderr when
+    /// done, whether it succeeded or not
+    #[structopt(long = "summary")]
+    summary: bool,
+
+    /// Write the `--summary` JSON to this file instead of stderr
+    #[structopt(long = "summary-file", parse(from_os_str))]
+    summary_file: Option<PathBuf>,
+
+    /// How reports spell extracted paths: `relative` (to the output
+    /// directory) or `absolute`
+    #[structopt(long = "report-paths", default_value = "relative")]
+    report_paths: PathStyle,
+
+    /// Write the payload into this SQLite database (one row per entry)
+    /// instead of the output directory
+    #[cfg(feature = "rusqlite")]
+    #[structopt(long = "sqlite", parse(from_os_str))]
+    sqlite: Option<PathBuf>,
+
+    /// Write the payload as a tarball to this file, or to stdout with `-`,
+    /// instead of the output directory
+    #[cfg(feature = "tar")]
+    #[structopt(long = "tar", parse(from_os_str))]
+    tar: Option<PathBuf>,
+}
+
+impl Opt {
+    fn extract_options(&self) -> ExtractOptions {
+        ExtractOptions {
+            exclude_dirs: self.exclude_dirs.clone(),
+            warnings_as_errors: self.warnings_as_errors,
+            strip_leading_slash: self.strip_leading_slash,
+            check_trailer: self.check_trailer,
+            sanitize_control_chars: self.sanitize_control_chars,
+            relative_symlinks: self.relative_symlinks,
+            thin_arch: self.thin,
+            symlinks_sidecar: self.symlinks_sidecar,
+            atomic_writes: self.atomic,
+            require_empty_output: self.require_empty_output,
+            avoid_clobber_dir: self.avoid_clobber_dir,
+            gzip_files: self.gzip_files,
+            content_addressed: self.content_addressed,
+            bom_layout: self.bom_layout,
+            rate_limit: self.rate_limit,
+            manifest: self.manifest.clone(),
+            lsbom: self.lsbom.clone(),
+            size_index: self.size_index.clone(),
+            pair_apple_double: self.pair_apple_double,
+            #[cfg(feature = "codesign")]
+            code_signatures: self.code_signatures.clone(),
+            component_report: self.component_report.clone(),
+            per_component_dirs: self.per_component_dirs,
+            delta_baseline: self.delta_baseline.clone(),
+            payload_image: self.payload_image.clone(),
+            record_decisions: self.record_decisions.clone(),
+            replay_decisions: self.replay_decisions.clone(),
+            report_paths: self.report_paths,
+            ..Default::default()
+        }
+    }
+}
+
+/// Do what `opt` asks of `extractor`. Returns the stats of an extraction
+/// to disk, `None` for the other modes.
+fn run<R: Read + Seek + Debug>(
+    extractor: PkgExtractor<R>,
+    opt: &Opt,
+) -> Result<Option<ExtractionStats>, Box<dyn Error>> {
+    let options = ExtractOptions {
+        #[cfg(feature = "signature")]
+        detached_signature: match (&opt.detached_signature, &opt.public_key) {
+            (Some(signature), Some(key)) => Some(DetachedSignature::load(signature, key)?),
+            _ => None,
+        },
+        ..opt.extract_options()
+    };
+    let mut extractor = extractor.with_options(options);
+    if let Some(dir) = &opt.temp_dir {
+        extractor = extractor.with_temp_dir(dir.clone());
+    }
+    if let Some(path) = &opt.ignore_file {
+        extractor = extractor.with_ignore_file(path)?;
+    }
+    if let Some(path) = &opt.heap_json {
+        let heap = extractor.heap_map()?;
+        fs::write(path, serde_json::to_vec_pretty(&heap)?)?;
+        info!("Wrote {} heap entries to {}", heap.len(), path.display());
+    }
+
+    if let Some(path) = &opt.has {
+        let found = extractor.contains(path)?;
+        info!("{path} {}", if found { "found" } else { "not found" });
+        std::process::exit(if found { 0 } else { 1 });
+    }
+
+    if opt.tree_digest {
+        println!("{}", extractor.tree_digest()?);
+        return Ok(None);
+    }
+
+    if opt.list {
+        for name in extractor.list_entries()? {
+            println!("{name}");
+        }
+        return Ok(None);
+    }
+
+    if opt.breakdown {
+        let mut breakdown: Vec<_> = extractor.size_breakdown()?.into_iter().collect();
+        breakdown.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (dir, size) in breakdown {
+            println!("{size:>14}  {dir}");
+        }
+        return Ok(None);
+    }
+
+    #[cfg(feature = "rusqlite")]
+    if let Some(db) = &opt.sqlite {
+        extractor.extract_to_sqlite(db)?;
+        return Ok(None);
+    }
+
+    #[cfg(feature = "tar")]
+    if let Some(dest) = &opt.tar {
+        use std::io::Write;
+        // Logs go to stderr, so stdout carries nothing but the tarball.
+        if dest.as_os_str() == "-" {
+            extractor
+                .extract_to_tar(std::io::stdout().lock())?
+                .flush()?;
+        } else {
+            extractor
+                .extract_to_tar(std::io::BufWriter::new(File::create(dest)?))?
+                .flush()?;
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(extractor.extract()?))
+}
+
+/// The `--summary` report of a run, successful or not.
+#[derive(Serialize)]
+struct Summary<'a> {
+    /// `ok` or `error`.
+    status: &'static str,
+    /// What made the run fail.
+    error: Option<String>,
+    // The extraction's stats: `null` when the run failed, or did not
+    // extract to disk (`--tar`, `--sqlite`, inspection).
+    files: Option<u64>,
+    bytes: Option<u64>,
+    skipped: Option<u64>,
+    failed: Option<usize>,
+    warnings: Vec<&'a str>,
+    elapsed_secs: f64,
+    output_dir: Option<&'a Path>,
+}
+
+impl<'a> Summary<'a> {
+    fn new(result: &'a Result<Option<ExtractionStats>, Box<dyn Error>>, elapsed: Duration) -> Self {
+        let stats = result.as_ref().ok().and_then(Option::as_ref);
+        Self {
+            status: if result.is_ok() { "ok" } else { "error" },
+            error: result.as_ref().err().map(|e| e.to_string()),
+            files: stats.map(|stats| stats.files),
+            bytes: stats.map(|stats| stats.bytes),
+            skipped: stats.map(|stats| stats.skipped),
+            failed: stats.map(|stats| stats.failed_files.len()),
+            warnings: stats.map_or_else(Vec::new, |stats| {
+                stats.warnings.iter().map(|w| w.message.as_str()).collect()
+            }),
+            elapsed_secs: elapsed.as_secs_f64(),
+            output_dir: stats.map(|stats| stats.output_dir.as_path()),
+        }
+    }
+
+    /// Write the summary where `--summary-file` says, or to stderr.
+    fn write(&self, opt: &Opt) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string(self)?;
+        match &opt.summary_file {
+            Some(path) => fs::write(path, json + "\n")?,
+            None => eprintln!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+/// Open the package `opt` names and run on it.
+fn open_and_run(opt: &Opt) -> Result<Option<ExtractionStats>, Box<dyn Error>> {
+    if opt.pkg_path.as_os_str() == "-" {
+        debug!("Reading package from stdin");
+        let stdin = std::io::stdin().lock();
+        let extractor = match &opt.temp_dir {
+            Some(dir) => PkgExtractor::from_stream_in(stdin, opt.output_dir.clone(), dir.clone())?,
+            None => PkgExtractor::from_stream(stdin, opt.output_dir.clone())?,
+        };
+        return run(extractor, opt);
+    }
+
+    debug!("Opening package file: {}", opt.pkg_path.display());
+    let file = File::open(&opt.pkg_path)?;
+    let reader = BufReader::new(file);
+
+    let extractor =
+        PkgExtractor::new_with_file_path(reader, opt.output_dir.clone(), opt.pkg_path.clone());
+    run(extractor, opt)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -35,10 +431,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse command line arguments
     let opt = Opt::from_args();
 
-    // Create and run extractor
-    debug!("Opening package file: {}", opt.pkg_path.display());
-    let file = File::open(&opt.pkg_path)?;
-    let reader = BufReader::new(file);
-
-    pkg_extractor::PkgExtractor::new_with_file_path(reader, opt.output_dir, opt.pkg_path).extract()
+    let started = Instant::now();
+    let result = open_and_run(&opt);
+    // Written whatever the outcome: a failed run is the one an
+    // orchestrator most needs to hear about.
+    let summary = if opt.summary || opt.summary_file.is_some() {
+        Summary::new(&result, started.elapsed()).write(&opt)
+    } else {
+        Ok(())
+    };
+    result?;
+    summary
 }
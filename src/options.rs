@@ -0,0 +1,458 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Knobs controlling what [`crate::PkgExtractor`] writes and where.
+//!
+//! Every field defaults to the historical behaviour (extract every payload
+//! entry into `output_dir`, nothing else), so callers only spell out what
+//! they want to change:
+//!
+//! ```
+//! use pkg_extractor::ExtractOptions;
+//!
+//! let options = ExtractOptions {
+//!     extract_scripts: true,
+//!     ..Default::default()
+//! };
+//! # let _ = options;
+//! ```
+
+use crate::{FileType, PathStyle, ResumeCursor};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Options for [`crate::PkgExtractor::with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Also unpack each component's `Scripts` archive (pre/postinstall and
+    /// friends). A component package's scripts land in
+    /// `<output_dir>/Scripts/`; for product packages every component gets
+    /// its own `<output_dir>/Scripts/<identifier>/` so identically named
+    /// scripts from different components don't overwrite each other.
+    pub extract_scripts: bool,
+
+    /// Only extract regular files of at least this many bytes.
+    pub min_size: Option<u64>,
+
+    /// Only extract regular files of at most this many bytes.
+    pub max_size: Option<u64>,
+
+    /// Skip every entry at or below these directories (`usr/share/doc`,
+    /// `./Library/Caches/`, ...), matched as whole path components against
+    /// the entry name without its `./`. Applies to listings too.
+    pub exclude_dirs: Vec<String>,
+
+    /// Skip every entry this gitignore-style file matches, e.g. one read
+    /// from a `.pkgignore` with [`crate::PkgExtractor::with_ignore_file`].
+    /// Applies to listings too.
+    pub ignore: Option<crate::IgnoreFile>,
+
+    /// Fail instead of logging a warning when something looks off (missing
+    /// Payload, entries escaping the output directory, a component that
+    /// failed to extract, ...). The returned error carries the warning text.
+    pub warnings_as_errors: bool,
+
+    /// Extract absolute entry names (`/usr/bin/foo`) relative to the output
+    /// directory (`usr/bin/foo`) instead of refusing them, matching what
+    /// `tar`/`cpio` do by default. `..` components are still refused.
+    pub strip_leading_slash: bool,
+
+    /// Rewrite absolute symlink targets (`/Applications/Foo.app/...`) as
+    /// paths relative to the link, so they resolve inside the extracted
+    /// tree rather than on the host.
+    pub relative_symlinks: bool,
+
+    /// Record symlinks in [`crate::SYMLINKS_SIDECAR`] in the output
+    /// directory (link, relative to it, to target) instead of creating
+    /// them, for Windows hosts where creating symlinks needs privileges,
+    /// so they can be recreated later or elsewhere.
+    pub symlinks_sidecar: bool,
+
+    /// Throttle writing file contents to at most this many bytes per second,
+    /// e.g. to avoid saturating a network filesystem.
+    pub rate_limit: Option<u64>,
+
+    /// Write a newline-delimited JSON manifest of every extracted entry
+    /// (`path`, `type`, `size`, `mode` and, for symlinks, `target`) to this
+    /// file once extraction finishes.
+    pub manifest: Option<PathBuf>,
+
+    /// Write a listing of every extracted entry in the format `lsbom`
+    /// prints (path, mode, uid/gid, size, CRC, symlink target) to this file
+    /// once extraction finishes, for diffing against `lsbom` output.
+    pub lsbom: Option<PathBuf>,
+
+    /// Write every component's metadata ([`crate::ComponentInfo`]: version,
+    /// `auth`, `relocatable`, whether it runs scripts, ...) as a JSON array
+    /// to this file.
+    pub component_report: Option<PathBuf>,
+
+    /// Write a JSON object mapping every directory (relative to the output
+    /// directory, `.` for the output directory itself) to the total size of
+    /// the regular files beneath it, at any depth, to this file once
+    /// extraction finishes -- e.g. `sizes.json` for a treemap.
+    pub size_index: Option<PathBuf>,
+
+    /// Pair every AppleDouble `._name` file with its sibling `name` in the
+    /// `manifest`: `name`'s line gets an `apple_double` object naming the
+    /// `._name` file and the extended attributes, Finder info and resource
+    /// fork it carries, so the macOS metadata of a file can be found and
+    /// restored from it. The `._name` files are still written as is, and
+    /// one that cannot be parsed is left unpaired with a warning.
+    ///
+    /// Only takes effect together with `manifest`.
+    pub pair_apple_double: bool,
+
+    /// Write a JSON array of the code signatures ([`crate::CodeSignature`]:
+    /// signing identifier, team ID, whether ad-hoc, whether the code still
+    /// matches its page hashes) of every extracted Mach-O to this file, one
+    /// element per architecture slice. Binaries whose signature cannot be
+    /// parsed are left out with a [`crate::WarningKind::UnparsableFile`]
+    /// warning.
+    #[cfg(feature = "codesign")]
+    pub code_signatures: Option<PathBuf>,
+
+    /// Also write the decompressed cpio of every component's Payload to
+    /// this file, byte for byte, for forensic re-analysis with other tools.
+    /// Streams are concatenated in component (archive) order and each keeps
+    /// its own `TRAILER!!!`, so readers must carry on past every trailer.
+    pub payload_image: Option<PathBuf>,
+
+    /// Log every extraction decision (entry, type, action taken, target
+    /// path) to this file as newline-delimited JSON.
+    pub record_decisions: Option<PathBuf>,
+
+    /// Check that this run makes exactly the decisions recorded in this
+    /// file by `record_decisions`, failing on the first difference. Catches
+    /// regressions in name handling and filtering.
+    pub replay_decisions: Option<PathBuf>,
+
+    /// How reports such as the manifest spell extracted paths.
+    pub report_paths: PathStyle,
+
+    /// Give every extracted file and directory a fixed mode instead of the
+    /// one recorded in the payload, e.g. to strip setuid or world-writable
+    /// bits before sharing the contents. Symlinks are left alone.
+    pub force_mode: Option<ForceMode>,
+
+    /// Longest path component, in bytes, an entry may have. Most
+    /// filesystems refuse components over 255 bytes, and without a cap such
+    /// entries fail with an unhelpful OS error. `None` applies no check.
+    pub max_component_len: Option<usize>,
+
+    /// What to do with entries exceeding `max_component_len`.
+    pub long_components: LongComponentPolicy,
+
+    /// What to do with entries by extension, e.g. `"log" => Skip` or
+    /// `"plist" => Route("plists")`. Keys are matched case-insensitively,
+    /// with or without the leading dot, against every component of the
+    /// entry's path. The outermost match wins, so `"dSYM" => Skip` drops
+    /// whole `.dSYM` bundles whatever they contain. Where several keys
+    /// match the same extension (`"log"`, `".log"`, `"LOG"`), the first in
+    /// key order wins.
+    ///
+    /// Size filters are applied before this map. A `Route` takes precedence
+    /// over `component_dir_map` for the entries it matches.
+    pub extension_actions: BTreeMap<String, EntryAction>,
+
+    /// Extract the payload of the component with a given identifier into a
+    /// directory of its own instead of the output directory. Paths are
+    /// taken relative to the output directory; one that would leave it
+    /// (absolute, or with a `..` component) is refused with a warning and
+    /// the component's payload is not extracted. Components not listed
+    /// extract as usual.
+    pub component_dir_map: BTreeMap<String, PathBuf>,
+
+    /// Extract the payload of every component not in `component_dir_map`
+    /// into a directory named after its identifier. Identifiers are made
+    /// into safe single-segment names first (`/`, `:` and other characters
+    /// unsafe in a path become `_`, clashes get a `-2`, `-3`, ... suffix);
+    /// [`crate::ExtractionStats::component_dirs`] records the mapping.
+    pub per_component_dirs: bool,
+
+    /// Warn when a Payload or Scripts archive lacks the `TRAILER!!!` entry
+    /// that ends a complete cpio stream, a sign the package was truncated.
+    pub check_trailer: bool,
+
+    /// Skip an entry that cannot be written (permission denied, a name the
+    /// filesystem rejects, ...) instead of aborting, and list it in
+    /// [`crate::ExtractionStats::failed_files`].
+    pub continue_on_file_error: bool,
+
+    /// Start a component over, at most this many times, when extracting it
+    /// fails with a transient I/O error (timeout, dropped connection, ...)
+    /// from a flaky remote reader. Anything else fails straight away.
+    pub component_retries: u32,
+
+    /// Replace control characters (newline, tab, escape, ...) in entry
+    /// names with a visible `\xNN` escape, both in the names written to
+    /// disk and in listings, so a hostile name can neither inject terminal
+    /// escape sequences nor create a confusing file. Renamed entries are
+    /// logged, and the manifest records their original name. Symlink
+    /// targets are created byte for byte, but reports show them escaped the
+    /// same way, non-UTF-8 bytes included.
+    pub sanitize_control_chars: bool,
+
+    /// Path to the `Bom` of an earlier version of the package: regular
+    /// files whose path, size and CRC all match an entry in it are not
+    /// written, so only new and changed files land in the output directory
+    /// (and in [`crate::ExtractionStats::delta`]). Directories and symlinks
+    /// are always extracted.
+    pub delta_baseline: Option<PathBuf>,
+
+    /// Keep only this architecture's slice of Mach-O universal binaries,
+    /// like `lipo -thin`. Other files, and universal binaries without that
+    /// slice, are written unchanged.
+    pub thin_arch: Option<crate::MachArch>,
+
+    /// Write every file and directory first, then apply their payload
+    /// modes and mtimes in one pass at the end, deepest paths first, so
+    /// read-only directories (`0o500`, ...) can still be filled. Directory
+    /// modes are otherwise only applied with `force_mode`. Ownership is
+    /// never applied.
+    pub defer_metadata: bool,
+
+    /// Refuse to extract or inspect the package unless it matches this
+    /// detached signature, checked once before the package is first parsed.
+    #[cfg(feature = "signature")]
+    pub detached_signature: Option<crate::DetachedSignature>,
+
+    /// Pipe regular files through this external command and write its
+    /// output instead of their contents.
+    #[cfg(feature = "transform")]
+    pub transform: Option<crate::TransformCommand>,
+
+    /// Skip everything before this position, as reported in an earlier
+    /// run's [`crate::ExtractionStats::cursor`].
+    pub resume_from: Option<ResumeCursor>,
+
+    /// Write each regular file to a temporary file first and rename it into
+    /// place once complete, so an interrupted run never leaves a truncated
+    /// file at its final path. The temporary file lives next to its
+    /// destination unless [`crate::PkgExtractor::with_temp_dir`] says
+    /// otherwise. Symlinks are likewise made under a temporary name and
+    /// renamed over whatever file or symlink was at their path, rather than
+    /// replacing it in two steps.
+    pub atomic_writes: bool,
+
+    /// Fail before writing anything when the output directory already
+    /// exists and holds anything, rather than merging into it. Checked once
+    /// per extractor, so components extracted one by one, through it or
+    /// its [`crate::ComponentHandle`]s, can share the directory.
+    pub require_empty_output: bool,
+
+    /// When the output directory already exists, extract into the first of
+    /// `<dir>-2`, `<dir>-3`, ... that does not, leaving the existing one
+    /// untouched. [`crate::ExtractionStats::output_dir`] says which was
+    /// used. The directory is picked once per extractor: components
+    /// extracted one by one, through it or its
+    /// [`crate::ComponentHandle`]s, all land in the same one.
+    pub avoid_clobber_dir: bool,
+
+    /// Write every regular file gzip-compressed, as `<path>.gz`; the
+    /// manifest records the payload name each `.gz` came from under
+    /// `original`. Directories and symlinks are extracted as usual.
+    pub gzip_files: bool,
+
+    /// Store regular files in a flat content-addressed layout instead of
+    /// their payload paths: each distinct content is written once, as
+    /// `ab/cd/abcd...` named by its SHA-256, and [`crate::CAS_MANIFEST`] maps
+    /// every path to its object. Directories and symlinks are only recorded
+    /// in that manifest.
+    pub content_addressed: bool,
+
+    /// Treat each component's `Bom` as the authority on its layout: payload
+    /// entries get the BOM's mode, entries the BOM does not list (or lists
+    /// with another type) are skipped, and directories only the BOM lists
+    /// are created. Every discrepancy is reported as a
+    /// [`crate::WarningKind::BomMismatch`] warning; mode differences are
+    /// resolved, so `warnings_as_errors` does not fail on them. Directory
+    /// modes are applied once their contents are written.
+    pub bom_layout: bool,
+}
+
+/// Handling of path components longer than
+/// [`ExtractOptions::max_component_len`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LongComponentPolicy {
+    /// Fail, naming the offending component.
+    #[default]
+    Error,
+    /// Shorten the component to a truncated prefix plus a hash of the full
+    /// name, keeping a short extension. The manifest records the original
+    /// entry name under `original`.
+    Shorten,
+}
+
+/// Action for entries matched by [`ExtractOptions::extension_actions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryAction {
+    /// Extract as usual.
+    Extract,
+    /// Leave the entry out.
+    Skip,
+    /// Extract the entry below this directory instead of the output
+    /// directory, keeping its payload path. The directory is taken
+    /// relative to the output directory; one that would leave it is
+    /// refused with a warning and the entry is not extracted.
+    Route(PathBuf),
+}
+
+/// Fixed permission bits for [`ExtractOptions::force_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForceMode {
+    /// Mode of regular files, e.g. `0o644`.
+    pub file: u32,
+    /// Mode of directories, e.g. `0o755`.
+    pub dir: u32,
+}
+
+impl ExtractOptions {
+    /// Whether a regular file of `size` bytes passes `min_size`/`max_size`.
+    pub(crate) fn accepts_size(&self, size: u64) -> bool {
+        !(self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max))
+    }
+
+    /// Whether the entry `name`, of cpio mode `mode`, lies in one of
+    /// `exclude_dirs` or is matched by the `ignore` file.
+    pub(crate) fn excludes(&self, name: &str, mode: u32) -> bool {
+        let name = crate::bom::bom_key(name);
+        let is_dir = FileType::from_mode(mode) == FileType::Directory;
+        if let Some(ignore) = &self.ignore {
+            if !name.is_empty() && ignore.is_ignored(name, is_dir) {
+                return true;
+            }
+        }
+        self.exclude_dirs.iter().any(|dir| {
+            let dir = crate::bom::bom_key(dir).trim_end_matches('/');
+            !dir.is_empty()
+                && name
+                    .strip_prefix(dir)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// The mode an entry recorded with `mode` is extracted with: the
+    /// payload's own, or the file type bits combined with `force_mode`.
+    pub(crate) fn entry_mode(&self, mode: u32) -> u32 {
+        let Some(force) = self.force_mode else {
+            return mode;
+        };
+        let perm = match FileType::from_mode(mode) {
+            FileType::Regular => force.file,
+            FileType::Directory => force.dir,
+            FileType::Symlink | FileType::Other => return mode,
+        };
+        (mode & 0o170000) | (perm & 0o7777)
+    }
+
+    /// The `extension_actions` entry for `name`: the outermost component
+    /// of the path whose extension is mapped.
+    pub(crate) fn extension_action(&self, name: &str) -> Option<&EntryAction> {
+        if self.extension_actions.is_empty() {
+            return None;
+        }
+        name.split('/').find_map(|component| {
+            let (_, ext) = component.rsplit_once('.')?;
+            self.extension_actions
+                .iter()
+                .find(|(key, _)| key.trim_start_matches('.').eq_ignore_ascii_case(ext))
+                .map(|(_, action)| action)
+        })
+    }
+
+    /// `name` with control characters escaped when `sanitize_control_chars`
+    /// is set, borrowed when there is nothing to escape.
+    pub(crate) fn sanitize_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if !self.sanitize_control_chars || !name.chars().any(char::is_control) {
+            return Cow::Borrowed(name);
+        }
+        let mut sanitized = String::with_capacity(name.len() + 8);
+        for c in name.chars() {
+            if c.is_control() {
+                sanitized.push_str(&format!("\\x{:02x}", c as u32));
+            } else {
+                sanitized.push(c);
+            }
+        }
+        Cow::Owned(sanitized)
+    }
+
+    /// How bytes that may not be UTF-8, such as a symlink target, appear in
+    /// reports: with `sanitize_control_chars`, invalid bytes become `\xNN`
+    /// escapes like control characters do; otherwise they are replaced by
+    /// U+FFFD.
+    pub(crate) fn display_bytes<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        if !self.sanitize_control_chars {
+            return String::from_utf8_lossy(bytes);
+        }
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return self.sanitize_name(text);
+        }
+        let mut shown = String::with_capacity(bytes.len() + 8);
+        for chunk in bytes.utf8_chunks() {
+            shown.push_str(&self.sanitize_name(chunk.valid()));
+            for byte in chunk.invalid() {
+                shown.push_str(&format!("\\x{byte:02x}"));
+            }
+        }
+        Cow::Owned(shown)
+    }
+
+    /// Apply `max_component_len` to a `/`-separated entry name: borrowed
+    /// when every component fits, shortened or refused otherwise.
+    pub(crate) fn cap_components<'a>(&self, name: &'a str) -> Result<Cow<'a, str>, Box<dyn Error>> {
+        let Some(max) = self.max_component_len else {
+            return Ok(Cow::Borrowed(name));
+        };
+        if name.split('/').all(|c| c.len() <= max) {
+            return Ok(Cow::Borrowed(name));
+        }
+
+        let mut capped = Vec::new();
+        for component in name.split('/') {
+            if component.len() <= max {
+                capped.push(Cow::Borrowed(component));
+                continue;
+            }
+            match self.long_components {
+                LongComponentPolicy::Error => {
+                    return Err(format!(
+                        "Path component {component:?} of {name:?} is {} bytes, over the {max}-byte limit",
+                        component.len()
+                    )
+                    .into());
+                }
+                LongComponentPolicy::Shorten => capped.push(Cow::Owned(shorten(component, max)?)),
+            }
+        }
+        Ok(Cow::Owned(capped.join("/")))
+    }
+}
+
+/// `<prefix>~<16 hex digits of FNV-1a>[.ext]`, at most `max` bytes. The
+/// hash is of the whole component, so distinct long names sharing a
+/// prefix stay distinct.
+fn shorten(component: &str, max: usize) -> Result<String, Box<dyn Error>> {
+    let hash = component.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    let hash = format!("~{hash:016x}");
+    let ext = match component.rfind('.') {
+        Some(dot) if component.len() - dot <= 16 => &component[dot..],
+        _ => "",
+    };
+    let Some(mut keep) = max.checked_sub(hash.len() + ext.len()) else {
+        return Err(format!("cannot shorten {component:?} to {max} bytes").into());
+    };
+    while !component.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    Ok(format!("{}{hash}{ext}", &component[..keep]))
+}
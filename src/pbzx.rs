@@ -22,10 +22,12 @@
 
 use log::{debug, info};
 use lzma_rust2::XzReader;
-use std::io::{Cursor, Read, Write};
+use std::io::{self, Cursor, Read, Write};
 
 pub struct PbzxReader<R: Read> {
     reader: R,
+    /// What is left of the chunk being read through [`Read`].
+    chunk: Cursor<Vec<u8>>,
 }
 
 impl<R: Read> PbzxReader<R> {
@@ -45,7 +47,10 @@ impl<R: Read> PbzxReader<R> {
             u64::from_be_bytes(block_size_bytes)
         );
 
-        Ok(Self { reader })
+        Ok(Self {
+            reader,
+            chunk: Cursor::new(Vec::new()),
+        })
     }
 
     pub fn decompress_to<W: Write>(
@@ -55,47 +60,64 @@ impl<R: Read> PbzxReader<R> {
         let mut total_chunks = 0usize;
         let mut total_bytes = 0usize;
 
-        loop {
-            let mut uncompressed_size_bytes = [0u8; 8];
-            match self.reader.read_exact(&mut uncompressed_size_bytes) {
-                Ok(()) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(e.into()),
-            }
-            let uncompressed_size = u64::from_be_bytes(uncompressed_size_bytes);
-
-            let mut compressed_size_bytes = [0u8; 8];
-            self.reader.read_exact(&mut compressed_size_bytes)?;
-            let compressed_size = u64::from_be_bytes(compressed_size_bytes);
-
-            let mut chunk_data = vec![0u8; compressed_size as usize];
-            self.reader.read_exact(&mut chunk_data)?;
-
-            debug!(
-                "chunk {}: uncompressed={} compressed={}",
-                total_chunks + 1,
-                uncompressed_size,
-                compressed_size
-            );
-
-            if compressed_size < uncompressed_size {
-                // Each pbzx chunk is exactly one xz stream; no multi-stream.
-                let mut decoder = XzReader::new(Cursor::new(chunk_data), false);
-                let mut decompressed = Vec::with_capacity(uncompressed_size as usize);
-                decoder.read_to_end(&mut decompressed)?;
-                output.write_all(&decompressed)?;
-                total_bytes += decompressed.len();
-            } else {
-                output.write_all(&chunk_data)?;
-                total_bytes += chunk_data.len();
-            }
-
+        while let Some(chunk) = self.next_chunk()? {
+            output.write_all(&chunk)?;
+            total_bytes += chunk.len();
             total_chunks += 1;
         }
 
         info!("decoded {} chunks, {} bytes", total_chunks, total_bytes);
         Ok(())
     }
+
+    /// Decode the next chunk, or `None` at the end of the stream.
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut uncompressed_size_bytes = [0u8; 8];
+        match self.reader.read_exact(&mut uncompressed_size_bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let uncompressed_size = u64::from_be_bytes(uncompressed_size_bytes);
+
+        let mut compressed_size_bytes = [0u8; 8];
+        self.reader.read_exact(&mut compressed_size_bytes)?;
+        let compressed_size = u64::from_be_bytes(compressed_size_bytes);
+
+        let mut chunk_data = vec![0u8; compressed_size as usize];
+        self.reader.read_exact(&mut chunk_data)?;
+
+        debug!(
+            "chunk: uncompressed={} compressed={}",
+            uncompressed_size, compressed_size
+        );
+
+        if compressed_size < uncompressed_size {
+            // Each pbzx chunk is exactly one xz stream; no multi-stream.
+            let mut decoder = XzReader::new(Cursor::new(chunk_data), false);
+            let mut decompressed = Vec::with_capacity(uncompressed_size as usize);
+            decoder.read_to_end(&mut decompressed)?;
+            Ok(Some(decompressed))
+        } else {
+            Ok(Some(chunk_data))
+        }
+    }
+}
+
+/// Decodes one chunk at a time, as it is read.
+impl<R: Read> Read for PbzxReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.chunk.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.next_chunk()? {
+                Some(chunk) => self.chunk = Cursor::new(chunk),
+                None => return Ok(0),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -198,4 +220,29 @@ mod tests {
         let mut out = Vec::new();
         assert!(reader.decompress_to(&mut out).is_err());
     }
+
+    #[test]
+    fn reads_chunk_by_chunk() {
+        let compressible = vec![b'a'; 4096];
+        let compressed = xz_encode(&compressible);
+        let stream = build(&[
+            (b"first ".as_slice(), b"first ".as_slice()),
+            (b"".as_slice(), b"".as_slice()),
+            (compressible.as_slice(), compressed.as_slice()),
+        ]);
+        let mut reader = PbzxReader::new(Cursor::new(&stream)).unwrap();
+
+        let mut out = Vec::new();
+        let mut buf = [0u8; 100];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(out, decode(&stream));
+        assert_eq!(&out[..6], b"first ");
+        assert_eq!(out.len(), 6 + 4096);
+    }
 }
@@ -0,0 +1,251 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reports written alongside an extraction, and the one rule they share for
+//! how paths of extracted entries are spelled ([`PathStyle`]).
+
+use crate::apple_double::AppleDouble;
+use crate::ComponentInfo;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How reports spell the paths of extracted entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Relative to the output directory, e.g. `usr/bin/foo`.
+    #[default]
+    Relative,
+    /// Absolute, e.g. `/tmp/out/usr/bin/foo`, even when the output
+    /// directory was given as a relative path.
+    Absolute,
+}
+
+impl PathStyle {
+    /// Spell `path`, which lives under `output_dir`, in this style.
+    pub fn render(self, output_dir: &Path, path: &Path) -> String {
+        let rendered = match self {
+            PathStyle::Relative => path
+                .strip_prefix(output_dir)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| path.to_path_buf()),
+            PathStyle::Absolute => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+        };
+        rendered.to_string_lossy().into_owned()
+    }
+}
+
+impl FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relative" => Ok(PathStyle::Relative),
+            "absolute" => Ok(PathStyle::Absolute),
+            _ => Err(format!("unknown path style {s:?} (relative, absolute)")),
+        }
+    }
+}
+
+/// One extracted entry, as recorded for the manifest.
+#[derive(Debug, Clone)]
+pub(crate) struct ManifestEntry {
+    pub path: PathBuf,
+    pub kind: &'static str,
+    pub size: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// POSIX `cksum` CRC of the contents (of the target, for symlinks).
+    pub checksum: Option<u32>,
+    pub link_target: Option<String>,
+    /// The payload's entry name, when it had to be changed on extraction.
+    pub original: Option<String>,
+    /// With `pair_apple_double`, the `._name` file describing this entry.
+    pub apple_double: Option<AppleDouble>,
+}
+
+#[derive(Serialize)]
+struct ManifestLine<'a> {
+    path: String,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    size: u64,
+    mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    apple_double: Option<&'a AppleDouble>,
+}
+
+/// Write `entries` to `dest` as newline-delimited JSON, one object per
+/// entry: `{"path", "type", "size", "mode", "target"?, "original"?,
+/// "apple_double"?}`.
+pub(crate) fn write_manifest(
+    dest: &Path,
+    entries: &[ManifestEntry],
+    output_dir: &Path,
+    style: PathStyle,
+) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(dest)?);
+    for entry in entries {
+        let line = ManifestLine {
+            path: style.render(output_dir, &entry.path),
+            kind: entry.kind,
+            size: entry.size,
+            mode: format!("{:04o}", entry.mode & 0o7777),
+            target: entry.link_target.as_deref(),
+            original: entry.original.as_deref(),
+            apple_double: entry.apple_double.as_ref(),
+        };
+        serde_json::to_writer(&mut out, &line)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Write `entries` to `dest` the way `lsbom` prints a BOM: one line per
+/// entry, tab-separated, `./`-rooted path, octal mode and `uid/gid`, then
+/// size and CRC for files, plus the target for symlinks.
+pub(crate) fn write_lsbom(
+    dest: &Path,
+    entries: &[ManifestEntry],
+    output_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(dest)?);
+    for entry in entries {
+        let path = PathStyle::Relative.render(output_dir, &entry.path);
+        write!(
+            out,
+            "./{}\t{:o}\t{}/{}",
+            path.replace('\\', "/"),
+            entry.mode,
+            entry.uid,
+            entry.gid
+        )?;
+        if let Some(checksum) = entry.checksum {
+            write!(out, "\t{}\t{checksum}", entry.size)?;
+        }
+        if let Some(target) = &entry.link_target {
+            write!(out, "\t{target}")?;
+        }
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Write `components` to `dest` as a JSON array, flags included, so
+/// reviewers can spot components installing as root or running scripts.
+pub(crate) fn write_components(
+    dest: &Path,
+    components: &[ComponentInfo],
+) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(dest)?);
+    serde_json::to_writer_pretty(&mut out, components)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write to `dest` a JSON object mapping each directory holding extracted
+/// regular files, and each of its ancestors up to `.`, to the total size of
+/// the files beneath it.
+pub(crate) fn write_size_index(
+    dest: &Path,
+    entries: &[ManifestEntry],
+    output_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut sizes = BTreeMap::new();
+    for entry in entries.iter().filter(|entry| entry.kind == "file") {
+        let path = entry.path.strip_prefix(output_dir).unwrap_or(&entry.path);
+        for dir in path.ancestors().skip(1) {
+            let key = if dir.as_os_str().is_empty() {
+                ".".to_string()
+            } else {
+                dir.to_string_lossy().replace('\\', "/")
+            };
+            *sizes.entry(key).or_insert(0u64) += entry.size;
+        }
+    }
+    let mut out = BufWriter::new(File::create(dest)?);
+    serde_json::to_writer_pretty(&mut out, &sizes)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// The CRC printed by POSIX `cksum` (and by `lsbom`): CRC-32 with the
+/// 0x04C11DB7 polynomial, MSB first, over the data followed by its length.
+pub(crate) struct Cksum {
+    crc: u32,
+    len: u64,
+}
+
+const CKSUM_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+impl Cksum {
+    pub(crate) fn new() -> Self {
+        Self { crc: 0, len: 0 }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.push(byte);
+        }
+        self.len += data.len() as u64;
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.crc = (self.crc << 8) ^ CKSUM_TABLE[((self.crc >> 24) as u8 ^ byte) as usize];
+    }
+
+    pub(crate) fn finish(mut self) -> u32 {
+        let mut len = self.len;
+        while len != 0 {
+            self.push(len as u8);
+            len >>= 8;
+        }
+        !self.crc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cksum_matches_posix_cksum() {
+        // `printf 'hello\n' | cksum` and `cksum < /dev/null`.
+        let mut cksum = Cksum::new();
+        cksum.update(b"hello\n");
+        assert_eq!(cksum.finish(), 3015617425);
+        assert_eq!(Cksum::new().finish(), 4294967295);
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The installer UI files of a product package (background image, welcome,
+//! license, ...), read on their own for previewing an installer's branding
+//! and legal text.
+
+use crate::distribution::{Distribution, InstallerResource};
+use crate::PkgExtractor;
+use log::debug;
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+/// Localisations tried, in order, before any other `.lproj`.
+const PREFERRED_LOCALISATIONS: [&str; 3] = ["en", "English", "Base"];
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// The contents of the `resource` file the Distribution declares, or
+    /// `None` when it declares none or the archive lacks it. An unlocalised
+    /// `Resources/<file>` is preferred, then the English (or base)
+    /// localisation, then any other.
+    pub fn installer_resource(
+        &mut self,
+        resource: InstallerResource,
+    ) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        self.with_xar(|_, xar| {
+            let Some(data) = xar.get_file_data_from_path("Distribution")? else {
+                debug!("No Distribution, so no installer resources");
+                return Ok(None);
+            };
+            let distribution = Distribution::parse(&data)?;
+            let Some(file) = distribution.resource(resource) else {
+                debug!("The Distribution declares no {resource:?}");
+                return Ok(None);
+            };
+
+            let mut candidates = Vec::new();
+            for (path, _) in xar.files()? {
+                let Some(rest) = path.strip_prefix("Resources/") else {
+                    continue;
+                };
+                let rank = if rest == file {
+                    0
+                } else if let Some((lproj, name)) = rest.split_once('/') {
+                    let Some(lang) = lproj.strip_suffix(".lproj") else {
+                        continue;
+                    };
+                    if name != file {
+                        continue;
+                    }
+                    1 + PREFERRED_LOCALISATIONS
+                        .iter()
+                        .position(|preferred| *preferred == lang)
+                        .unwrap_or(PREFERRED_LOCALISATIONS.len())
+                } else {
+                    continue;
+                };
+                candidates.push((rank, path));
+            }
+            candidates.sort();
+            match candidates.into_iter().next() {
+                Some((_, path)) => {
+                    debug!("Reading {resource:?} from {path}");
+                    Ok(xar.get_file_data_from_path(&path)?)
+                }
+                None => {
+                    debug!("{file} ({resource:?}) is not in the archive");
+                    Ok(None)
+                }
+            }
+        })
+    }
+
+    /// Write the `resource` file to `dest`, as found by
+    /// [`Self::installer_resource`]. Returns whether there was one.
+    pub fn extract_installer_resource(
+        &mut self,
+        resource: InstallerResource,
+        dest: &Path,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(data) = self.installer_resource(resource)? else {
+            return Ok(false);
+        };
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(dest, data)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::XarBuilder;
+    use std::io::Cursor;
+
+    #[test]
+    fn extracts_declared_license_and_background() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pkg = XarBuilder::new()
+            .file(
+                "Distribution",
+                r#"<installer-gui-script minSpecVersion="1">
+    <background file="background.png" alignment="bottomleft"/>
+    <license file="License.rtf"/>
+</installer-gui-script>"#,
+            )
+            .file("Resources/background.png", b"\x89PNG".as_slice())
+            .file("Resources/fr.lproj/License.rtf", "{\\rtf1 Licence}")
+            .file("Resources/en.lproj/License.rtf", "{\\rtf1 License}")
+            .build();
+        let mut extractor = PkgExtractor::new(Cursor::new(pkg), None);
+
+        let license = tmp.path().join("preview/License.rtf");
+        assert!(extractor
+            .extract_installer_resource(InstallerResource::License, &license)
+            .unwrap());
+        assert_eq!(fs::read(&license).unwrap(), b"{\\rtf1 License}");
+        assert_eq!(
+            extractor
+                .installer_resource(InstallerResource::Background)
+                .unwrap()
+                .as_deref(),
+            Some(b"\x89PNG".as_slice())
+        );
+        assert!(!extractor
+            .extract_installer_resource(InstallerResource::Welcome, &tmp.path().join("w"))
+            .unwrap());
+    }
+}
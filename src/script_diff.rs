@@ -0,0 +1,281 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Comparing the install scripts of two versions of a package, for
+//! reviewing what a release changes in code that runs as root.
+
+use crate::bom::bom_key;
+use crate::component::{pkg_flavor, read_components};
+use crate::{walk_archives, FileType, PkgExtractor};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{Read, Seek};
+
+/// Lines of unchanged context around each change in a unified diff.
+const CONTEXT: usize = 3;
+
+/// Cells of the quadratic LCS table above which scripts are only reported
+/// as differing (4M cells, 16 MiB), e.g. two 2000-line scripts.
+const MAX_LCS_CELLS: usize = 4 << 20;
+
+/// How one script differs between two package versions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptChange {
+    /// Only the newer package has it.
+    Added,
+    /// Only the older package has it.
+    Removed,
+    /// Both have it, with different contents: the unified diff from the
+    /// older to the newer one, or a `Files ... differ` line for scripts too
+    /// long to diff.
+    Modified(String),
+}
+
+/// A script that changed, named as `extract_scripts` lays it out below
+/// `Scripts/`: `postinstall` in a component package,
+/// `<identifier>/postinstall` in a product package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScriptDiff {
+    pub script: String,
+    pub change: ScriptChange,
+}
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Compare the scripts of this package with those of `newer`, a later
+    /// version of it: every script added, removed or modified, by name.
+    /// Scripts are read in memory; nothing is written to disk.
+    pub fn diff_scripts<R2: Read + Seek + Sized + Debug>(
+        &mut self,
+        newer: &mut PkgExtractor<R2>,
+    ) -> Result<Vec<ScriptDiff>, Box<dyn Error>> {
+        let old = self.scripts()?;
+        let mut new = newer.scripts()?;
+
+        let mut diffs = Vec::new();
+        for (script, before) in old {
+            let change = match new.remove(&script) {
+                None => ScriptChange::Removed,
+                Some(after) if after == before => continue,
+                Some(after) => ScriptChange::Modified(unified_diff(
+                    &script,
+                    &String::from_utf8_lossy(&before),
+                    &String::from_utf8_lossy(&after),
+                )),
+            };
+            diffs.push(ScriptDiff { script, change });
+        }
+        diffs.extend(new.into_keys().map(|script| ScriptDiff {
+            script,
+            change: ScriptChange::Added,
+        }));
+        diffs.sort_by(|a, b| a.script.cmp(&b.script));
+        Ok(diffs)
+    }
+
+    /// The contents of every regular file in the components' `Scripts`.
+    fn scripts(&mut self) -> Result<BTreeMap<String, Vec<u8>>, Box<dyn Error>> {
+        self.with_xar(|_, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            let mut scripts = BTreeMap::new();
+            walk_archives(xar, &components, "Scripts", |component, header, body| {
+                if FileType::from_mode(header.mode()) != FileType::Regular {
+                    return Ok(());
+                }
+                let name = bom_key(header.name());
+                let script = if component.path.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{name}", component.identifier)
+                };
+                let mut contents = Vec::new();
+                body.take(header.file_size()).read_to_end(&mut contents)?;
+                scripts.insert(script, contents);
+                Ok(())
+            })?;
+            Ok(scripts)
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A unified diff (`diff -u` style, with [`CONTEXT`] lines of context)
+/// turning `old` into `new`, both labelled `name`, or just `diff -q`'s
+/// `Files ... differ` when the scripts are too long for its
+/// [`MAX_LCS_CELLS`] table.
+fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_LCS_CELLS {
+        return format!("Files a/{name} and b/{name} differ\n");
+    }
+
+    // Longest common subsequence lengths of every pair of suffixes; install
+    // scripts are usually small enough for the quadratic table.
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // Each op with the old and new line indices it starts at.
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push((Op::Equal, i, j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Delete, i, j));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, i, j));
+            j += 1;
+        }
+    }
+
+    let mut out = format!("--- a/{name}\n+++ b/{name}\n");
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != Op::Equal).collect();
+    let mut k = 0;
+    while k < changes.len() {
+        // Changes closer than twice the context share a hunk.
+        let mut last = k;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT {
+            last += 1;
+        }
+        let start = changes[k].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|op| op.0 != Op::Insert).count();
+        let new_len = hunk.iter().filter(|op| op.0 != Op::Delete).count();
+        let (_, old_start, new_start) = hunk[0];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        ));
+        for &(op, i, j) in hunk {
+            let (prefix, line) = match op {
+                Op::Equal => (' ', a[i]),
+                Op::Delete => ('-', a[i]),
+                Op::Insert => ('+', b[j]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        k = last + 1;
+    }
+    out
+}
+
+/// A hunk header range: 1-based start and length, the start being the
+/// line before an empty range.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{cpio, gzip, package_info, XarBuilder};
+    use std::io::Cursor;
+
+    fn package(scripts: &[(&str, &[u8])]) -> PkgExtractor<Cursor<Vec<u8>>> {
+        let scripts: Vec<_> = scripts
+            .iter()
+            .map(|&(name, body)| (name, 0o100755, body))
+            .collect();
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.scripts"))
+            .file("Payload", gzip(&cpio(&[("./a", 0o100644, b"a")])))
+            .file("Scripts", gzip(&cpio(&scripts)))
+            .build();
+        PkgExtractor::new(Cursor::new(pkg), None)
+    }
+
+    #[test]
+    fn diff_scripts_reports_modified_added_and_removed() {
+        let mut old = package(&[
+            (
+                "./postinstall",
+                b"#!/bin/sh\nset -e\nmkdir -p /opt/app\nchown root /opt/app\nexit 0\n",
+            ),
+            ("./preflight", b"#!/bin/sh\n"),
+            ("./preinstall", b"#!/bin/sh\nexit 0\n"),
+        ]);
+        let mut new = package(&[
+            (
+                "./postinstall",
+                b"#!/bin/sh\nset -e\nmkdir -p /opt/app\nchmod 777 /opt/app\nexit 0\n",
+            ),
+            ("./preinstall", b"#!/bin/sh\nexit 0\n"),
+            ("./preupgrade", b"#!/bin/sh\n"),
+        ]);
+
+        let diffs = old.diff_scripts(&mut new).unwrap();
+
+        assert_eq!(
+            diffs,
+            [
+                ScriptDiff {
+                    script: "postinstall".to_string(),
+                    change: ScriptChange::Modified(
+                        "--- a/postinstall\n+++ b/postinstall\n\
+                         @@ -1,5 +1,5 @@\n \
+                         #!/bin/sh\n \
+                         set -e\n \
+                         mkdir -p /opt/app\n\
+                         -chown root /opt/app\n\
+                         +chmod 777 /opt/app\n \
+                         exit 0\n"
+                            .to_string()
+                    ),
+                },
+                ScriptDiff {
+                    script: "preflight".to_string(),
+                    change: ScriptChange::Removed,
+                },
+                ScriptDiff {
+                    script: "preupgrade".to_string(),
+                    change: ScriptChange::Added,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn long_scripts_are_only_reported_as_differing() {
+        let old: String = (0..2100).map(|i| format!("echo {i}\n")).collect();
+        let new = format!("{old}echo done\n");
+        assert_eq!(
+            unified_diff("postinstall", &old, &new),
+            "Files a/postinstall and b/postinstall differ\n"
+        );
+        assert!(unified_diff("postinstall", "a\n", "b\n").starts_with("--- a/postinstall"));
+    }
+}
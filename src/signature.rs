@@ -0,0 +1,150 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Verifying a package against a detached Ed25519 signature (a `.sig`
+//! shipped next to the `.pkg`), independently of the xar's own signature.
+//!
+//! The signature is over the SHA-256 digest of the package, so checking it
+//! streams the package once instead of holding it in memory: sign what
+//! `openssl dgst -sha256 -binary foo.pkg` outputs.
+
+use crate::PkgExtractor;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// An Ed25519 signature over the SHA-256 digest of the whole `.pkg` file
+/// and the public key to check it with, both raw (32 and 64 bytes), as set
+/// in [`crate::ExtractOptions::detached_signature`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedSignature {
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl DetachedSignature {
+    /// Read the raw signature and public key from the files at `signature`
+    /// and `public_key`.
+    pub fn load(signature: &Path, public_key: &Path) -> Result<Self, Box<dyn Error>> {
+        let read = |path: &Path| {
+            fs::read(path).map_err(|e| format!("Could not read {}: {e}", path.display()))
+        };
+        Ok(Self {
+            public_key: read(public_key)?,
+            signature: read(signature)?,
+        })
+    }
+}
+
+/// Check that `signature` signs what `reader` holds from its position to
+/// its end, leaving it at that position again.
+pub(crate) fn verify<T: Read + Seek>(
+    reader: &mut T,
+    signature: &DetachedSignature,
+) -> Result<(), Box<dyn Error>> {
+    let start = reader.stream_position()?;
+    let mut hasher = Sha256::new();
+    let hashed = io::copy(reader, &mut hasher);
+    reader.seek(SeekFrom::Start(start))?;
+    hashed?;
+
+    UnparsedPublicKey::new(&ED25519, &signature.public_key)
+        .verify(&hasher.finalize(), &signature.signature)
+        .map_err(|_| "package does not match its detached signature")?;
+    Ok(())
+}
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Check that `signature` signs the package, from where the xar starts
+    /// to the end of the reader. [`crate::ExtractOptions::detached_signature`]
+    /// is checked the same way, before the package is first parsed.
+    pub fn verify_detached_signature(
+        &mut self,
+        signature: &DetachedSignature,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut reader = self.take_reader()?;
+        let verified = verify(&mut reader, signature);
+        self.reader = Some(reader);
+        verified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::ExtractOptions;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::io::Cursor;
+
+    fn signed_package() -> (Vec<u8>, DetachedSignature) {
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.signed"))
+            .file("Payload", gzip(&cpio(&[("./file", 0o100644, b"signed")])))
+            .build();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let keys = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let signature = DetachedSignature {
+            public_key: keys.public_key().as_ref().to_vec(),
+            signature: keys.sign(&Sha256::digest(&pkg)).as_ref().to_vec(),
+        };
+        (pkg, signature)
+    }
+
+    #[test]
+    fn detached_signature_gates_extraction() {
+        let (pkg, valid) = signed_package();
+        let mut invalid = valid.clone();
+        invalid.signature[0] ^= 1;
+
+        let extract = |signature: DetachedSignature| {
+            let tmp = tempfile::tempdir().unwrap();
+            let result = PkgExtractor::new(Cursor::new(pkg.clone()), Some(tmp.path().into()))
+                .with_options(ExtractOptions {
+                    detached_signature: Some(signature),
+                    ..Default::default()
+                })
+                .extract();
+            (result, tmp.path().join("file").exists())
+        };
+
+        let (result, written) = extract(valid);
+        result.unwrap();
+        assert!(written);
+
+        let (result, written) = extract(invalid);
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("detached signature"), "{err}");
+        assert!(!written);
+    }
+
+    #[test]
+    fn detached_signature_gates_inspection_too() {
+        let (pkg, mut signature) = signed_package();
+        let options = |signature: &DetachedSignature| ExtractOptions {
+            detached_signature: Some(signature.clone()),
+            ..Default::default()
+        };
+
+        let mut extractor =
+            PkgExtractor::new(Cursor::new(pkg.clone()), None).with_options(options(&signature));
+        assert_eq!(extractor.list_entries().unwrap(), ["./file"]);
+        assert_eq!(extractor.list_entries().unwrap(), ["./file"]);
+
+        signature.signature[0] ^= 1;
+        let mut extractor =
+            PkgExtractor::new(Cursor::new(pkg), None).with_options(options(&signature));
+        let err = extractor.list_entries().unwrap_err();
+        assert!(err.to_string().contains("detached signature"), "{err}");
+        // The reader is kept: verifying again fails the same way.
+        assert!(extractor.verify_detached_signature(&signature).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! SQLite export: one row per payload entry, for `SELECT`-based exploration
+//! of a package.
+
+use crate::component::{pkg_flavor, read_components};
+use crate::{walk_payloads, FileType, PkgExtractor};
+use log::{debug, info};
+use rusqlite::{params, Connection};
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS entries (
+    path TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    mode INTEGER NOT NULL,
+    type TEXT NOT NULL,
+    content BLOB
+)";
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Write the payload of every component into the SQLite database at
+    /// `db_path` (created if needed), one row per entry in an `entries`
+    /// table: `(path, size, mode, type, content)`.
+    ///
+    /// `path` is relative to the package root, `type` is `file`,
+    /// `directory` or `symlink`, and `content` holds a file's bytes, a
+    /// symlink's target, or `NULL` for directories. An entry appearing in
+    /// several components keeps the last one, as on disk. The same filters
+    /// as [`PkgExtractor::extract`] apply; `Scripts` are not included.
+    pub fn extract_to_sqlite(mut self, db_path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let mut conn = Connection::open(db_path.as_ref())?;
+        conn.execute(SCHEMA, [])?;
+        let tx = conn.transaction()?;
+        let mut rows = 0u64;
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            let mut insert = tx.prepare(
+                "INSERT OR REPLACE INTO entries (path, size, mode, type, content)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            walk_payloads(xar, &components, |_, header, body| {
+                let Some(path) = this.archive_path(header)? else {
+                    return Ok(());
+                };
+                let mode = this.options.entry_mode(header.mode());
+                let file_type = FileType::from_mode(mode);
+                let content = match file_type {
+                    FileType::Directory => None,
+                    FileType::Regular | FileType::Symlink => {
+                        let mut content = Vec::new();
+                        body.take(header.file_size()).read_to_end(&mut content)?;
+                        Some(content)
+                    }
+                    FileType::Other => {
+                        debug!("Skipping {file_type:?} entry: {}", header.name());
+                        return Ok(());
+                    }
+                };
+                insert.execute(params![
+                    path.to_string_lossy(),
+                    header.file_size(),
+                    mode,
+                    file_type.as_str(),
+                    content,
+                ])?;
+                rows += 1;
+                Ok(())
+            })
+        })?;
+        tx.commit()?;
+        info!("Wrote {rows} entries to {}", db_path.as_ref().display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::PkgExtractor;
+    use rusqlite::Connection;
+    use std::io::Cursor;
+
+    #[test]
+    fn extract_to_sqlite_stores_queryable_rows() {
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.db"))
+            .file(
+                "Payload",
+                gzip(&cpio(&[
+                    (".", 0o040755, b""),
+                    ("./etc", 0o040755, b""),
+                    ("./etc/motd", 0o100644, b"welcome\n"),
+                    ("./etc/issue", 0o120777, b"motd"),
+                ])),
+            )
+            .build();
+        let tmp = tempfile::tempdir().unwrap();
+        let db = tmp.path().join("pkg.sqlite");
+
+        PkgExtractor::new(Cursor::new(pkg), None)
+            .extract_to_sqlite(&db)
+            .unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        let (content, mode, kind): (Vec<u8>, u32, String) = conn
+            .query_row(
+                "SELECT content, mode, type FROM entries WHERE path = 'etc/motd'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(content, b"welcome\n");
+        assert_eq!(mode & 0o7777, 0o644);
+        assert_eq!(kind, "file");
+
+        let target: Vec<u8> = conn
+            .query_row(
+                "SELECT content FROM entries WHERE path = 'etc/issue' AND type = 'symlink'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(target, b"motd");
+
+        let count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+}
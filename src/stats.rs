@@ -0,0 +1,109 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// What an extraction did, returned by [`crate::PkgExtractor::extract`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractionStats {
+    /// Regular files and symlinks written.
+    pub files: u64,
+    /// Bytes of file contents written.
+    pub bytes: u64,
+    /// Entries deliberately not written: filtered out (size, extension,
+    /// excluded directory, unchanged, ...), of an unsupported type, refused
+    /// as unsafe, naming the payload root itself, or before the entry a
+    /// `resume_from` cursor resumes at. The entries of components the
+    /// cursor lists as done are not counted.
+    pub skipped: u64,
+    /// Entries that could not be written and were skipped because
+    /// `continue_on_file_error` is set.
+    pub failed_files: Vec<FailedFile>,
+    /// Every warning logged, in order; with `warnings_as_errors` the first
+    /// one fails the extraction instead.
+    pub warnings: Vec<Warning>,
+    /// With `delta_baseline`, the regular files written because they are
+    /// new or changed relative to the baseline BOM.
+    pub delta: Vec<PathBuf>,
+    /// With `per_component_dirs`, the directory (below the output
+    /// directory) each component identifier was extracted into.
+    pub component_dirs: BTreeMap<String, String>,
+    /// The directory extracted into: the output directory, or with
+    /// `avoid_clobber_dir` the suffixed one used instead when it existed.
+    pub output_dir: PathBuf,
+    /// How far extraction got, updated as each entry is done; pass it back
+    /// as `ExtractOptions::resume_from` to carry on from there. A failed run
+    /// returns no stats: save the cursor as it moves with
+    /// [`crate::PkgExtractor::with_cursor_callback`] instead.
+    pub cursor: Option<ResumeCursor>,
+}
+
+/// A position in a package's payloads, at entry granularity. Components
+/// are named by identifier, so the cursor stays valid whatever order they
+/// are extracted in (`with_component_order`, ...). An entry interrupted
+/// half-way is redone from scratch, and a component's Scripts are
+/// extracted again when resuming inside it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeCursor {
+    /// Identifiers of the components fully extracted, Scripts included.
+    pub done: Vec<String>,
+    /// Identifier of the component whose Payload was being extracted;
+    /// empty for a bare cpio (`extract_cpio_stream`).
+    pub component: String,
+    /// How many entries of that Payload are done, counting every cpio
+    /// header, `.` included.
+    pub entry: u64,
+}
+
+/// An entry [`ExtractionStats::failed_files`] reports as not written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedFile {
+    /// Where the entry would have been written.
+    pub path: PathBuf,
+    /// Why writing it failed.
+    pub error: String,
+}
+
+/// A suspicious but recoverable condition met while extracting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// The message that was logged.
+    pub message: String,
+}
+
+/// What a [`Warning`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A component without a Payload.
+    MissingPayload,
+    /// A component of a product package that failed to extract, the
+    /// others being extracted regardless.
+    ComponentFailed,
+    /// A Distribution that could not be parsed.
+    UnparsableDistribution,
+    /// A component the Distribution declares but the archive lacks.
+    MissingComponent,
+    /// An entry or identifier refused because it would resolve outside
+    /// the output directory.
+    UnsafeName,
+    /// A cpio archive without its end-of-archive trailer.
+    MissingTrailer,
+    /// A component started over after a transient I/O error.
+    Retry,
+    /// An entry skipped by `continue_on_file_error`.
+    FileFailed,
+    /// A payload entry disagreeing with the component's BOM under
+    /// `bom_layout`, or a component without a BOM.
+    BomMismatch,
+    /// A file left out of a report because it could not be parsed, e.g.
+    /// a Mach-O with a malformed code signature, or an AppleDouble `._name`
+    /// file left unpaired.
+    UnparsableFile,
+}
@@ -0,0 +1,168 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Tarball export: stream the payload entries into a [`tar::Builder`]
+//! instead of materialising them on disk.
+
+use crate::component::{pkg_flavor, read_components};
+use crate::{walk_payloads, FileType, PkgExtractor};
+use cpio_archive::CpioHeader;
+use log::debug;
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{self, Read, Seek, Write};
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Write the payload of every component as a tarball to `writer` and
+    /// return the writer once the archive is finished.
+    ///
+    /// Modes, ownership, mtimes and symlinks are preserved; `Scripts` are
+    /// not included. The size filters and `strip_leading_slash` apply as for
+    /// an on-disk extraction, and entries that would escape the archive root
+    /// are refused the same way.
+    pub fn extract_to_tar<W: Write>(mut self, writer: W) -> Result<W, Box<dyn Error>> {
+        let mut builder = tar::Builder::new(writer);
+        self.extract_into_tar(&mut builder)?;
+        Ok(builder.into_inner()?)
+    }
+
+    /// Append the payload of every component to a caller-owned tar builder,
+    /// so package contents can be interleaved with other entries (a README,
+    /// several packages in one tarball, ...). The builder is not finished;
+    /// that is left to the caller.
+    ///
+    /// Entries are written exactly as [`PkgExtractor::extract_to_tar`] does.
+    pub fn extract_into_tar<W: Write>(
+        &mut self,
+        builder: &mut tar::Builder<W>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            walk_payloads(xar, &components, |_, header, body| {
+                this.append_tar_entry(builder, header, body)
+            })
+        })
+    }
+
+    fn append_tar_entry<W: Write>(
+        &mut self,
+        builder: &mut tar::Builder<W>,
+        header: &dyn CpioHeader,
+        body: &mut dyn Read,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(path) = self.archive_path(header)? else {
+            return Ok(());
+        };
+        let name = header.name();
+        let mode = self.options.entry_mode(header.mode());
+        let file_type = FileType::from_mode(mode);
+
+        let mut tar_header = tar::Header::new_gnu();
+        tar_header.set_mode(mode & 0o7777);
+        tar_header.set_uid(header.uid() as u64);
+        tar_header.set_gid(header.gid() as u64);
+        tar_header.set_mtime(header.mtime() as u64);
+
+        match file_type {
+            FileType::Directory => {
+                tar_header.set_entry_type(tar::EntryType::Directory);
+                tar_header.set_size(0);
+                builder.append_data(&mut tar_header, &path, io::empty())?;
+            }
+            FileType::Regular => {
+                tar_header.set_entry_type(tar::EntryType::Regular);
+                tar_header.set_size(header.file_size());
+                builder.append_data(&mut tar_header, &path, body.take(header.file_size()))?;
+            }
+            FileType::Symlink => {
+                let mut target = Vec::new();
+                body.take(header.file_size()).read_to_end(&mut target)?;
+                let target = String::from_utf8(target)
+                    .map_err(|e| format!("invalid utf-8 symlink target for {name}: {e}"))?;
+                tar_header.set_entry_type(tar::EntryType::Symlink);
+                tar_header.set_size(0);
+                builder.append_link(&mut tar_header, &path, target)?;
+            }
+            FileType::Other => {
+                debug!("Skipping {file_type:?} entry: {name}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::PkgExtractor;
+    use std::collections::HashMap;
+    use std::io::{Cursor, Read};
+
+    fn component_pkg(identifier: &str, files: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        XarBuilder::new()
+            .file("PackageInfo", package_info(identifier))
+            .file("Payload", gzip(&cpio(files)))
+            .build()
+    }
+
+    #[test]
+    fn extract_into_tar_combines_two_packages() {
+        let first = component_pkg(
+            "com.example.first",
+            &[
+                (".", 0o040755, b""),
+                ("./bin", 0o040755, b""),
+                ("./bin/tool", 0o100755, b"#!/bin/sh\n"),
+                ("./bin/alias", 0o120777, b"tool"),
+            ],
+        );
+        let second = component_pkg(
+            "com.example.second",
+            &[("./share/doc.txt", 0o100644, b"docs")],
+        );
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut readme = tar::Header::new_gnu();
+        readme.set_size(6);
+        readme.set_mode(0o644);
+        builder
+            .append_data(&mut readme, "README", &b"hello\n"[..])
+            .unwrap();
+        for pkg in [first, second] {
+            PkgExtractor::new(Cursor::new(pkg), None)
+                .extract_into_tar(&mut builder)
+                .unwrap();
+        }
+        let tarball = builder.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(Cursor::new(tarball));
+        let mut entries = HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let link = entry
+                .link_name()
+                .unwrap()
+                .map(|l| l.to_string_lossy().into_owned());
+            let mode = entry.header().mode().unwrap();
+            let mut body = Vec::new();
+            entry.read_to_end(&mut body).unwrap();
+            entries.insert(path, (mode, link, body));
+        }
+
+        let mut names: Vec<_> = entries.keys().cloned().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["README", "bin", "bin/alias", "bin/tool", "share/doc.txt"]
+        );
+        assert_eq!(entries["bin/tool"].0, 0o755);
+        assert_eq!(entries["bin/tool"].2, b"#!/bin/sh\n");
+        assert_eq!(entries["bin/alias"].1.as_deref(), Some("tool"));
+        assert_eq!(entries["share/doc.txt"].2, b"docs");
+    }
+}
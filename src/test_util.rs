@@ -0,0 +1,272 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Fixture builders shared by the unit tests: hand-rolled ODC cpio entries
+//! and a minimal xar writer, so tests can assemble component and product
+//! packages in memory instead of downloading real installers.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Build one ODC ("070707") cpio header-plus-body for the given entry.
+/// `name` must be valid UTF-8; the NUL terminator is added here. For
+/// symlinks, pass the link target in `body`.
+pub fn odc_entry(name: &str, mode: u32, body: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(b"070707");
+    for _ in 0..2 {
+        buf.extend_from_slice(b"000000"); // dev, ino
+    }
+    buf.extend_from_slice(format!("{mode:06o}").as_bytes());
+    buf.extend_from_slice(b"000000"); // uid
+    buf.extend_from_slice(b"000000"); // gid
+    buf.extend_from_slice(b"000001"); // nlink
+    buf.extend_from_slice(b"000000"); // rdev
+    buf.extend_from_slice(b"00000000000"); // mtime
+    let name_bytes = name.as_bytes();
+    let namesize = name_bytes.len() + 1; // includes trailing NUL
+    buf.extend_from_slice(format!("{namesize:06o}").as_bytes());
+    buf.extend_from_slice(format!("{:011o}", body.len()).as_bytes());
+    buf.extend_from_slice(name_bytes);
+    buf.push(0);
+    buf.extend_from_slice(body);
+    buf
+}
+
+pub fn trailer() -> Vec<u8> {
+    odc_entry("TRAILER!!!", 0, b"")
+}
+
+/// Concatenate `(name, mode, body)` entries into a complete cpio archive,
+/// trailer included.
+pub fn cpio(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, mode, body) in entries {
+        out.extend(odc_entry(name, *mode, body));
+    }
+    out.extend(trailer());
+    out
+}
+
+/// Gzip `data`, the framing Apple uses for `Scripts` and legacy `Payload`s.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = libflate::gzip::Encoder::new(Vec::new()).unwrap();
+    encoder.write_all(data).unwrap();
+    encoder.finish().into_result().unwrap()
+}
+
+/// A minimal `PackageInfo` document declaring `identifier`.
+pub fn package_info(identifier: &str) -> Vec<u8> {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<pkg-info format-version="2" identifier="{identifier}" version="1.0" auth="root"/>
+"#
+    )
+    .into_bytes()
+}
+
+/// A `Distribution` script declaring one `<pkg-ref>` per `(id, sub_pkg)`,
+/// each pointing at its archive with the usual `#sub_pkg` reference.
+pub fn distribution(pkg_refs: &[(&str, &str)]) -> Vec<u8> {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<installer-gui-script minSpecVersion=\"1\">\n",
+    );
+    for (id, sub_pkg) in pkg_refs {
+        xml.push_str(&format!(
+            "    <choice id=\"{id}\"><pkg-ref id=\"{id}\"/></choice>\n    \
+             <pkg-ref id=\"{id}\" version=\"1.0\">#{sub_pkg}</pkg-ref>\n"
+        ));
+    }
+    xml.push_str("</installer-gui-script>\n");
+    xml.into_bytes()
+}
+
+/// Assembles an uncompressed-heap xar archive from `(path, bytes)` pairs.
+/// Intermediate directories are synthesised from the `/`-separated paths,
+/// so `.file("a.pkg/Payload", ..)` yields an `a.pkg` directory entry. Adding
+/// a top-level `Distribution` file turns the result into a product package.
+#[derive(Default)]
+pub struct XarBuilder {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct Node {
+    data: Option<Vec<u8>>,
+    children: BTreeMap<String, Node>,
+}
+
+impl XarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(mut self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.files.push((path.to_string(), data.into()));
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let mut root = Node::default();
+        for (path, data) in self.files {
+            let mut node = &mut root;
+            for part in path.split('/') {
+                node = node.children.entry(part.to_string()).or_default();
+            }
+            node.data = Some(data);
+        }
+
+        // The heap starts with the (unchecked) 20-byte TOC checksum slot.
+        let mut heap = vec![0u8; 20];
+        let mut toc = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xar><toc>\
+             <creation-time>2026-01-01T00:00:00</creation-time>\
+             <checksum style=\"sha1\"><offset>0</offset><size>20</size></checksum>",
+        );
+        let mut next_id = 1;
+        for (name, node) in &root.children {
+            write_node(name, node, &mut next_id, &mut heap, &mut toc);
+        }
+        toc.push_str("</toc></xar>");
+
+        let mut encoder = libflate::zlib::Encoder::new(Vec::new()).unwrap();
+        encoder.write_all(toc.as_bytes()).unwrap();
+        let toc_compressed = encoder.finish().into_result().unwrap();
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"xar!");
+        out.extend_from_slice(&28u16.to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(toc_compressed.len() as u64).to_be_bytes());
+        out.extend_from_slice(&(toc.len() as u64).to_be_bytes());
+        out.extend_from_slice(&1u32.to_be_bytes()); // sha1
+        out.extend(toc_compressed);
+        out.extend(heap);
+        out
+    }
+}
+
+fn write_node(name: &str, node: &Node, next_id: &mut u64, heap: &mut Vec<u8>, toc: &mut String) {
+    let id = *next_id;
+    *next_id += 1;
+    toc.push_str(&format!("<file id=\"{id}\"><name>{name}</name>"));
+    match &node.data {
+        Some(data) => {
+            toc.push_str(&format!(
+                "<type>file</type><data><offset>{}</offset><size>{len}</size>\
+                 <length>{len}</length><encoding style=\"application/octet-stream\"/>\
+                 <extracted-checksum style=\"sha1\">0</extracted-checksum>\
+                 <archived-checksum style=\"sha1\">0</archived-checksum></data>",
+                heap.len(),
+                len = data.len()
+            ));
+            heap.extend_from_slice(data);
+        }
+        None => toc.push_str("<type>directory</type>"),
+    }
+    for (child_name, child) in &node.children {
+        write_node(child_name, child, next_id, heap, toc);
+    }
+    toc.push_str("</file>");
+}
+
+/// Assemble a minimal BOM whose `Paths` tree is one leaf listing
+/// `(path, mode, size, checksum, link target)` entries. Paths are
+/// `lsbom`-style (`.`, `./usr`, ...) and must come after their parent.
+pub fn bom(entries: &[(&str, u32, u32, u32, Option<&str>)]) -> Vec<u8> {
+    // Block 0 is the null block.
+    let mut blocks: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut ids = BTreeMap::new();
+    let mut leaf_indices = Vec::new();
+    for (i, (path, mode, size, checksum, target)) in entries.iter().enumerate() {
+        let id = i as u32 + 1;
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (ids[parent], name),
+            None => (0, *path),
+        };
+        ids.insert(path.to_string(), id);
+
+        let kind: u8 = match mode & 0o170000 {
+            0o100000 => 1,
+            0o040000 => 2,
+            0o120000 => 3,
+            _ => 4,
+        };
+        let mut info2 = vec![kind, 1];
+        info2.extend_from_slice(&3u16.to_be_bytes()); // architecture
+        info2.extend_from_slice(&(*mode as u16).to_be_bytes());
+        info2.extend_from_slice(&0u32.to_be_bytes()); // uid
+        info2.extend_from_slice(&80u32.to_be_bytes()); // gid
+        info2.extend_from_slice(&0u32.to_be_bytes()); // mtime
+        info2.extend_from_slice(&size.to_be_bytes());
+        info2.push(1);
+        info2.extend_from_slice(&checksum.to_be_bytes());
+        let target = target.map(|t| format!("{t}\0")).unwrap_or_default();
+        info2.extend_from_slice(&(target.len() as u32).to_be_bytes());
+        info2.extend_from_slice(target.as_bytes());
+        blocks.push(info2);
+
+        let mut info1 = id.to_be_bytes().to_vec();
+        info1.extend_from_slice(&(blocks.len() as u32 - 1).to_be_bytes());
+        blocks.push(info1);
+
+        let mut file = parent.to_be_bytes().to_vec();
+        file.extend_from_slice(name.as_bytes());
+        file.push(0);
+        blocks.push(file);
+
+        let n = blocks.len() as u32;
+        leaf_indices.push((n - 2, n - 1));
+    }
+
+    let mut leaf = 1u16.to_be_bytes().to_vec();
+    leaf.extend_from_slice(&(leaf_indices.len() as u16).to_be_bytes());
+    leaf.extend_from_slice(&[0; 8]); // forward, backward
+    for (info1, file) in leaf_indices {
+        leaf.extend_from_slice(&info1.to_be_bytes());
+        leaf.extend_from_slice(&file.to_be_bytes());
+    }
+    blocks.push(leaf);
+
+    let mut tree = b"tree".to_vec();
+    tree.extend_from_slice(&1u32.to_be_bytes());
+    tree.extend_from_slice(&(blocks.len() as u32 - 1).to_be_bytes());
+    tree.extend_from_slice(&4096u32.to_be_bytes());
+    tree.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    tree.push(0);
+    blocks.push(tree);
+    let tree_id = blocks.len() as u32 - 1;
+
+    let mut data = vec![0u8; 32];
+    let mut pointers = Vec::new();
+    for block in &blocks {
+        let offset = if block.is_empty() { 0 } else { data.len() };
+        pointers.push((offset as u32, block.len() as u32));
+        data.extend_from_slice(block);
+    }
+    let index_offset = data.len() as u32;
+    data.extend_from_slice(&(pointers.len() as u32).to_be_bytes());
+    for (offset, len) in pointers {
+        data.extend_from_slice(&offset.to_be_bytes());
+        data.extend_from_slice(&len.to_be_bytes());
+    }
+    let index_len = data.len() as u32 - index_offset;
+    let vars_offset = data.len() as u32;
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&tree_id.to_be_bytes());
+    data.push(5);
+    data.extend_from_slice(b"Paths");
+    let vars_len = data.len() as u32 - vars_offset;
+
+    data[..8].copy_from_slice(b"BOMStore");
+    data[8..12].copy_from_slice(&1u32.to_be_bytes());
+    data[12..16].copy_from_slice(&(blocks.len() as u32).to_be_bytes());
+    data[16..20].copy_from_slice(&index_offset.to_be_bytes());
+    data[20..24].copy_from_slice(&index_len.to_be_bytes());
+    data[24..28].copy_from_slice(&vars_offset.to_be_bytes());
+    data[28..32].copy_from_slice(&vars_len.to_be_bytes());
+    data
+}
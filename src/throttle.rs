@@ -0,0 +1,46 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Token bucket used to cap extraction throughput, see
+//! [`crate::ExtractOptions::rate_limit`].
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Holds at most one second's worth of tokens, so short pauses can be caught
+/// up on but sustained throughput never exceeds `bytes_per_sec`. The bucket
+/// starts empty, which makes the cap hold from the very first byte.
+pub(crate) struct RateLimiter {
+    bytes_per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1) as f64,
+            tokens: 0.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Account for `bytes` just written, sleeping until the bucket is no
+    /// longer in debt.
+    pub(crate) fn consume(&mut self, bytes: usize) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.bytes_per_sec).min(self.bytes_per_sec);
+        self.last_refill = now;
+
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            thread::sleep(Duration::from_secs_f64(-self.tokens / self.bytes_per_sec));
+            self.tokens = 0.0;
+            self.last_refill = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Piping regular files through an external command on their way to disk.
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// An external command regular files are piped through (contents on
+/// stdin, replacement on stdout) before being written, set as
+/// [`crate::ExtractOptions::transform`].
+///
+/// A file over `max_size`, a command that cannot be started or one exiting
+/// unsuccessfully fails that file, which `continue_on_file_error` then
+/// skips like any other write failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Only transform files with one of these extensions (with or without
+    /// the dot, case-insensitive); every regular file when empty.
+    pub extensions: Vec<String>,
+    /// Largest file, in bytes, handed to the command.
+    pub max_size: u64,
+}
+
+impl TransformCommand {
+    /// Run `program` with `args` on every regular file of at most 64 MiB.
+    pub fn new(program: impl Into<String>, args: &[&str]) -> Self {
+        Self {
+            program: program.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            extensions: Vec::new(),
+            max_size: 64 * 1024 * 1024,
+        }
+    }
+
+    pub(crate) fn applies_to(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let Some(ext) = path.extension() else {
+            return false;
+        };
+        self.extensions
+            .iter()
+            .any(|wanted| ext.eq_ignore_ascii_case(wanted.trim_start_matches('.')))
+    }
+
+    /// The command's output for the `size` bytes read from `body`.
+    pub(crate) fn run(&self, body: &mut dyn Read, size: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        if size > self.max_size {
+            return Err(format!(
+                "{size} bytes is over the {}-byte limit for {}",
+                self.max_size, self.program
+            )
+            .into());
+        }
+        let mut input = Vec::with_capacity(size as usize);
+        body.take(size).read_to_end(&mut input)?;
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Running {}: {e}", self.program))?;
+        // Feed stdin from another thread so a command writing output before
+        // it has read all its input cannot deadlock against us.
+        let mut stdin = child.stdin.take().ok_or("no stdin for transform")?;
+        let feeder = std::thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output()?;
+        // A command that exits without reading everything breaks the pipe;
+        // its exit status below is what matters.
+        let _ = feeder.join();
+
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed ({}): {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{ExtractOptions, PkgExtractor};
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn transform_pipes_matching_files_through_the_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cpio = cpio(&[
+            ("./notes.txt", 0o100644, b"hello world\n"),
+            ("./data.bin", 0o100644, b"untouched"),
+            ("./big.txt", 0o100644, &[b'a'; 64]),
+            ("./after.txt", 0o100644, b"still here\n"),
+        ]);
+        let mut command = TransformCommand::new("tr", &["a-z", "A-Z"]);
+        command.extensions = vec!["txt".into()];
+        command.max_size = 32;
+
+        let mut extractor =
+            PkgExtractor::new(Cursor::new(Vec::new()), Some(tmp.path().to_path_buf()))
+                .with_options(ExtractOptions {
+                    transform: Some(command),
+                    continue_on_file_error: true,
+                    ..Default::default()
+                });
+        extractor.extract_cpio(&cpio, tmp.path()).unwrap();
+
+        let root = tmp.path();
+        assert_eq!(fs::read(root.join("notes.txt")).unwrap(), b"HELLO WORLD\n");
+        assert_eq!(fs::read(root.join("data.bin")).unwrap(), b"untouched");
+        assert_eq!(fs::read(root.join("after.txt")).unwrap(), b"STILL HERE\n");
+        assert!(!root.join("big.txt").exists());
+        let failed = &extractor.stats().failed_files;
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].path, root.join("big.txt"));
+    }
+
+    #[test]
+    fn failing_command_fails_the_file() {
+        let mut body: &[u8] = b"input";
+        let err = TransformCommand::new("sh", &["-c", "echo nope >&2; exit 3"])
+            .run(&mut body, 5)
+            .unwrap_err();
+        assert!(err.to_string().contains("nope"), "{err}");
+    }
+}
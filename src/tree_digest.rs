@@ -0,0 +1,247 @@
+// Copyright (C) 2026 Thibault Saunier <tsaunier@igalia.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A fingerprint of the tree a package extracts to that is the same on
+//! every platform, for CI jobs comparing extractions across operating
+//! systems. See [`PkgExtractor::tree_digest`].
+//!
+//! It is computed from the payloads, never from the disk, and every entry
+//! is first put in a canonical form:
+//!
+//! - **Paths** are relative to the payload root. A leading `./` or `/` is
+//!   dropped, `\` counts as a separator (it is one on Windows), and empty
+//!   and `.` components are removed, so `./a//b/./c` and `a\b\c` are both
+//!   `a/b/c`. Case is kept as is.
+//! - **Order** does not matter: entries are hashed sorted by path,
+//!   bytewise. When several entries have the same canonical path, the
+//!   last one wins, as it would on disk.
+//! - **Modes** keep only what every platform can represent: whether a
+//!   regular file is executable (any of its `0o111` bits set). Other
+//!   permission bits, setuid/setgid/sticky, ownership and mtimes are left
+//!   out.
+//! - **Symlinks** count by their target (with `\` read as `/`) whether or
+//!   not the platform could create them, so skipping them on Windows does
+//!   not change the digest.
+//! - **Devices, FIFOs and sockets**, and paths with a `..` component, are
+//!   never extracted, so they are left out. So are the entries
+//!   `exclude_dirs` and `ignore` filter out.
+//!
+//! Each entry is then encoded as the path length (u64, little-endian), the
+//! path bytes, a kind byte (`d`, `f`, `x` for an executable file, `l`), and
+//! the SHA-256 of the file contents or symlink target (all zeros for a
+//! directory). The digest is the hex SHA-256 of those records.
+
+use crate::component::{pkg_flavor, read_components};
+use crate::{walk_payloads, FileType, PkgExtractor};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Debug;
+use std::io::{self, Read, Seek, Write};
+
+/// One entry in canonical form: its kind byte and content hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CanonicalEntry {
+    pub kind: u8,
+    pub hash: [u8; 32],
+}
+
+/// `name` as a canonical path, or `None` for the payload root and for the
+/// paths extraction refuses because of a `..` component.
+pub(crate) fn canonical_path(name: &str) -> Option<String> {
+    let parts: Vec<&str> = name
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    (!parts.is_empty() && !parts.contains(&"..")).then(|| parts.join("/"))
+}
+
+/// The canonical form of an entry of cpio mode `mode`, whose body is read
+/// from `body`; `None` for the entries that are never extracted.
+pub(crate) fn canonical_entry(
+    mode: u32,
+    body: &mut dyn Read,
+) -> io::Result<Option<CanonicalEntry>> {
+    let mut hasher = Sha256::new();
+    let kind = match FileType::from_mode(mode) {
+        FileType::Directory => {
+            return Ok(Some(CanonicalEntry {
+                kind: b'd',
+                hash: [0; 32],
+            }))
+        }
+        FileType::Regular => {
+            io::copy(body, &mut HashWriter(&mut hasher))?;
+            if mode & 0o111 != 0 {
+                b'x'
+            } else {
+                b'f'
+            }
+        }
+        FileType::Symlink => {
+            let mut target = Vec::new();
+            body.read_to_end(&mut target)?;
+            for b in &mut target {
+                if *b == b'\\' {
+                    *b = b'/';
+                }
+            }
+            hasher.update(&target);
+            b'l'
+        }
+        FileType::Other => return Ok(None),
+    };
+    Ok(Some(CanonicalEntry {
+        kind,
+        hash: hasher.finalize().into(),
+    }))
+}
+
+/// The hex digest of canonical entries, keyed by canonical path.
+pub(crate) fn digest(entries: &BTreeMap<String, CanonicalEntry>) -> String {
+    let mut hasher = Sha256::new();
+    for (path, entry) in entries {
+        hasher.update((path.len() as u64).to_le_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update([entry.kind]);
+        hasher.update(entry.hash);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek + Sized + Debug> PkgExtractor<R> {
+    /// Hex SHA-256 fingerprint of the tree the package extracts to, the
+    /// same on every platform: see the [module documentation](self) for
+    /// the canonical form hashed. Nothing is written to disk.
+    pub fn tree_digest(&mut self) -> Result<String, Box<dyn Error>> {
+        self.with_xar(|this, xar| {
+            let flavor = pkg_flavor(xar)?;
+            let components = read_components(xar, flavor)?;
+            let mut entries = BTreeMap::new();
+            walk_payloads(xar, &components, |_, header, body| {
+                if this.options.excludes(header.name(), header.mode()) {
+                    return Ok(());
+                }
+                let Some(path) = canonical_path(header.name()) else {
+                    return Ok(());
+                };
+                let mut body = body.take(header.file_size());
+                if let Some(entry) = canonical_entry(header.mode(), &mut body)? {
+                    entries.insert(path, entry);
+                }
+                Ok(())
+            })?;
+            Ok(digest(&entries))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{cpio, gzip, package_info, XarBuilder};
+    use std::io::Cursor;
+
+    fn package(entries: &[(&str, u32, &[u8])]) -> PkgExtractor<Cursor<Vec<u8>>> {
+        let pkg = XarBuilder::new()
+            .file("PackageInfo", package_info("com.example.digest"))
+            .file("Payload", gzip(&cpio(entries)))
+            .build();
+        PkgExtractor::new(Cursor::new(pkg), None)
+    }
+
+    #[test]
+    fn canonical_path_normalizes_separators_and_dots() {
+        assert_eq!(
+            canonical_path("./usr/bin/foo").as_deref(),
+            Some("usr/bin/foo")
+        );
+        assert_eq!(
+            canonical_path("/usr//bin/./foo/").as_deref(),
+            Some("usr/bin/foo")
+        );
+        assert_eq!(
+            canonical_path("usr\\bin\\foo").as_deref(),
+            Some("usr/bin/foo")
+        );
+        assert_eq!(canonical_path("Usr/Bin").as_deref(), Some("Usr/Bin"));
+        for root in ["", ".", "./", "/", "./.", "..", "a/../../b"] {
+            assert_eq!(canonical_path(root), None, "{root:?}");
+        }
+    }
+
+    #[test]
+    fn canonical_entry_keeps_only_portable_metadata() {
+        let entry = |mode: u32, body: &[u8]| canonical_entry(mode, &mut &body[..]).unwrap();
+
+        assert_eq!(entry(0o100644, b"a"), entry(0o100600, b"a"));
+        assert_eq!(entry(0o104755, b"a"), entry(0o100700, b"a"));
+        assert_eq!(entry(0o100755, b"a").unwrap().kind, b'x');
+        assert_eq!(entry(0o100644, b"a").unwrap().kind, b'f');
+        assert_ne!(entry(0o100644, b"a"), entry(0o100644, b"b"));
+        assert_eq!(entry(0o040700, b""), entry(0o041777, b""));
+        assert_eq!(entry(0o120777, b"..\\lib"), entry(0o120755, b"../lib"));
+        assert_eq!(entry(0o020644, b""), None);
+    }
+
+    #[test]
+    fn tree_digest_is_stable_across_platform_differences() {
+        let digest = package(&[
+            ("./bin", 0o040755, b""),
+            ("./bin/tool", 0o100755, b"#!/bin/sh\n"),
+            ("./lib", 0o040755, b""),
+            ("./lib/libfoo.dylib", 0o100644, b"dylib"),
+            ("./lib/current", 0o120777, b"libfoo.dylib"),
+        ])
+        .tree_digest()
+        .unwrap();
+        assert_eq!(
+            digest,
+            "2eb7985cd69d564da16281804a6e4b4dbcf6efb62b205c107a763f68640afba2"
+        );
+
+        // Same tree: other order, spelling, permissions and a device.
+        let same = package(&[
+            ("lib\\current", 0o120755, b"libfoo.dylib"),
+            ("/lib//libfoo.dylib", 0o100600, b"dylib"),
+            ("./bin/./tool", 0o100700, b"#!/bin/sh\n"),
+            ("./dev/null", 0o020666, b""),
+            ("./lib/", 0o040700, b""),
+            ("./bin", 0o040755, b""),
+        ])
+        .tree_digest()
+        .unwrap();
+        assert_eq!(same, digest);
+
+        let not_executable = package(&[
+            ("./bin", 0o040755, b""),
+            ("./bin/tool", 0o100644, b"#!/bin/sh\n"),
+            ("./lib", 0o040755, b""),
+            ("./lib/libfoo.dylib", 0o100644, b"dylib"),
+            ("./lib/current", 0o120777, b"libfoo.dylib"),
+        ])
+        .tree_digest()
+        .unwrap();
+        assert_ne!(not_executable, digest);
+    }
+}
@@ -0,0 +1,113 @@
+//! Tests running the `pkg-extractor` binary on the small package in
+//! `tests/fixtures/simple.pkg`.
+
+use std::process::Command;
+
+const SIMPLE_PKG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/simple.pkg");
+
+#[cfg(feature = "tar")]
+#[test]
+fn tar_to_stdout_streams_a_readable_tarball() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pkg-extractor"))
+        .args([SIMPLE_PKG, "--tar", "-"])
+        .env("RUST_LOG", "debug")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let mut archive = tar::Archive::new(std::io::Cursor::new(output.stdout));
+    let mut names: Vec<String> = archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    names.sort();
+    assert_eq!(names, ["bin", "bin/alias", "bin/tool", "share/doc.txt"]);
+}
+
+#[test]
+fn summary_file_reports_the_extraction() {
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    let summary = tmp.path().join("summary.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_pkg-extractor"))
+        .arg(SIMPLE_PKG)
+        .arg("-o")
+        .arg(&out)
+        .args(["--exclude-dir", "share"])
+        .arg("--summary-file")
+        .arg(&summary)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(summary).unwrap()).unwrap();
+    assert_eq!(summary["status"], "ok");
+    assert_eq!(summary["error"], serde_json::Value::Null);
+    assert_eq!(summary["files"], 2);
+    assert_eq!(
+        summary["bytes"],
+        std::fs::metadata(out.join("bin/tool")).unwrap().len()
+    );
+    // `share` and the payload's `.` root entry.
+    assert_eq!(summary["skipped"], 2);
+    assert_eq!(summary["failed"], 0);
+    assert_eq!(summary["warnings"], serde_json::json!([]));
+    assert!(summary["elapsed_secs"].as_f64().unwrap() >= 0.0);
+    assert_eq!(summary["output_dir"], out.to_str().unwrap());
+}
+
+#[test]
+fn summary_file_reports_a_failed_run() {
+    let tmp = tempfile::tempdir().unwrap();
+    let out = tmp.path().join("out");
+    std::fs::create_dir(&out).unwrap();
+    std::fs::write(out.join("mine"), b"mine").unwrap();
+    let summary = tmp.path().join("summary.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_pkg-extractor"))
+        .arg(SIMPLE_PKG)
+        .arg("-o")
+        .arg(&out)
+        .arg("--require-empty-output")
+        .arg("--summary-file")
+        .arg(&summary)
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(summary).unwrap()).unwrap();
+    assert_eq!(summary["status"], "error");
+    assert!(summary["error"].as_str().unwrap().contains("is not empty"));
+    assert_eq!(summary["files"], serde_json::Value::Null);
+    assert_eq!(summary["output_dir"], serde_json::Value::Null);
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn summary_file_reports_a_tar_run() {
+    let tmp = tempfile::tempdir().unwrap();
+    let summary = tmp.path().join("summary.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_pkg-extractor"))
+        .arg(SIMPLE_PKG)
+        .arg("--tar")
+        .arg(tmp.path().join("out.tar"))
+        .arg("--summary-file")
+        .arg(&summary)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(summary).unwrap()).unwrap();
+    assert_eq!(summary["status"], "ok");
+    assert_eq!(summary["files"], serde_json::Value::Null);
+}